@@ -0,0 +1,109 @@
+// holds a target speed with the reference cruise control policy, a smoke test for the closed loop.
+// telemetry comes from shared memory (the game or ets2_emulator) or a replayed recording.
+// usage: cruise_control [--name NAME | --replay PATH] [--setpoint limit|cruise|KMH] [--kp P] [--ki I] [--kd D]
+//                       [--max-throttle T] [--max-brake B] [--mock]
+type CResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[cfg(not(target_os = "linux"))]
+fn main() -> CResult<()> {
+    println!("cruise control requires linux, posix shared memory and uinput");
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn main() -> CResult<()> {
+    use control::control_loop::{ControlLoop, CruiseControl, Limits, PidGains, Setpoint};
+    use control::synchronization::Synchronization;
+    use control::telemetry::{ReplaySpeed, SelectGame, Telemetry};
+    use control::virtual_controller::{MockBackend, OutputBackend};
+    use control::{ServiceType, Services};
+
+    let mut name = None;
+    let mut replay = None;
+    let mut setpoint = Setpoint::SpeedLimit;
+    let mut gains = PidGains::default();
+    let mut limits = Limits::default();
+    let mut mock = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        if flag == "--mock" {
+            mock = true;
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", flag))?;
+
+        match flag.as_str() {
+            "--name" => name = Some(value),
+            "--replay" => replay = Some(value),
+            "--setpoint" => {
+                setpoint = match value.as_str() {
+                    "limit" => Setpoint::SpeedLimit,
+                    "cruise" => Setpoint::Cruise,
+                    kmh => Setpoint::Fixed(kmh.parse::<f32>()? / 3.6),
+                }
+            }
+            "--kp" => gains.kp = value.parse()?,
+            "--ki" => gains.ki = value.parse()?,
+            "--kd" => gains.kd = value.parse()?,
+            "--max-throttle" => limits.max_throttle = value.parse()?,
+            "--max-brake" => limits.max_brake = value.parse()?,
+            _ => return Err(format!("unknown argument {}", flag).into()),
+        }
+    }
+
+    let policy = CruiseControl::new(setpoint)
+        .with_gains(gains)
+        .with_limits(limits);
+
+    // mock keeps the events in memory, the game does not see them
+    let mock_backend = MockBackend::new();
+    let backend: Box<dyn OutputBackend> = if mock {
+        Box::new(mock_backend.clone())
+    } else {
+        uinput_backend()?
+    };
+
+    let control = ControlLoop::new(Box::new(policy), backend);
+
+    let mut sync = Synchronization::new();
+    sync.set_output_transmitter(control.get_input_transmitter());
+
+    let mut telemetry = match (replay, name) {
+        (Some(path), _) => Telemetry::via_replay(path, ReplaySpeed::Original),
+        (None, Some(name)) => Telemetry::via_shared_memory_named(SelectGame::ETS2, &name),
+        (None, None) => Telemetry::via_shared_memory(SelectGame::ETS2),
+    };
+    telemetry.set_transmitter(sync.get_input_transmitter());
+
+    println!("cruise control run, {:?}", setpoint);
+
+    let mut cap_sess = Services::new();
+    cap_sess.add_service(ServiceType::ControlOutput, Box::new(control))?;
+    cap_sess.add_service(ServiceType::SynchronizeInputs, Box::new(sync))?;
+    cap_sess.add_service(ServiceType::TelemetryInput, Box::new(telemetry))?;
+
+    // runs until the game, emulator or recording stops
+    cap_sess.block_until_telemetry_finished()?;
+    cap_sess.stop_all_services()?;
+
+    if mock {
+        println!("{} reports emitted", mock_backend.reports().len());
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn uinput_backend() -> CResult<Box<dyn control::virtual_controller::OutputBackend>> {
+    Ok(Box::new(control::virtual_controller::UinputBackend::new()))
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn uinput_backend() -> CResult<Box<dyn control::virtual_controller::OutputBackend>> {
+    Err("uinput is only available on linux, use --mock".into())
+}
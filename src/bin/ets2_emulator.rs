@@ -0,0 +1,44 @@
+// emulates the ets2 telemetry plugin over posix shared memory.
+// usage: ets2_emulator [--name NAME] [--frames N] [--rate HZ] [--pause-at FRAME] [--resume-at FRAME] [--gameplay-at FRAME]
+type CResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[cfg(not(target_os = "linux"))]
+fn main() -> CResult<()> {
+    println!("ets2 emulator requires linux, use TelemetryEmulation.exe on windows");
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn main() -> CResult<()> {
+    use control::telemetry::emulation::{ETS2Emulator, EmulationConfig, ScriptEvent};
+    use std::io;
+
+    let mut config = EmulationConfig::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {}", flag))?;
+
+        match flag.as_str() {
+            "--name" => config.name = value,
+            "--frames" => config.frames = value.parse()?,
+            "--rate" => config.rate = value.parse()?,
+            "--pause-at" => config.script.push(ScriptEvent::Pause(value.parse()?)),
+            "--resume-at" => config.script.push(ScriptEvent::Resume(value.parse()?)),
+            "--gameplay-at" => config.script.push(ScriptEvent::Gameplay(value.parse()?)),
+            _ => return Err(format!("unknown argument {}", flag).into()),
+        }
+    }
+
+    let mut emulator = ETS2Emulator::new(config).map_err(|err| format!("{:?}", err))?;
+
+    let stdout = io::stdout();
+    emulator
+        .run(&mut stdout.lock())
+        .map_err(|err| format!("{:?}", err))?;
+
+    Ok(())
+}
@@ -1,27 +1,32 @@
-use control::screencapture::ScreenCapture;
-use control::synchronization::Synchronization;
-use control::telemetry::{SelectGame, Telemetry};
-use control::{Input, Process, ServiceType, Services};
-
-use image;
-use std::fs::File;
-use std::io;
-use std::io::prelude::*;
-use std::io::{BufRead, BufReader, Error, ErrorKind};
-use std::process::{Command, Stdio};
-use std::rc::Rc;
-use std::sync::mpsc::channel;
-use std::thread;
-use std::time::{Duration, Instant};
 type CResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+#[cfg(not(windows))]
 fn main() -> CResult<()> {
     println!("main run");
+    println!("no screen capture backend available on this platform");
 
-    let (out_transmitter, out_receiver) = channel();
+    Ok(())
+}
+
+#[cfg(windows)]
+fn main() -> CResult<()> {
+    use control::dataset::DatasetWriter;
+    use control::screencapture::ScreenCapture;
+    use control::synchronization::Synchronization;
+    use control::telemetry::SelectGame;
+    use control::{ServiceType, Services};
+
+    use std::thread;
+    use std::time::Duration;
+
+    println!("main run");
+
+    // persist synchronized groups
+    let dataset = DatasetWriter::new("dataset");
+    let out_transmitter = dataset.get_input_transmitter();
 
     //game selection
-    let sel_game = SelectGame::ETS2;
+    let _sel_game = SelectGame::ETS2;
     // setup telemetry
 
     let mut sync = Synchronization::new();
@@ -45,6 +50,11 @@ fn main() -> CResult<()> {
 
     let mut cap_sess = Services::new();
 
+    //start dataset services, stopped last so that every group is written
+    cap_sess
+        .add_service(ServiceType::DatasetOutput, Box::new(dataset))
+        .unwrap();
+
     //start sync services
     cap_sess
         .add_service(ServiceType::SynchronizeInputs, Box::new(sync))
@@ -81,18 +91,5 @@ fn main() -> CResult<()> {
     //join emulation thread
     //emulation_thread.wait().unwrap();
 
-    for grouped_input in out_receiver.iter() {
-        //
-
-        println!(
-            "input_type: {:?} header: {:?}",
-            grouped_input[0].input_type(),
-            grouped_input[0].header()
-        );
-        //assert!(i[0].header().0 + 1 == i.last().unwrap().header().0);
-        // println!("f - id: {}", i[0].header().0);
-        // println!("l - id: {}", i.last().unwrap().header().0);
-    }
-
     Ok(())
 }
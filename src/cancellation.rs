@@ -0,0 +1,71 @@
+use crate::queue::QueueReceiver;
+
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// how long a service loop blocks on its channel before checking the shutdown token
+pub const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// shared shutdown flag for service threads. clones share the flag, waiting threads wake up on cancel
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        let (cancelled, condvar) = &*self.inner;
+        *cancelled.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.0.lock().unwrap()
+    }
+
+    // sleeps until the timeout passed or the token is cancelled, returns true when cancelled
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let (cancelled, condvar) = &*self.inner;
+        let mut guard = cancelled.lock().unwrap();
+
+        while !*guard {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            guard = condvar.wait_timeout(guard, deadline - now).unwrap().0;
+        }
+
+        *guard
+    }
+
+    // blocks until items arrive on the receiver, wakes up regularly to check the token.
+    // after cancel the queued items are still returned. None when the loop should exit:
+    // cancelled and drained, or every transmitter has been dropped
+    pub fn recv_all<T>(&self, receiver: &QueueReceiver<T>) -> Option<Vec<T>> {
+        if self.is_cancelled() {
+            let queued: Vec<T> = receiver.try_iter().collect();
+            return if queued.is_empty() {
+                None
+            } else {
+                Some(queued)
+            };
+        }
+
+        match receiver.recv_timeout(SHUTDOWN_POLL_INTERVAL) {
+            Ok(item) => {
+                let mut items = vec![item];
+                items.extend(receiver.try_iter());
+                Some(items)
+            }
+            Err(RecvTimeoutError::Timeout) => Some(Vec::new()),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
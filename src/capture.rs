@@ -0,0 +1,89 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+
+struct Anchor {
+    instant: Instant,
+    wall: SystemTime,
+}
+
+static ANCHOR: OnceLock<Anchor> = OnceLock::new();
+
+fn anchor() -> &'static Anchor {
+    ANCHOR.get_or_init(|| Anchor {
+        instant: Instant::now(),
+        wall: SystemTime::now(),
+    })
+}
+
+// process wide monotonic clock shared by every input source, starts on first use.
+// the wall clock is read once when the clock starts and anchors every capture time
+pub struct CaptureClock;
+
+impl CaptureClock {
+    // monotonic time since the clock started
+    pub fn now() -> Duration {
+        anchor().instant.elapsed()
+    }
+
+    // capture time of an instant, zero for instants before the clock started
+    pub fn time_of(instant: Instant) -> Duration {
+        instant.saturating_duration_since(anchor().instant)
+    }
+
+    // wall clock time the clock started at
+    pub fn started_at() -> SystemTime {
+        anchor().wall
+    }
+}
+
+// when and in which order an input was acquired by its source.
+// sequence starts at 1 per source, the default (sequence 0) marks an input that was not captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CaptureStamp {
+    pub sequence: u64,
+    // monotonic, see CaptureClock
+    pub time: Duration,
+}
+
+impl CaptureStamp {
+    pub fn new(sequence: u64, time: Duration) -> CaptureStamp {
+        CaptureStamp { sequence, time }
+    }
+
+    pub fn is_captured(&self) -> bool {
+        self.sequence != 0
+    }
+
+    pub fn wall_clock(&self) -> SystemTime {
+        CaptureClock::started_at() + self.time
+    }
+}
+
+// sequence numbers of a single input source. clones share the counter
+#[derive(Debug, Clone, Default)]
+pub struct CaptureSequence {
+    last: Arc<AtomicU64>,
+}
+
+impl CaptureSequence {
+    pub fn new() -> CaptureSequence {
+        CaptureSequence::default()
+    }
+
+    // next sequence number stamped with the current capture time
+    pub fn stamp(&self) -> CaptureStamp {
+        self.stamp_at(Instant::now())
+    }
+
+    // use when the acquisition happened before the input could be built
+    pub fn stamp_at(&self, acquired: Instant) -> CaptureStamp {
+        let sequence = self.last.fetch_add(1, Ordering::Relaxed) + 1;
+        CaptureStamp::new(sequence, CaptureClock::time_of(acquired))
+    }
+
+    // sequence number of the latest stamp, 0 before the first one
+    pub fn last(&self) -> u64 {
+        self.last.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,208 @@
+use crate::{ServiceError, ServiceType};
+
+// how a service depends on another one, both have to be running first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    // the service sends its data to the dependency, i.e. inputs to synchronization
+    SendsTo,
+    // the service receives the output of the dependency, i.e. the dataset from synchronization
+    ReceivesFrom,
+}
+
+// dependencies are started first, dependents are stopped along with their dependencies.
+//
+// data flows from senders to receivers and services stop in that direction: a sender stops
+// before the service it sends to, a receiver after the service it receives from. every service
+// drains the inputs queued before stop, so nothing is in flight once the last receiver stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyGraph {
+    // (service, depends on, how)
+    edges: Vec<(ServiceType, ServiceType, Edge)>,
+}
+
+impl DependencyGraph {
+    // graph without dependencies
+    pub fn empty() -> DependencyGraph {
+        DependencyGraph { edges: Vec::new() }
+    }
+
+    // the service sends to depends_on. refuses a dependency that would create a cycle
+    pub fn add(
+        &mut self,
+        service: ServiceType,
+        depends_on: ServiceType,
+    ) -> Result<(), ServiceError> {
+        self.insert(service, depends_on, Edge::SendsTo)
+    }
+
+    // the service receives the output of depends_on. refuses a dependency that would create a cycle
+    pub fn add_receiver(
+        &mut self,
+        service: ServiceType,
+        depends_on: ServiceType,
+    ) -> Result<(), ServiceError> {
+        self.insert(service, depends_on, Edge::ReceivesFrom)
+    }
+
+    fn insert(
+        &mut self,
+        service: ServiceType,
+        depends_on: ServiceType,
+        edge: Edge,
+    ) -> Result<(), ServiceError> {
+        if self.edges.contains(&(service, depends_on, edge)) {
+            return Ok(());
+        }
+
+        if let Some(mut path) = self.path(depends_on, service, DependencyGraph::dependencies) {
+            path.insert(0, service);
+            return Err(ServiceError::DependencyCycle(path));
+        }
+
+        // data must not flow back to where it came from, the stop order follows it
+        let (from, to) = match edge {
+            Edge::SendsTo => (service, depends_on),
+            Edge::ReceivesFrom => (depends_on, service),
+        };
+        if let Some(mut path) = self.path(to, from, DependencyGraph::receivers) {
+            path.insert(0, from);
+            return Err(ServiceError::DependencyCycle(path));
+        }
+
+        self.edges.push((service, depends_on, edge));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, service: ServiceType, depends_on: ServiceType) {
+        self.edges
+            .retain(|(from, to, _)| (*from, *to) != (service, depends_on));
+    }
+
+    // direct dependencies of the service
+    pub fn dependencies(&self, service: ServiceType) -> Vec<ServiceType> {
+        self.edges
+            .iter()
+            .filter(|(from, _, _)| *from == service)
+            .map(|(_, to, _)| *to)
+            .collect()
+    }
+
+    // services that directly depend on the service
+    pub fn dependents(&self, service: ServiceType) -> Vec<ServiceType> {
+        self.edges
+            .iter()
+            .filter(|(_, to, _)| *to == service)
+            .map(|(from, _, _)| *from)
+            .collect()
+    }
+
+    // services the service passes its data to
+    fn receivers(&self, service: ServiceType) -> Vec<ServiceType> {
+        self.edges
+            .iter()
+            .filter_map(|(from, to, edge)| match edge {
+                Edge::SendsTo if *from == service => Some(*to),
+                Edge::ReceivesFrom if *to == service => Some(*from),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // services in start order, dependencies before dependents. services that do not depend on
+    // each other keep their order. dependencies outside of services are ignored
+    pub fn start_order(&self, services: &[ServiceType]) -> Vec<ServiceType> {
+        let mut remaining: Vec<ServiceType> = services.to_vec();
+        let mut order = Vec::with_capacity(services.len());
+
+        while !remaining.is_empty() {
+            // the graph has no cycles, so there is always a service without remaining dependencies
+            let i = remaining
+                .iter()
+                .position(|service| {
+                    self.dependencies(*service)
+                        .iter()
+                        .all(|dependency| !remaining.contains(dependency))
+                })
+                .unwrap();
+
+            order.push(remaining.remove(i));
+        }
+
+        order
+    }
+
+    // services in stop order, senders before the services they send to. services the data does
+    // not flow between are stopped in reverse start order
+    pub fn stop_order(&self, services: &[ServiceType]) -> Vec<ServiceType> {
+        let mut remaining: Vec<ServiceType> =
+            self.start_order(services).into_iter().rev().collect();
+        let mut order = Vec::with_capacity(services.len());
+
+        while !remaining.is_empty() {
+            // data does not flow in cycles, so there is always a service nothing is sent to
+            let i = remaining
+                .iter()
+                .position(|service| {
+                    remaining
+                        .iter()
+                        .all(|sender| !self.receivers(*sender).contains(service))
+                })
+                .unwrap();
+
+            order.push(remaining.remove(i));
+        }
+
+        order
+    }
+
+    // path from service to target following next, including both
+    fn path<F>(
+        &self,
+        service: ServiceType,
+        target: ServiceType,
+        next: F,
+    ) -> Option<Vec<ServiceType>>
+    where
+        F: Fn(&DependencyGraph, ServiceType) -> Vec<ServiceType> + Copy,
+    {
+        if service == target {
+            return Some(vec![service]);
+        }
+
+        next(self, service).into_iter().find_map(|following| {
+            let mut path = self.path(following, target, next)?;
+            path.insert(0, service);
+            Some(path)
+        })
+    }
+}
+
+impl Default for DependencyGraph {
+    // inputs send to synchronization, the recorder passes telemetry on to synchronization.
+    // the dataset output and the control loop receive the output of synchronization, directly or
+    // through the resampler
+    fn default() -> Self {
+        let mut graph = DependencyGraph::empty();
+
+        for service in [
+            ServiceType::UserInput,
+            ServiceType::TelemetryInput,
+            ServiceType::ScreenCaptureInput,
+            ServiceType::RecordInputs,
+        ] {
+            graph.add(service, ServiceType::SynchronizeInputs).unwrap();
+        }
+
+        for (service, depends_on) in [
+            (ServiceType::DatasetOutput, ServiceType::SynchronizeInputs),
+            (ServiceType::ControlOutput, ServiceType::SynchronizeInputs),
+            (ServiceType::ResampleInputs, ServiceType::SynchronizeInputs),
+            (ServiceType::DatasetOutput, ServiceType::ResampleInputs),
+            (ServiceType::ControlOutput, ServiceType::ResampleInputs),
+        ] {
+            graph.add_receiver(service, depends_on).unwrap();
+        }
+
+        graph
+    }
+}
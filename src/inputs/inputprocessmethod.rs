@@ -1,10 +1,17 @@
-use crate::{Input, ServiceError};
-use std::sync::mpsc::Sender;
+use crate::queue::InputSender;
+use crate::telemetry::ValidationReport;
+use crate::{CaptureSequence, ServiceError};
 
 pub trait InputProcessMethod {
     fn start(&mut self) -> Result<(), ServiceError>;
     fn stop(&mut self);
-    fn join(&mut self);
+    fn join(&mut self) -> Result<(), ServiceError>;
+    fn is_finished(&self) -> bool;
     fn method(&self) -> &str;
-    fn set_transmitter(&mut self, transmitter: Sender<Box<dyn Input + Send>>);
+    fn set_transmitter(&mut self, transmitter: InputSender);
+    // replaces the sequence of the source, clones share the counter
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence);
+    fn validation_report(&self) -> Option<ValidationReport> {
+        None
+    }
 }
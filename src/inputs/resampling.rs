@@ -0,0 +1,277 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::queue::{bounded, GroupReceiver, GroupSender, QueuePolicy};
+use crate::screencapture::Pixels;
+use crate::telemetry::ets2::{Interpolate, TelemetryPacket};
+use crate::telemetry::EventGame;
+use crate::userinput::ControllerSnapshot;
+use crate::utils::{join_thread, thread_finished};
+use crate::{CancellationToken, CaptureStamp, Input, Process, ServiceError};
+
+// groups waiting to be resampled
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+// frames further apart are not interpolated, the clock starts over (i.e. after a pause)
+pub const DEFAULT_MAX_GAP: Duration = Duration::from_secs(1);
+
+// Responsible for turning the one group per game frame output of synchronization into groups
+// on a fixed clock. the clock runs on game time, a replay as fast as possible gives the same samples.
+//
+// every sample is [FrameStart, latest controller snapshot, latest image, FrameEnd] at the tick
+// time: FrameEnd values are interpolated between the frames around the tick (see Interpolate),
+// FrameStart is held from the frame before the tick. controller snapshots are held like the
+// images, raw user events are not resampled.
+//
+// samples carry their own capture sequence (from 1) on both telemetry packets, the packet ids
+// are those of the frame the values were held from
+pub struct Resampler {
+    period: Duration,
+    max_gap: Duration,
+    input_transmitter: GroupSender,
+    input_receiver: Option<GroupReceiver>,
+    output_transmitter: Option<GroupSender>,
+    shutdown: CancellationToken,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+}
+
+impl Resampler {
+    // samples per second, i.e. 10.0 or 30.0. fails when the rate is not positive and finite
+    pub fn new(rate: f64) -> Result<Resampler, ServiceError> {
+        if !(rate.is_finite() && rate > 0.0) {
+            return Err(ServiceError::InvalidConfig(format!(
+                "resampling rate {} is not a positive number",
+                rate
+            )));
+        }
+
+        Ok(Resampler::with_period(Duration::from_secs_f64(1.0 / rate)))
+    }
+
+    pub fn with_period(period: Duration) -> Resampler {
+        let (input_transmitter, input_receiver) =
+            bounded(QueuePolicy::block(DEFAULT_QUEUE_CAPACITY));
+        Resampler {
+            period,
+            max_gap: DEFAULT_MAX_GAP,
+            input_transmitter,
+            input_receiver: Some(input_receiver),
+            output_transmitter: None,
+            shutdown: CancellationToken::new(),
+            handle: None,
+        }
+    }
+
+    pub fn set_max_gap(&mut self, max_gap: Duration) {
+        self.max_gap = max_gap;
+    }
+
+    //return cloned transmitter, use as the synchronization output transmitter
+    pub fn get_input_transmitter(&self) -> GroupSender {
+        self.input_transmitter.clone()
+    }
+
+    pub fn set_output_transmitter(&mut self, transmitter: GroupSender) {
+        self.output_transmitter = Some(transmitter);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Frame {
+    start: TelemetryPacket,
+    end: TelemetryPacket,
+}
+
+impl Frame {
+    // None when the group is not framed by FrameStart and FrameEnd
+    fn from_group(group: &[Box<dyn Input + Send>]) -> Option<Frame> {
+        let packet = |event: EventGame| {
+            group
+                .iter()
+                .filter(|input| input.event_type() == event)
+                .find_map(|input| input.as_any().downcast_ref::<TelemetryPacket>())
+                .copied()
+        };
+
+        let end = packet(EventGame::FrameEndEvent)?;
+        end.event_payload().frame_end()?;
+
+        Some(Frame {
+            start: packet(EventGame::FrameStartEvent)?,
+            end,
+        })
+    }
+
+    fn time(&self) -> u64 {
+        self.end.time
+    }
+}
+
+struct Sampler {
+    period: u64,
+    max_gap: u64,
+    previous: Option<Frame>,
+    next_tick: u64,
+    image: Option<Pixels>,
+    controller: Option<ControllerSnapshot>,
+    // capture sequence of the latest sample
+    sequence: u64,
+}
+
+impl Sampler {
+    fn new(period: Duration, max_gap: Duration) -> Sampler {
+        Sampler {
+            // a zero period would never advance the clock
+            period: (period.as_micros() as u64).max(1),
+            max_gap: max_gap.as_micros() as u64,
+            previous: None,
+            next_tick: 0,
+            image: None,
+            controller: None,
+            sequence: 0,
+        }
+    }
+
+    fn push(&mut self, group: Vec<Box<dyn Input + Send>>) -> Vec<Vec<Box<dyn Input + Send>>> {
+        let frame = match Frame::from_group(&group) {
+            Some(frame) => frame,
+            None => return Vec::new(),
+        };
+        let time = frame.time();
+
+        let previous = match self.previous {
+            Some(previous) if time > previous.time() && time - previous.time() <= self.max_gap => {
+                Some(previous)
+            }
+            // first frame, a gap or the game time went backwards
+            _ => {
+                self.next_tick = time;
+                None
+            }
+        };
+
+        let mut samples = Vec::new();
+
+        if let Some(previous) = previous {
+            while self.next_tick < time {
+                let t = (self.next_tick - previous.time()) as f32 / (time - previous.time()) as f32;
+                samples.push(self.sample(&previous, &frame, t));
+                self.next_tick += self.period;
+            }
+        }
+
+        // latest image of the group, by capture time
+        let image = group
+            .iter()
+            .filter_map(|input| input.as_any().downcast_ref::<Pixels>())
+            .max_by_key(|pixels| pixels.capture().time);
+        if let Some(image) = image {
+            self.image = Some(image.clone());
+        }
+
+        // the aggregator adds one snapshot per frame, the latest wins
+        let controller = group
+            .iter()
+            .rev()
+            .find_map(|input| ControllerSnapshot::from_input(input.as_ref()));
+        if let Some(controller) = controller {
+            self.controller = Some(*controller);
+        }
+
+        if self.next_tick == time {
+            samples.push(self.sample(&frame, &frame, 0.0));
+            self.next_tick += self.period;
+        }
+
+        self.previous = Some(frame);
+        samples
+    }
+
+    fn sample(&mut self, previous: &Frame, next: &Frame, t: f32) -> Vec<Box<dyn Input + Send>> {
+        let tick = self.next_tick;
+        // several samples can be interpolated from the same frames, each is a capture of its own
+        self.sequence += 1;
+
+        let mut start = previous.start;
+        start.time = tick;
+        start.capture = CaptureStamp::new(self.sequence, previous.start.capture.time);
+
+        let mut end = previous.end;
+        end.time = tick;
+        if let (Some(from), Some(to)) = (
+            previous.end.event_payload().frame_end(),
+            next.end.event_payload().frame_end(),
+        ) {
+            end.data.frame_end_data = from.interpolate(to, t);
+        }
+
+        let from = previous.end.capture.time;
+        let to = next.end.capture.time;
+        end.capture = CaptureStamp::new(self.sequence, from + to.saturating_sub(from).mul_f32(t));
+
+        let mut sample: Vec<Box<dyn Input + Send>> = vec![Box::new(start)];
+        if let Some(controller) = self.controller {
+            sample.push(Box::new(controller));
+        }
+        if let Some(image) = self.image.as_ref() {
+            sample.push(Box::new(image.clone()));
+        }
+        sample.push(Box::new(end));
+        sample
+    }
+}
+
+impl Process for Resampler {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_none() {
+            return Err(ServiceError::AlreadyActive);
+        }
+        if self.output_transmitter.is_none() {
+            return Err(ServiceError::TransmitterNotSet);
+        }
+
+        let receiver = self.input_receiver.take().unwrap();
+        let transmitter = self.output_transmitter.take().unwrap();
+        let mut sampler = Sampler::new(self.period, self.max_gap);
+
+        let shutdown = CancellationToken::new();
+        self.shutdown = shutdown.clone();
+
+        let process = move || {
+            // groups queued before stop are still resampled
+            while let Some(groups) = shutdown.recv_all(&receiver) {
+                for group in groups {
+                    for sample in sampler.push(group) {
+                        if transmitter.send(sample).is_err() {
+                            return Err(ServiceError::Disconnected(String::from(
+                                "resampler output",
+                            )));
+                        }
+                    }
+                }
+            }
+
+            println!("exiting resampler loop");
+            Ok(())
+        };
+
+        self.handle = Some(thread::spawn(process));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_some() {
+            return Err(ServiceError::NotActive);
+        }
+        self.shutdown.cancel();
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+}
@@ -1,10 +1,19 @@
-use crate::{Input, InputProcessMethod, Process, ServiceError};
-use std::sync::mpsc::Sender;
+use crate::queue::InputSender;
+use crate::{CaptureSequence, InputProcessMethod, Process, ServiceError};
 
 //use dxgcap::DXGIManager;
 
+#[path = "screencapture/pixels.rs"]
+mod pixels;
+pub use pixels::Pixels;
+
+// desktop duplication api is only available on windows
+#[cfg(windows)]
 #[path = "screencapture/desktopduplication.rs"]
 mod desktopduplication;
+#[cfg(windows)]
+pub use desktopduplication::CaptureError;
+#[cfg(windows)]
 use desktopduplication::DesktopDuplication;
 
 // #[path = "screencapture/d3d11device.rs"]
@@ -16,12 +25,10 @@ pub struct ScreenCapture {
 }
 
 impl ScreenCapture {
+    #[cfg(windows)]
     pub fn via_desktopduplication() -> Result<ScreenCapture, ServiceError> {
         //let d = *d_device.get_device();
-        let screencapture_input = match DesktopDuplication::new() {
-            Ok(dd) => dd,
-            Err(err) => return Err(ServiceError::WindowsGetLastError(err as i32)),
-        };
+        let screencapture_input = DesktopDuplication::new()?;
 
         Ok(ScreenCapture {
             screencapture_input: Box::new(screencapture_input),
@@ -32,9 +39,14 @@ impl ScreenCapture {
         self.screencapture_input.method()
     }
 
-    pub fn set_transmitter(&mut self, transmitter: Sender<Box<dyn Input + Send>>) {
+    pub fn set_transmitter(&mut self, transmitter: InputSender) {
         self.screencapture_input.set_transmitter(transmitter);
     }
+
+    // share one sequence between the processes of a restart factory, see Telemetry
+    pub fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.screencapture_input.set_capture_sequence(sequence);
+    }
 }
 
 impl Process for ScreenCapture {
@@ -49,7 +61,11 @@ impl Process for ScreenCapture {
         Ok(())
     }
 
-    fn join(&mut self) {
-        self.screencapture_input.join();
+    fn join(&mut self) -> Result<(), ServiceError> {
+        self.screencapture_input.join()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.screencapture_input.is_finished()
     }
 }
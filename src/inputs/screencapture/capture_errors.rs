@@ -1,5 +1,6 @@
+use std::fmt;
 use winapi::shared::winerror;
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CaptureError {
     AccessLost,         // winerror::DXGI_ERROR_ACCESS_LOST,
     DeviceRemoved,      // winerror::DXGI_ERROR_DEVICE_REMOVED,
@@ -30,3 +31,23 @@ impl CaptureError {
         }
     }
 }
+
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            CaptureError::AccessLost => "desktop duplication access lost",
+            CaptureError::DeviceRemoved => "graphics device removed",
+            CaptureError::ExclusiveOwnership => "output is in exclusive use by another application",
+            CaptureError::InvalidCall => "invalid dxgi call",
+            CaptureError::NotFound => "output not found",
+            CaptureError::WaitTimeout => "timed out waiting for the next frame",
+            CaptureError::WasStillDrawing => "gpu was still drawing",
+            CaptureError::DxgiError => "dxgi error",
+            CaptureError::NoDebugLayer => "d3d11 debug layer not available",
+            CaptureError::InvalidParameter => "invalid parameter",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+impl std::error::Error for CaptureError {}
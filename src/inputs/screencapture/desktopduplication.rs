@@ -1,7 +1,9 @@
-use crate::telemetry::EventGame;
-use crate::{Input, InputProcessMethod, InputType, ServiceError};
+use crate::queue::InputSender;
+use crate::screencapture::Pixels;
+use crate::utils::{join_thread, thread_finished};
+use crate::{CancellationToken, CaptureSequence, InputProcessMethod, ServiceError};
 mod capture_errors;
-use capture_errors::CaptureError;
+pub use capture_errors::CaptureError;
 
 mod d3d11device;
 use d3d11device::{CompatibleCPUTexture2D, D3D11Device};
@@ -13,44 +15,22 @@ use wio::com::ComPtr;
 use std::mem;
 use std::ptr;
 use std::slice;
-use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-pub struct Pixels {
-    pixels: Vec<u8>,
-    width: u32,
-    height: u32,
-}
-
-impl Pixels {
-    pub fn new(pixels: Vec<u8>, width: u32, height: u32) -> Pixels {
-        Pixels {
-            pixels,
-            width,
-            height,
-        }
-    }
-}
+// minimum time between two captured frames
+const CAPTURE_INTERVAL: Duration = Duration::from_millis(15);
 
-impl Input for Pixels {
-    fn input_type(&self) -> InputType {
-        InputType::Image
-    }
-    fn event_type(&self) -> EventGame {
-        EventGame::NA
-    }
-
-    fn header(&self) -> (u64, u32, u64, u32) {
-        (0, self.width, 0, self.height)
-    }
-}
+// wait before retrying when no new frame is available
+const RETRY_INTERVAL: Duration = Duration::from_millis(2);
 
 pub struct DesktopDuplication {
-    transmitter: Option<Sender<Box<dyn Input + Send>>>,
-    handle: Option<thread::JoinHandle<u32>>,
-    sentinal: Arc<Mutex<bool>>,
+    transmitter: Option<InputSender>,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+    shutdown: CancellationToken,
+    // shared with the capture thread, a restart factory passes the sequence of the session in
+    // through set_capture_sequence so frame numbers keep increasing
+    sequence: CaptureSequence,
 }
 
 impl DesktopDuplication {
@@ -60,7 +40,8 @@ impl DesktopDuplication {
         Ok(DesktopDuplication {
             transmitter: None,
             handle: None,
-            sentinal: Arc::new(Mutex::new(false)),
+            shutdown: CancellationToken::new(),
+            sequence: CaptureSequence::new(),
         })
     }
 }
@@ -75,20 +56,18 @@ impl InputProcessMethod for DesktopDuplication {
 
         //need to add capture interval,
 
-        // clone variable
-        let sentinal = Arc::clone(&self.sentinal);
+        // new token, a stop before the thread is running is not lost
+        self.shutdown = CancellationToken::new();
+        let shutdown = self.shutdown.clone();
 
         // take value
         let tx = self.transmitter.take().unwrap();
+        let sequence = self.sequence.clone();
 
         let handle = thread::spawn(move || {
             // needed to pass pointers between
-            // handle error case
-            let d3d11device = D3D11Device::new().unwrap();
-            let (outdup, dev, devctx) = d3d11device.init_duplication().unwrap();
-            {
-                *sentinal.lock().unwrap() = true;
-            }
+            let d3d11device = D3D11Device::new()?;
+            let (outdup, dev, devctx) = d3d11device.init_duplication()?;
 
             let mut compatible_texture: Option<(ComPtr<d3d11::ID3D11Texture2D>, u32, u32)> = None;
             let mut dxgi_outdupl_frame_info: dxgi1_2::DXGI_OUTDUPL_FRAME_INFO =
@@ -100,21 +79,20 @@ impl InputProcessMethod for DesktopDuplication {
 
             let mut last_frame = Instant::now();
             let mut first_iter = true;
+            let mut result = Ok(());
 
             loop {
-                //need to be able to recreate output duplication if failed, investigate how
-                let timestamp = Instant::now();
-                //check sentinal condition
-                if !*sentinal.lock().unwrap() {
+                // sleep until the capture interval has passed, not on the first iteration. stop wakes the sleep up
+                let wait = if first_iter {
+                    Duration::from_secs(0)
+                } else {
+                    CAPTURE_INTERVAL.saturating_sub(last_frame.elapsed())
+                };
+                if shutdown.wait_timeout(wait) {
                     println!("stopping desktopduplication loop");
                     break;
                 }
 
-                // check if interval has passed and not first iteration
-                if timestamp.duration_since(last_frame).as_millis() < 15 && !first_iter {
-                    continue;
-                }
-
                 // use to capture at time zero or close to it
                 if first_iter {
                     first_iter = false;
@@ -128,8 +106,8 @@ impl InputProcessMethod for DesktopDuplication {
                         if success != winerror::DXGI_ERROR_INVALID_CALL {
                             // need to be able to restart output duplication api
                             println!("ReleaseFrame Error {:x}", success);
+                            result = Err(CaptureError::from_win_error(success).into());
                             break;
-                            // return Err(ServiceError::WindowsGetLastError(success));
                         }
                     }
                 }
@@ -147,21 +125,25 @@ impl InputProcessMethod for DesktopDuplication {
                     match success as u32 {
                         0x887A0027 => {
                             //DXGI_ERROR_WAIT_TIMEOUT
-                            thread::sleep(Duration::from_millis(2));
+                            shutdown.wait_timeout(RETRY_INTERVAL);
                             continue;
                         }
                         _ => {
+                            // access lost on mode switches and uac prompts, duplication has to be recreated
                             println!("AquireFrame Error {:x}", success);
-                            continue;
+                            result = Err(CaptureError::from_win_error(success).into());
+                            break;
                         }
                     }
-                    //return Err(ServiceError::WindowsGetLastError(success));
                 }
 
+                // frame is stamped when it was acquired, not after the copy to the cpu
+                let acquired = Instant::now();
+
                 if dxgi_outdupl_frame_info.AccumulatedFrames < 1 {
                     //no frame available wait before retrying
                     //println!("No accumalated frames");
-                    thread::sleep(Duration::from_millis(2));
+                    shutdown.wait_timeout(RETRY_INTERVAL);
 
                     continue;
                 }
@@ -172,7 +154,6 @@ impl InputProcessMethod for DesktopDuplication {
                     Ok(texture) => texture,
                     Err(err) => {
                         println!("ID3D11Texture2D Error {:x}", err);
-                        //return Err(ServiceError::WindowsGetLastError(err));
                         continue;
                     }
                 };
@@ -183,8 +164,8 @@ impl InputProcessMethod for DesktopDuplication {
                         Ok(par) => Some(par),
                         Err(f) => {
                             println!("CompatibleCPUTexture2D {:?}", f);
-                            continue;
-                            //return Err(ServiceError::WindowsGetLastError(f as i32));
+                            result = Err(f.into());
+                            break;
                         }
                     };
                 }
@@ -226,16 +207,8 @@ impl InputProcessMethod for DesktopDuplication {
                     )
                 };
 
-                let pixels = Pixels::new(buf.to_vec(), width, height);
-
-                match tx.send(Box::new(pixels)) {
-                    Err(err) => {
-                        println!("desktopduplication loop transmit error {}", err);
-                    }
-                    _ => (),
-                }
-
-                last_frame = Instant::now();
+                let pixels = Pixels::new(buf.to_vec(), width, height)
+                    .with_capture(sequence.stamp_at(acquired));
 
                 unsafe {
                     devctx.Unmap(
@@ -243,16 +216,21 @@ impl InputProcessMethod for DesktopDuplication {
                         subresource,
                     )
                 };
-            }
 
-            let success = unsafe { outdup.Release() };
+                if tx.send(Box::new(pixels)).is_err() {
+                    result = Err(ServiceError::Disconnected(String::from(
+                        "screen capture output",
+                    )));
+                    break;
+                }
 
-            if success != 0x0 {
-                println!("OuputDuplication Release Error {:x}", success);
-                //return Err(ServiceError::WindowsGetLastError(success as i32));
+                last_frame = Instant::now();
             }
 
-            success
+            // returns the remaining reference count, not an error code
+            unsafe { outdup.Release() };
+
+            result
         });
 
         self.handle = Some(handle);
@@ -261,17 +239,22 @@ impl InputProcessMethod for DesktopDuplication {
     fn stop(&mut self) {
         //stop loop
 
-        *self.sentinal.lock().unwrap() = false;
+        self.shutdown.cancel();
     }
-    fn join(&mut self) {
-        // take ownership of handle and
-
-        self.handle.take().unwrap().join().unwrap();
+    fn join(&mut self) -> Result<(), ServiceError> {
+        // take ownership of handle and join
+        join_thread(self.handle.take())
+    }
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
     }
     fn method(&self) -> &str {
         "DesktopDuplicationAPI"
     }
-    fn set_transmitter(&mut self, transmitter: Sender<Box<dyn Input + Send>>) {
+    fn set_transmitter(&mut self, transmitter: InputSender) {
         self.transmitter = Some(transmitter);
     }
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.sequence = sequence;
+    }
 }
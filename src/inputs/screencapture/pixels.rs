@@ -0,0 +1,118 @@
+use crate::{CaptureStamp, ImageEncoding, Input, InputHeader, InputType, Serialized, ServiceError};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::ColorType;
+use std::any::Any;
+
+// captured frame, 4 bytes per pixel in BGRA order. rows may be padded (row pitch)
+#[derive(Clone)]
+pub struct Pixels {
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    capture: CaptureStamp,
+}
+
+impl Pixels {
+    pub fn new(pixels: Vec<u8>, width: u32, height: u32) -> Pixels {
+        Pixels {
+            pixels,
+            width,
+            height,
+            capture: CaptureStamp::default(),
+        }
+    }
+
+    // set by the capture method when the frame was acquired
+    pub fn with_capture(mut self, capture: CaptureStamp) -> Pixels {
+        self.capture = capture;
+        self
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // tightly packed RGB copy, drops the row padding and alpha channel.
+    // fails when the buffer is smaller than height rows of width pixels
+    pub fn to_rgb(&self) -> Result<Vec<u8>, ServiceError> {
+        if self.width == 0 || self.height == 0 {
+            return Ok(Vec::new());
+        }
+
+        let (width, height) = (self.width as usize, self.height as usize);
+        let row_pitch = self.pixels.len() / height;
+        if row_pitch < width * 4 {
+            return Err(ServiceError::Serialization(
+                format!(
+                    "{} bytes do not hold {}x{} bgra pixels",
+                    self.pixels.len(),
+                    self.width,
+                    self.height
+                )
+                .into(),
+            ));
+        }
+
+        let mut rgb = Vec::with_capacity(width * height * 3);
+
+        for row in self.pixels.chunks(row_pitch).take(height) {
+            for bgra in row.chunks(4).take(width) {
+                rgb.extend_from_slice(&[bgra[2], bgra[1], bgra[0]]);
+            }
+        }
+
+        Ok(rgb)
+    }
+
+    pub fn encode(&self, encoding: ImageEncoding) -> Result<Vec<u8>, ServiceError> {
+        let rgb = self.to_rgb()?;
+        let mut data = Vec::new();
+
+        let res = match encoding {
+            ImageEncoding::Png => {
+                PngEncoder::new(&mut data).encode(&rgb, self.width, self.height, ColorType::Rgb8)
+            }
+            ImageEncoding::Jpeg(quality) => JpegEncoder::new_with_quality(&mut data, quality)
+                .encode(&rgb, self.width, self.height, ColorType::Rgb8),
+        };
+
+        res.map_err(|err| ServiceError::Serialization(Box::new(err)))?;
+
+        Ok(data)
+    }
+}
+
+impl Input for Pixels {
+    fn header(&self) -> InputHeader {
+        InputHeader {
+            sequence: self.capture.sequence,
+            capture: self.capture,
+            payload_size: self.pixels.len() as u32,
+            ..InputHeader::new(InputType::Image)
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    fn serialize(&self, image_encoding: ImageEncoding) -> Result<Serialized, ServiceError> {
+        Ok(Serialized::Image {
+            encoding: image_encoding,
+            data: self.encode(image_encoding)?,
+        })
+    }
+}
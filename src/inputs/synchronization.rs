@@ -1,41 +1,85 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
-use std::sync::{Arc, Mutex};
 use std::thread;
 
+use crate::queue::{
+    input_queue, DropCounters, GroupSender, InputQueueConfig, InputReceiver, InputSender,
+};
 use crate::telemetry::EventGame;
-use crate::{Input, InputType, Process, ServiceError};
+use crate::utils::{join_thread, thread_finished};
+use crate::{CancellationToken, Process, ServiceError};
+
+#[path = "synchronization/alignment.rs"]
+mod alignment;
+#[path = "synchronization/grouping.rs"]
+mod grouping;
+
+pub use alignment::{AlignmentCounters, AlignmentReport, AlignmentReportHandle, NearestCapture};
+pub use grouping::{
+    FrameCount, FrameGrouping, Group, GroupingPolicy, PauseGate, PerImage, TimeWindow,
+};
 
 // Responsible for aligning data in a sensible manner.
 // i.e when telemetry indicates pause state all other inputs should be discared or stopped
 pub struct Synchronization {
-    input_transmitter: Sender<Box<dyn Input + Send>>,
-    input_receiver: Option<Receiver<Box<dyn Input + Send>>>,
-    output_transmitter: Option<Sender<Vec<Box<dyn Input + Send>>>>,
-    sentinal: Arc<Mutex<bool>>,
-    handle: Option<thread::JoinHandle<()>>,
+    input_transmitter: InputSender,
+    input_receiver: Option<InputReceiver>,
+    output_transmitter: Option<GroupSender>,
+    policy: Option<Box<dyn GroupingPolicy>>,
+    shutdown: CancellationToken,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
 }
 
 impl Synchronization {
+    // one group per game frame while the game is not paused
     pub fn new() -> Synchronization {
-        let (input_transmitter, input_receiver) = channel();
+        Synchronization::with_queue_config(&InputQueueConfig::default())
+    }
+
+    // queue capacity and backpressure per input type
+    pub fn with_queue_config(config: &InputQueueConfig) -> Synchronization {
+        Synchronization::with_config(config, Box::new(PauseGate::new(FrameGrouping::new())))
+    }
+
+    // i.e. Box::new(PauseGate::new(FrameCount::new(3))), wrap the policy in PauseGate to
+    // discard inputs while the game is paused
+    pub fn with_policy(policy: Box<dyn GroupingPolicy>) -> Synchronization {
+        Synchronization::with_config(&InputQueueConfig::default(), policy)
+    }
+
+    pub fn with_config(
+        config: &InputQueueConfig,
+        policy: Box<dyn GroupingPolicy>,
+    ) -> Synchronization {
+        let (input_transmitter, input_receiver) = input_queue(config);
         let input_receiver = Some(input_receiver);
         Synchronization {
             input_transmitter,
             input_receiver,
             output_transmitter: None,
-            sentinal: Arc::new(Mutex::new(false)),
+            policy: Some(policy),
+            shutdown: CancellationToken::new(),
             handle: None,
         }
     }
 
     //return cloned transmitter
-    pub fn get_input_transmitter(&self) -> Sender<Box<dyn Input + Send>> {
+    pub fn get_input_transmitter(&self) -> InputSender {
         self.input_transmitter.clone()
     }
 
-    pub fn set_output_transmitter(&mut self, transmitter: Sender<Vec<Box<dyn Input + Send>>>) {
+    pub fn set_output_transmitter(&mut self, transmitter: GroupSender) {
         self.output_transmitter = Some(transmitter);
     }
+
+    // inputs discarded by the queue policy, per input type
+    pub fn dropped(&self) -> DropCounters {
+        self.input_transmitter.dropped()
+    }
+}
+
+impl Default for Synchronization {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Process for Synchronization {
@@ -43,86 +87,44 @@ impl Process for Synchronization {
         if self.input_receiver.is_none() {
             return Err(ServiceError::AlreadyActive);
         }
+        if self.output_transmitter.is_none() {
+            return Err(ServiceError::TransmitterNotSet);
+        }
 
         let receiver = self.input_receiver.take().unwrap();
         let transmitter = self.output_transmitter.take().unwrap();
+        let mut policy = self.policy.take().unwrap();
 
-        let sentinal = Arc::new(Mutex::new(true));
-
-        self.sentinal = sentinal.clone();
+        let shutdown = CancellationToken::new();
 
-        let mut input_buf: Vec<Box<dyn Input + Send>> = Vec::new();
+        self.shutdown = shutdown.clone();
 
-        // push on to input_buf from StartFrameEvent .... until EndFrameEvent received
-        // drain and collect input_buf into new vec and pass onto distribution services
-        //
-
-        let process = move || {
-            let mut in_game_driving: bool = false;
-
-            loop {
-                if !*sentinal.lock().unwrap() {
-                    println!("exiting syncronization loop");
-                    break;
+        let send = move |groups: Vec<Group>| {
+            for group in groups {
+                if transmitter.send(group).is_err() {
+                    return Err(ServiceError::Disconnected(String::from(
+                        "synchronization output",
+                    )));
                 }
+            }
+            Ok(())
+        };
 
-                for input in receiver.try_iter() {
-                    // if packet is none loop will
-                    match input.input_type() {
-                        InputType::Telemetry => {
-                            match input.event_type() {
-                                EventGame::FrameStartEvent => {
-                                    if in_game_driving {
-                                        input_buf.push(input);
-                                    }
-                                }
-                                EventGame::FrameEndEvent => {
-                                    if in_game_driving {
-                                        input_buf.push(input);
-                                        // drain vec into new struct
-
-                                        let groupify: Vec<Box<dyn Input + Send>> =
-                                            input_buf.drain(..).collect();
-                                        transmitter.send(groupify).unwrap();
-                                    }
-                                }
-                                EventGame::PausedEvent => {
-                                    //stop pushing data on the distribution services
-                                    println!("received PausedEvent");
-                                    in_game_driving = false;
-                                }
-                                EventGame::StartedEvent => {
-                                    //start pushing data on the distribution services again
-                                    println!("received StartedEvent");
-                                    in_game_driving = true;
-                                }
-                                EventGame::OtherEvent => {
-                                    if in_game_driving {
-                                        input_buf.push(input);
-                                    }
-                                }
-                                EventGame::NotValidEvent => {
-                                    println!(
-                                        "received NotValidEvent: could be corruption or #[repr(c)]"
-                                    )
-                                }
-                                EventGame::NA => {}
-                            }
-                            //println!(" EventGame: {:?}", input.event_type())
-                        }
-                        InputType::User => {
-                            if in_game_driving {
-                                input_buf.push(input);
-                            }
-                        }
-                        InputType::Image => {
-                            if in_game_driving {
-                                input_buf.push(input);
-                            }
-                        }
-                    };
+        let process = move || {
+            // inputs queued before stop are still grouped, ends when every transmitter is dropped
+            while let Some(inputs) = shutdown.recv_all(&receiver) {
+                for input in inputs {
+                    if input.event_type() == EventGame::NotValidEvent {
+                        println!("received NotValidEvent: could be corruption or #[repr(c)]");
+                        continue;
+                    }
+
+                    send(policy.push(input))?;
                 }
             }
+
+            println!("exiting syncronization loop");
+            send(policy.flush())
         };
 
         let process = thread::spawn(process);
@@ -134,15 +136,15 @@ impl Process for Synchronization {
         if self.input_receiver.is_some() {
             return Err(ServiceError::NotActive);
         }
-        {
-            *self.sentinal.lock().unwrap() = false;
-        }
+        self.shutdown.cancel();
         Ok(())
     }
 
-    fn join(&mut self) {
-        if self.handle.is_some() {
-            self.handle.take().unwrap().join().unwrap();
-        }
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
     }
 }
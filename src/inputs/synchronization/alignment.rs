@@ -0,0 +1,255 @@
+use super::{Group, GroupingPolicy};
+use crate::telemetry::EventGame;
+use crate::{Input, InputHeader, InputType};
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// number of unmatched inputs kept in the report
+const RECENT_UNMATCHED: usize = 64;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlignmentCounters {
+    // frame groups sent on
+    pub frames: u64,
+    pub matched_images: u64,
+    pub matched_user: u64,
+    pub unmatched_images: u64,
+    pub unmatched_user: u64,
+    // frames without FrameEnd, discarded
+    pub incomplete_frames: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct AlignmentReport {
+    pub counters: AlignmentCounters,
+    // headers of the most recent unmatched inputs, oldest first
+    pub recent_unmatched: VecDeque<InputHeader>,
+}
+
+// shares the report of a NearestCapture policy
+#[derive(Debug, Clone)]
+pub struct AlignmentReportHandle {
+    report: Arc<Mutex<AlignmentReport>>,
+}
+
+impl AlignmentReportHandle {
+    // snapshot of the counters and recent unmatched inputs
+    pub fn report(&self) -> AlignmentReport {
+        self.report.lock().unwrap().clone()
+    }
+}
+
+impl AlignmentReport {
+    fn unmatched(&mut self, header: InputHeader) {
+        match header.source {
+            InputType::Image => self.counters.unmatched_images += 1,
+            _ => self.counters.unmatched_user += 1,
+        }
+
+        if self.recent_unmatched.len() == RECENT_UNMATCHED {
+            self.recent_unmatched.pop_front();
+        }
+        self.recent_unmatched.push_back(header);
+    }
+}
+
+struct Frame {
+    time: Duration,
+    // FrameStart and the telemetry after it, ends with FrameEnd once closed
+    telemetry: Vec<Box<dyn Input + Send>>,
+    closed: bool,
+    image: Option<Box<dyn Input + Send>>,
+    user: Vec<Box<dyn Input + Send>>,
+}
+
+impl Frame {
+    fn distance(&self, time: Duration) -> Duration {
+        time.abs_diff(self.time)
+    }
+
+    // FrameStart, telemetry, user inputs and the image in capture order, FrameEnd
+    fn into_group(mut self) -> Group {
+        let frame_end = self.telemetry.pop();
+
+        let mut matched = self.user;
+        matched.extend(self.image);
+        matched.sort_by_key(|input| input.capture().time);
+
+        let mut group = self.telemetry;
+        group.extend(matched);
+        group.extend(frame_end);
+        group
+    }
+}
+
+// one group per game frame, every image and user input goes to the frame with the nearest
+// capture time. the frame time is the capture time of its FrameStart. inputs without a frame
+// within the tolerance are reported as unmatched and discarded, a frame keeps only its nearest image.
+//
+// a decision is made once inputs captured later than the tolerance arrived, inputs are expected
+// in roughly capture order. frames are held until then and sent on at pause and stop
+pub struct NearestCapture {
+    tolerance: Duration,
+    frames: VecDeque<Frame>,
+    // images and user inputs waiting for their nearest frame
+    pending: Vec<Box<dyn Input + Send>>,
+    // latest capture time received
+    latest: Duration,
+    report: Arc<Mutex<AlignmentReport>>,
+}
+
+impl NearestCapture {
+    pub fn new(tolerance: Duration) -> NearestCapture {
+        NearestCapture {
+            tolerance,
+            frames: VecDeque::new(),
+            pending: Vec::new(),
+            latest: Duration::from_secs(0),
+            report: Arc::new(Mutex::new(AlignmentReport::default())),
+        }
+    }
+
+    // keep a handle before passing the policy to synchronization
+    pub fn report_handle(&self) -> AlignmentReportHandle {
+        AlignmentReportHandle {
+            report: self.report.clone(),
+        }
+    }
+}
+
+impl GroupingPolicy for NearestCapture {
+    // returns the frame groups that can not change anymore
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        let header = input.header();
+        self.latest = self.latest.max(header.capture.time);
+
+        match header.source {
+            InputType::Telemetry => self.push_telemetry(header.event, input),
+            InputType::Image | InputType::User => {
+                if header.capture.is_captured() {
+                    self.pending.push(input);
+                } else {
+                    self.report.lock().unwrap().unmatched(header);
+                }
+            }
+        }
+
+        self.resolve(false);
+        self.ready(false)
+    }
+
+    // decides every pending input and returns all closed frames
+    fn flush(&mut self) -> Vec<Group> {
+        self.resolve(true);
+        self.ready(true)
+    }
+}
+
+impl NearestCapture {
+    fn push_telemetry(&mut self, event: EventGame, input: Box<dyn Input + Send>) {
+        let open = self.frames.back_mut().filter(|frame| !frame.closed);
+
+        match (event, open) {
+            (EventGame::FrameStartEvent, open) => {
+                if open.is_some() {
+                    // FrameEnd never arrived
+                    self.frames.pop_back();
+                    self.report.lock().unwrap().counters.incomplete_frames += 1;
+                }
+                self.frames.push_back(Frame {
+                    time: input.capture().time,
+                    telemetry: vec![input],
+                    closed: false,
+                    image: None,
+                    user: Vec::new(),
+                });
+            }
+            (EventGame::FrameEndEvent, Some(frame)) => {
+                frame.telemetry.push(input);
+                frame.closed = true;
+            }
+            (EventGame::OtherEvent, Some(frame)) => frame.telemetry.push(input),
+            _ => {}
+        }
+    }
+
+    fn resolve(&mut self, force: bool) {
+        let tolerance = self.tolerance;
+        let latest = self.latest;
+
+        let (decided, waiting) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|input| force || latest > input.capture().time + tolerance);
+        self.pending = waiting;
+
+        let decided: Vec<Box<dyn Input + Send>> = decided;
+        let mut report = self.report.lock().unwrap();
+
+        for input in decided {
+            let header = input.header();
+            let time = header.capture.time;
+
+            let nearest = self
+                .frames
+                .iter_mut()
+                .filter(|frame| frame.distance(time) <= tolerance)
+                .min_by_key(|frame| frame.distance(time));
+
+            let frame = match nearest {
+                Some(frame) => frame,
+                None => {
+                    report.unmatched(header);
+                    continue;
+                }
+            };
+
+            match header.source {
+                InputType::Image => {
+                    let nearer = frame.image.as_ref().is_none_or(|image| {
+                        frame.distance(time) < frame.distance(image.capture().time)
+                    });
+
+                    if nearer {
+                        if let Some(previous) = frame.image.replace(input) {
+                            report.counters.matched_images -= 1;
+                            report.unmatched(previous.header());
+                        }
+                        report.counters.matched_images += 1;
+                    } else {
+                        report.unmatched(header);
+                    }
+                }
+                _ => {
+                    frame.user.push(input);
+                    report.counters.matched_user += 1;
+                }
+            }
+        }
+    }
+
+    fn ready(&mut self, force: bool) -> Vec<Group> {
+        let mut groups = Vec::new();
+        let mut report = self.report.lock().unwrap();
+
+        while let Some(frame) = self.frames.front() {
+            // pending inputs that could still match the frame are decided at frame time + tolerance
+            let settled = force || self.latest > frame.time + self.tolerance * 2;
+            if !frame.closed || !settled {
+                break;
+            }
+
+            let frame = self.frames.pop_front().unwrap();
+            report.counters.frames += 1;
+            groups.push(frame.into_group());
+        }
+
+        if force {
+            report.counters.incomplete_frames += self.frames.len() as u64;
+            self.frames.clear();
+        }
+
+        groups
+    }
+}
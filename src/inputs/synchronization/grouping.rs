@@ -0,0 +1,234 @@
+use crate::telemetry::EventGame;
+use crate::{Input, InputType};
+
+use std::time::Duration;
+
+pub type Group = Vec<Box<dyn Input + Send>>;
+
+// decides which inputs synchronization sends on together. push is called for every input
+// in arrival order and returns the groups that are complete
+pub trait GroupingPolicy: Send {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group>;
+
+    // called when synchronization stops, returns the groups that should not be lost
+    fn flush(&mut self) -> Vec<Group> {
+        Vec::new()
+    }
+}
+
+impl<P: GroupingPolicy + ?Sized> GroupingPolicy for Box<P> {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        (**self).push(input)
+    }
+
+    fn flush(&mut self) -> Vec<Group> {
+        (**self).flush()
+    }
+}
+
+// only passes inputs on between StartedEvent and PausedEvent, the inner policy is flushed on pause.
+// the started and paused events are not passed on
+pub struct PauseGate<P> {
+    inner: P,
+    in_game_driving: bool,
+}
+
+impl<P: GroupingPolicy> PauseGate<P> {
+    pub fn new(inner: P) -> PauseGate<P> {
+        PauseGate {
+            inner,
+            in_game_driving: false,
+        }
+    }
+}
+
+impl<P: GroupingPolicy> GroupingPolicy for PauseGate<P> {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        match input.event_type() {
+            EventGame::PausedEvent => {
+                //stop pushing data on the distribution services
+                println!("received PausedEvent");
+                self.in_game_driving = false;
+                self.inner.flush()
+            }
+            EventGame::StartedEvent => {
+                //start pushing data on the distribution services again
+                println!("received StartedEvent");
+                self.in_game_driving = true;
+                Vec::new()
+            }
+            _ if self.in_game_driving => self.inner.push(input),
+            _ => Vec::new(),
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Group> {
+        self.inner.flush()
+    }
+}
+
+// one group per game frame: everything received up to and including FrameEnd.
+// incomplete frames are kept on pause and stop, they continue with the next FrameEnd
+#[derive(Default)]
+pub struct FrameGrouping {
+    input_buf: Group,
+}
+
+impl FrameGrouping {
+    pub fn new() -> FrameGrouping {
+        FrameGrouping::default()
+    }
+}
+
+impl GroupingPolicy for FrameGrouping {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        match (input.input_type(), input.event_type()) {
+            (InputType::Telemetry, EventGame::FrameEndEvent) => {
+                self.input_buf.push(input);
+                vec![std::mem::take(&mut self.input_buf)]
+            }
+            (InputType::Telemetry, EventGame::FrameStartEvent | EventGame::OtherEvent)
+            | (InputType::User | InputType::Image, _) => {
+                self.input_buf.push(input);
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+// one group per N game frames, from the first FrameStart to the N-th FrameEnd.
+// completed frames are sent on at stop or pause
+pub struct FrameCount {
+    frames: usize,
+    completed: usize,
+    // length of input_buf at the last FrameEnd
+    completed_len: usize,
+    input_buf: Group,
+}
+
+impl FrameCount {
+    pub fn new(frames: usize) -> FrameCount {
+        FrameCount {
+            frames: frames.max(1),
+            completed: 0,
+            completed_len: 0,
+            input_buf: Vec::new(),
+        }
+    }
+
+    fn take_completed(&mut self) -> Vec<Group> {
+        let rest = self.input_buf.split_off(self.completed_len);
+        let group = std::mem::replace(&mut self.input_buf, rest);
+        self.completed = 0;
+        self.completed_len = 0;
+
+        if group.is_empty() {
+            Vec::new()
+        } else {
+            vec![group]
+        }
+    }
+}
+
+impl GroupingPolicy for FrameCount {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        let frame_end = input.event_type() == EventGame::FrameEndEvent;
+        self.input_buf.push(input);
+
+        if !frame_end {
+            return Vec::new();
+        }
+
+        self.completed += 1;
+        self.completed_len = self.input_buf.len();
+
+        if self.completed == self.frames {
+            self.take_completed()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Group> {
+        let groups = self.take_completed();
+        // incomplete frame
+        self.input_buf.clear();
+        groups
+    }
+}
+
+// groups inputs by capture time into windows of fixed length, a window is sent on when an
+// input of a later window arrives. groups are not framed by FrameStart and FrameEnd
+pub struct TimeWindow {
+    window: Duration,
+    current: Option<u128>,
+    input_buf: Group,
+}
+
+impl TimeWindow {
+    pub fn new(window: Duration) -> TimeWindow {
+        TimeWindow {
+            // a zero window would put every input into its own group
+            window: window.max(Duration::from_micros(1)),
+            current: None,
+            input_buf: Vec::new(),
+        }
+    }
+}
+
+impl GroupingPolicy for TimeWindow {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        let window = input.capture().time.as_nanos() / self.window.as_nanos();
+
+        // late inputs join the current window
+        let groups = match self.current {
+            Some(current) if window > current => self.flush(),
+            _ => Vec::new(),
+        };
+
+        self.current = Some(self.current.map_or(window, |current| current.max(window)));
+        self.input_buf.push(input);
+        groups
+    }
+
+    fn flush(&mut self) -> Vec<Group> {
+        self.current = None;
+        if self.input_buf.is_empty() {
+            Vec::new()
+        } else {
+            vec![std::mem::take(&mut self.input_buf)]
+        }
+    }
+}
+
+// one group per image: the inputs received since the previous image, ending with the image.
+// inputs after the last image are discarded at stop or pause
+#[derive(Default)]
+pub struct PerImage {
+    input_buf: Group,
+}
+
+impl PerImage {
+    pub fn new() -> PerImage {
+        PerImage::default()
+    }
+}
+
+impl GroupingPolicy for PerImage {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        let image = input.input_type() == InputType::Image;
+        self.input_buf.push(input);
+
+        if image {
+            vec![std::mem::take(&mut self.input_buf)]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn flush(&mut self) -> Vec<Group> {
+        self.input_buf.clear();
+        Vec::new()
+    }
+}
@@ -1,34 +1,104 @@
-use crate::{Input, InputProcessMethod, Process, ServiceError};
-use std::sync::mpsc::Sender;
+use crate::queue::InputSender;
+use crate::{CaptureSequence, InputProcessMethod, Process, ServiceError};
+use std::path::Path;
 
 #[path = "telemetry/data_layout.rs"]
 mod data_layout;
 
 pub use data_layout::{DataPair, EventGame, Packet, PacketParser, SelectGame};
 
+// typed ets2 packet layout, use ets2::Ets2Payload::from_input to read telemetry inputs
+pub use data_layout::ets2_telemetry as ets2;
+
+#[path = "telemetry/validation.rs"]
+mod validation;
+
+pub use validation::{PacketValidator, ValidationCounters, ValidationError, ValidationReport};
+
+#[path = "telemetry/recording.rs"]
+mod recording;
+
+pub use recording::{
+    Recorder, Replay, ReplaySpeed, TelemetryReader, TelemetryWriter, RECORDING_MAGIC,
+    RECORDING_VERSION,
+};
+
+// shared memory telemetry relies on win32 named objects
+#[cfg(windows)]
 #[path = "telemetry/shared_memory.rs"]
 mod shared_memory;
 
+#[cfg(windows)]
 use shared_memory::SharedMemory;
 
+// same protocol as the win32 shared memory, over shm_open and named semaphores. sem_timedwait
+// is not available on every unix, i.e. macos
+#[cfg(target_os = "linux")]
+#[path = "telemetry/posix_shared_memory.rs"]
+mod posix_shared_memory;
+
+#[cfg(target_os = "linux")]
+use posix_shared_memory::PosixSharedMemory;
+#[cfg(target_os = "linux")]
+pub use posix_shared_memory::{SharedMemoryProducer, DEFAULT_OBJECT_NAME};
+
+// writes emulated ets2 telemetry into shared memory
+#[cfg(target_os = "linux")]
+#[path = "telemetry/emulation.rs"]
+pub mod emulation;
+
 pub struct Telemetry {
     telemetry_input: Box<dyn InputProcessMethod + Send>,
 }
 
 impl Telemetry {
+    #[cfg(windows)]
     pub fn via_shared_memory(game: SelectGame) -> Telemetry {
         Telemetry {
             telemetry_input: Box::new(SharedMemory::new(game)),
         }
     }
 
+    #[cfg(target_os = "linux")]
+    pub fn via_shared_memory(game: SelectGame) -> Telemetry {
+        Telemetry {
+            telemetry_input: Box::new(PosixSharedMemory::new(game)),
+        }
+    }
+
+    // use when the producer created the shared memory objects under a different name
+    #[cfg(target_os = "linux")]
+    pub fn via_shared_memory_named(game: SelectGame, name: &str) -> Telemetry {
+        Telemetry {
+            telemetry_input: Box::new(PosixSharedMemory::with_name(game, name)),
+        }
+    }
+
+    // replay a recording made with Recorder
+    pub fn via_replay<P: AsRef<Path>>(path: P, speed: ReplaySpeed) -> Telemetry {
+        Telemetry {
+            telemetry_input: Box::new(Replay::new(path, speed)),
+        }
+    }
+
+    // None when the method does not validate packets
+    pub fn validation_report(&self) -> Option<ValidationReport> {
+        self.telemetry_input.validation_report()
+    }
+
     pub fn get_method(&self) -> &str {
         self.telemetry_input.method()
     }
 
-    pub fn set_transmitter(&mut self, transmitter: Sender<Box<dyn Input + Send>>) {
+    pub fn set_transmitter(&mut self, transmitter: InputSender) {
         self.telemetry_input.set_transmitter(transmitter);
     }
+
+    // every process a restart factory creates gets a clone of the same sequence, capture sequence
+    // numbers stay unique for the source over the whole session
+    pub fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.telemetry_input.set_capture_sequence(sequence);
+    }
 }
 
 impl Process for Telemetry {
@@ -42,7 +112,11 @@ impl Process for Telemetry {
         Ok(())
     }
 
-    fn join(&mut self) {
-        self.telemetry_input.join();
+    fn join(&mut self) -> Result<(), ServiceError> {
+        self.telemetry_input.join()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.telemetry_input.is_finished()
     }
 }
@@ -1,8 +1,9 @@
 use std::ffi::c_void;
 
-use crate::Input;
+use crate::telemetry::PacketValidator;
+use crate::{CaptureSequence, CaptureStamp, Input, InputHeader};
 
-mod ets2_telemetry;
+pub mod ets2_telemetry;
 
 #[derive(Debug, Clone, Copy)]
 pub enum SelectGame {
@@ -11,9 +12,55 @@ pub enum SelectGame {
 
 impl SelectGame {
     pub fn get_events(&self) {}
+
+    // stable identifier, used in recordings
+    pub fn code(&self) -> u16 {
+        match self {
+            SelectGame::ETS2 => 1,
+        }
+    }
+
+    // false when the raw event type is unknown, i.e. corruption
+    pub fn is_game_event(&self, raw_event_type: u32) -> bool {
+        match self {
+            SelectGame::ETS2 => {
+                ets2_telemetry::ETS2Event::new(raw_event_type)
+                    != ets2_telemetry::ETS2Event::NotValid
+            }
+        }
+    }
+
+    // payload size of the raw event type, None when the type is not a game event or its layout
+    // is not defined, the length is not checked then
+    pub fn expected_length(&self, raw_event_type: u32) -> Option<u32> {
+        match self {
+            SelectGame::ETS2 => ets2_telemetry::ETS2Event::new(raw_event_type).expected_length(),
+        }
+    }
+
+    // largest payload of a packet, the payload of a recording is never larger
+    pub fn max_payload_size(&self) -> usize {
+        match self {
+            SelectGame::ETS2 => ets2_telemetry::MAX_PAYLOAD_SIZE,
+        }
+    }
+
+    // bytes of the shared memory layout, is_alive followed by the packet
+    pub fn shared_memory_size(&self) -> usize {
+        match self {
+            SelectGame::ETS2 => ets2_telemetry::SHARED_MEMORY_SIZE,
+        }
+    }
+
+    pub fn from_code(code: u16) -> Option<SelectGame> {
+        match code {
+            1 => Some(SelectGame::ETS2),
+            _ => None,
+        }
+    }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EventGame {
     PausedEvent,
     StartedEvent,
@@ -27,31 +74,84 @@ pub enum EventGame {
 pub struct DataPair(pub bool, pub Box<dyn Input + Send>);
 pub trait Packet: Input {
     fn parser(&mut self, address: *mut c_void) -> bool;
+    fn set_capture(&mut self, capture: CaptureStamp);
+    // rebuild a packet from a recorded header (sequence, event code, game time, payload size) and payload
+    fn decode(&mut self, header: &InputHeader, payload: &[u8]);
 }
-#[derive(Debug, Clone, Copy)]
+// clones share the validation report and the capture sequence
+#[derive(Debug, Clone)]
 pub struct PacketParser {
     game: SelectGame,
+    validator: PacketValidator,
+    sequence: CaptureSequence,
 }
 
 impl PacketParser {
     pub fn new(selected_game: SelectGame) -> PacketParser {
         PacketParser {
             game: selected_game,
+            validator: PacketValidator::new(selected_game),
+            sequence: CaptureSequence::new(),
         }
     }
 
-    pub fn data(self, address: *mut c_void) -> DataPair {
+    // copy the packet without validation, stamped with the capture time of the copy
+    pub fn data(&self, address: *mut c_void) -> DataPair {
         // could use a match statement for enum type i.e. game telemetry data format
 
         let mut p = match self.game {
-            SelectGame::ETS2 => ets2_telemetry::telemetry_packet::new(),
+            SelectGame::ETS2 => ets2_telemetry::TelemetryPacket::new(),
         };
 
         let is_alive = p.parser(address);
+        p.set_capture(self.sequence.stamp());
 
-        DataPair {
-            0: is_alive,
-            1: Box::new(p),
+        DataPair(is_alive, Box::new(p))
+    }
+
+    // copy and validate the packet, the packet is None when it failed validation
+    pub fn validated(&mut self, address: *mut c_void) -> (bool, Option<Box<dyn Input + Send>>) {
+        let DataPair(is_alive, packet) = self.data(address);
+
+        (is_alive, self.validate(packet))
+    }
+
+    // the packet is None when it failed validation
+    pub fn validate(&mut self, packet: Box<dyn Input + Send>) -> Option<Box<dyn Input + Send>> {
+        match self.validator.validate(packet.as_ref()) {
+            Ok(_) => Some(packet),
+            Err(err) => {
+                println!("dropped telemetry packet, {}", err);
+                None
+            }
         }
     }
+
+    pub fn sequence(&self) -> &CaptureSequence {
+        &self.sequence
+    }
+
+    // clones made before keep the previous sequence
+    pub fn set_sequence(&mut self, sequence: CaptureSequence) {
+        self.sequence = sequence;
+    }
+
+    pub fn validator(&self) -> &PacketValidator {
+        &self.validator
+    }
+
+    pub fn record(&self, header: &InputHeader, payload: &[u8]) -> Box<dyn Input + Send> {
+        let mut p = match self.game {
+            SelectGame::ETS2 => ets2_telemetry::TelemetryPacket::new(),
+        };
+
+        p.decode(header, payload);
+        p.set_capture(self.sequence.stamp());
+
+        Box::new(p)
+    }
+
+    pub fn game(&self) -> SelectGame {
+        self.game
+    }
 }
@@ -0,0 +1,226 @@
+use crate::telemetry::data_layout::ets2_telemetry::{
+    ETS2Event, FrameEnd, FrameStart, TelemetryPacket,
+};
+use crate::telemetry::{SharedMemoryProducer, DEFAULT_OBJECT_NAME};
+use crate::ServiceError;
+
+use std::io::{self, Write};
+use std::mem;
+use std::thread;
+use std::time::{Duration, Instant};
+
+// how long a single write waits for the reader before retrying
+const WRITE_INTERVAL: Duration = Duration::from_millis(50);
+
+// game time step used when the emulation is not throttled, 60 frames per second
+const UNTHROTTLED_STEP_US: u64 = 16_667;
+
+// scripted events, emitted before the frame with the given index
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptEvent {
+    Pause(u64),
+    Resume(u64),
+    Gameplay(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct EmulationConfig {
+    // name of the shared memory objects
+    pub name: String,
+    // number of FrameStart/FrameEnd pairs
+    pub frames: u64,
+    // frames per second, zero writes as fast as the reader consumes
+    pub rate: f64,
+    pub script: Vec<ScriptEvent>,
+    // give up when the reader does not consume a packet within the timeout, wait forever when none
+    pub reader_timeout: Option<Duration>,
+}
+
+impl Default for EmulationConfig {
+    // same packet count as TelemetryEmulation.exe: configuration, started and 11590 frames
+    fn default() -> Self {
+        EmulationConfig {
+            name: String::from(DEFAULT_OBJECT_NAME),
+            frames: 11590,
+            rate: 0.0,
+            script: Vec::new(),
+            reader_timeout: None,
+        }
+    }
+}
+
+// writes ETS2 telemetry packets into shared memory, replaces TelemetryEmulation.exe.
+// every written packet is printed as id-type-length-time
+pub struct ETS2Emulator {
+    config: EmulationConfig,
+    producer: SharedMemoryProducer,
+    next_id: u64,
+}
+
+impl ETS2Emulator {
+    pub fn new(config: EmulationConfig) -> Result<ETS2Emulator, ServiceError> {
+        let producer = SharedMemoryProducer::create(&config.name)?;
+
+        Ok(ETS2Emulator {
+            config,
+            producer,
+            next_id: 0,
+        })
+    }
+
+    // number of packets a run writes
+    pub fn packet_count(&self) -> u64 {
+        let frames = self.config.frames;
+        let scripted = self.config.script.iter().filter(|event| match event {
+            ScriptEvent::Pause(at) | ScriptEvent::Resume(at) | ScriptEvent::Gameplay(at) => {
+                *at < frames
+            }
+        });
+
+        2 + frames * 2 + scripted.count() as u64
+    }
+
+    // blocks until every packet has been consumed by a reader
+    pub fn run<W: Write>(&mut self, out: &mut W) -> Result<u64, ServiceError> {
+        let step_us = if self.config.rate > 0.0 {
+            (1_000_000.0 / self.config.rate) as u64
+        } else {
+            UNTHROTTLED_STEP_US
+        };
+
+        let mut packets = Vec::with_capacity(self.packet_count() as usize);
+
+        packets.push(self.event(ETS2Event::Configuration, 0));
+        packets.push(self.event(ETS2Event::Started, 0));
+
+        for frame in 0..self.config.frames {
+            let time = frame * step_us;
+
+            for event in self.config.script.clone() {
+                match event {
+                    ScriptEvent::Pause(at) if at == frame => {
+                        packets.push(self.event(ETS2Event::Paused, time))
+                    }
+                    ScriptEvent::Resume(at) if at == frame => {
+                        packets.push(self.event(ETS2Event::Started, time))
+                    }
+                    ScriptEvent::Gameplay(at) if at == frame => {
+                        packets.push(self.event(ETS2Event::Gameplay, time))
+                    }
+                    _ => (),
+                }
+            }
+
+            packets.push(self.frame_start(time));
+            packets.push(self.frame_end(frame, time));
+        }
+
+        let frame_interval = if self.config.rate > 0.0 {
+            Some(Duration::from_secs_f64(1.0 / self.config.rate))
+        } else {
+            None
+        };
+
+        let mut next_frame = Instant::now();
+        let last = packets.len().saturating_sub(1);
+
+        for (i, packet) in packets.iter().enumerate() {
+            // throttle on every frame start
+            if let Some(interval) = frame_interval {
+                if packet.type_ == ETS2Event::FrameStart.to_raw() {
+                    let now = Instant::now();
+                    if next_frame > now {
+                        thread::sleep(next_frame - now);
+                    }
+                    next_frame += interval;
+                }
+            }
+
+            // the last packet tells the reader to stop
+            self.write(&packet.shared_memory_bytes(i != last))?;
+
+            writeln!(
+                out,
+                "{}-{}-{}-{}",
+                packet.id, packet.type_, packet.length, packet.time
+            )
+            .map_err(ServiceError::Io)?;
+        }
+
+        Ok(packets.len() as u64)
+    }
+
+    fn write(&self, data: &[u8]) -> Result<(), ServiceError> {
+        let started = Instant::now();
+
+        while !self.producer.write(data, WRITE_INTERVAL)? {
+            if let Some(timeout) = self.config.reader_timeout {
+                if started.elapsed() > timeout {
+                    return Err(ServiceError::Ipc {
+                        call: String::from("write"),
+                        source: io::Error::from(io::ErrorKind::TimedOut),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn packet(&mut self, event: ETS2Event, length: usize, time: u64) -> TelemetryPacket {
+        let mut packet = TelemetryPacket::new();
+        packet.type_ = event.to_raw();
+        packet.length = length as u32;
+        packet.id = self.next_id;
+        packet.time = time;
+
+        self.next_id += 1;
+        packet
+    }
+
+    fn event(&mut self, event: ETS2Event, time: u64) -> TelemetryPacket {
+        self.packet(event, 0, time)
+    }
+
+    fn frame_start(&mut self, time: u64) -> TelemetryPacket {
+        let mut packet = self.packet(ETS2Event::FrameStart, mem::size_of::<FrameStart>(), time);
+
+        packet.data.frame_start_data = FrameStart {
+            render_time: time,
+            simulation_time: time,
+            paused_simulation_time: time,
+            ..Default::default()
+        };
+
+        packet
+    }
+
+    // synthetic drive: accelerate through the gears and weave gently across the lane
+    fn frame_end(&mut self, frame: u64, time: u64) -> TelemetryPacket {
+        let mut packet = self.packet(ETS2Event::FrameEnd, mem::size_of::<FrameEnd>(), time);
+
+        let seconds = time as f32 / 1_000_000.0;
+        let speed = (seconds * 1.5).min(25.0);
+        let steering = (seconds * 0.5).sin() * 0.1;
+        let throttle = if speed < 25.0 { 0.6 } else { 0.3 };
+
+        let mut data = FrameEnd {
+            engine_gear: 1 + (speed / 5.0) as u32,
+            speed,
+            engine_rpm: 800.0 + (speed % 5.0) * 250.0,
+            input_steering: steering,
+            input_throttle: throttle,
+            effective_steering: steering,
+            effective_throttle: throttle,
+            navigation_speed_limit: 25.0,
+            ..Default::default()
+        };
+        data.local_linear_velocity.z = -speed;
+        data.world_placement.position.x = frame as f64 * 0.01;
+        data.world_placement.position.z = -(seconds as f64 * speed as f64);
+        data.world_placement.orientation.heading = steering;
+
+        packet.data.frame_end_data = data;
+        packet
+    }
+}
@@ -1,10 +1,14 @@
-use std::{fmt, mem, ptr};
+use std::any::Any;
+use std::{cmp, fmt, mem, ptr, slice};
 
 use std::ffi::c_void;
 
 use crate::telemetry::{EventGame, Packet};
-use crate::{Input, InputType};
+use crate::{CaptureStamp, ImageEncoding, Input, InputHeader, InputType, Serialized, ServiceError};
+use serde::Serialize;
+use serde_json::json;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ETS2Event {
     Paused,
     Started,
@@ -28,6 +32,41 @@ impl ETS2Event {
         }
     }
 
+    pub fn to_raw(&self) -> u32 {
+        match self {
+            Self::FrameStart => 1,
+            Self::FrameEnd => 2,
+            Self::Paused => 3,
+            Self::Started => 4,
+            Self::Configuration => 5,
+            Self::Gameplay => 6,
+            Self::NotValid => 0,
+        }
+    }
+
+    // payload size written by the producer for each event. None when the layout is not defined
+    // here, the plugin may attach data to configuration and gameplay events
+    pub fn expected_length(&self) -> Option<u32> {
+        match self {
+            Self::FrameStart => Some(mem::size_of::<FrameStart>() as u32),
+            Self::FrameEnd => Some(mem::size_of::<FrameEnd>() as u32),
+            Self::Paused | Self::Started => Some(0),
+            Self::Configuration | Self::Gameplay | Self::NotValid => None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Self::FrameStart => "FrameStart",
+            Self::FrameEnd => "FrameEnd",
+            Self::Paused => "Paused",
+            Self::Started => "Started",
+            Self::Configuration => "Configuration",
+            Self::Gameplay => "Gameplay",
+            Self::NotValid => "NotValid",
+        }
+    }
+
     pub fn to_eventgame(&self) -> EventGame {
         match self {
             Self::FrameStart => EventGame::FrameStartEvent,
@@ -41,144 +80,552 @@ impl ETS2Event {
 }
 
 // use #[repr(C, packed)] not perform alignment. Data has been aligned manually
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-struct euler {
-    heading: f32,
-    pitch: f32,
-    roll: f32,
+pub struct Euler {
+    pub heading: f32,
+    pub pitch: f32,
+    pub roll: f32,
     // 12 bytes
 }
 
 // could implement with generics, however will run into unknown aligement issues
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-struct fvector {
-    x: f32,
-    y: f32,
-    z: f32,
+pub struct FVector {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
     // 12 bytes
 }
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-struct dvector {
-    x: f64,
-    y: f64,
-    z: f64,
+pub struct DVector {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
     // 24 bytes
 }
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-struct fplacement {
-    position: fvector,
-    orientation: euler,
+pub struct FPlacement {
+    pub position: FVector,
+    pub orientation: Euler,
     // 24 bytes
 }
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-struct dplacement {
-    position: dvector,
-    orientation: euler,
-    padding: u32,
+pub struct DPlacement {
+    pub position: DVector,
+    pub orientation: Euler,
+    #[serde(skip)]
+    pub padding: u32,
     // 40 bytes
 }
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-pub struct frame_start {
-    flags: u32,
-    padding: u32,
-    render_time: u64,
-    simulation_time: u64,
-    paused_simulation_time: u64,
+pub struct FrameStart {
+    pub flags: u32,
+    #[serde(skip)]
+    pub padding: u32,
+    pub render_time: u64,
+    pub simulation_time: u64,
+    pub paused_simulation_time: u64,
     // 32 bytes
 }
-#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, PartialOrd, Clone, Copy, Serialize)]
 #[repr(C)]
-pub struct frame_end {
-    engine_gear: u32,
-    speed: f32,
-    engine_rpm: f32,
-    input_steering: f32,
-    input_throttle: f32,
-    input_brake: f32,
-    input_clutch: f32,
-    effective_steering: f32,
-    effective_throttle: f32,
-    effective_brake: f32,
-    effective_clutch: f32,
-    cruise_control: f32,
-    navigation_speed_limit: f32,
-    padding: u32,
-
-    cabin_angular_velocity: fvector,
-    cabin_angular_acceleration: fvector,
-    local_linear_velocity: fvector,
-    local_angular_velocity: fvector,
-    local_linear_acceleration: fvector,
-    local_angular_acceleration: fvector,
-
-    cabin_offset: fplacement,
-    head_offset: fplacement,
-    world_placement: dplacement,
+pub struct FrameEnd {
+    pub engine_gear: u32,
+    pub speed: f32,
+    pub engine_rpm: f32,
+    pub input_steering: f32,
+    pub input_throttle: f32,
+    pub input_brake: f32,
+    pub input_clutch: f32,
+    pub effective_steering: f32,
+    pub effective_throttle: f32,
+    pub effective_brake: f32,
+    pub effective_clutch: f32,
+    pub cruise_control: f32,
+    pub navigation_speed_limit: f32,
+    #[serde(skip)]
+    pub padding: u32,
+
+    pub cabin_angular_velocity: FVector,
+    pub cabin_angular_acceleration: FVector,
+    pub local_linear_velocity: FVector,
+    pub local_angular_velocity: FVector,
+    pub local_linear_acceleration: FVector,
+    pub local_angular_acceleration: FVector,
+
+    pub cabin_offset: FPlacement,
+    pub head_offset: FPlacement,
+    pub world_placement: DPlacement,
     // 216 bytes
 }
 
+// orientation is stored in unit range, <0,1) corresponds to <0,360) degrees
+impl Euler {
+    pub fn heading_degrees(&self) -> f32 {
+        self.heading * 360.0
+    }
+
+    pub fn pitch_degrees(&self) -> f32 {
+        self.pitch * 360.0
+    }
+
+    pub fn roll_degrees(&self) -> f32 {
+        self.roll * 360.0
+    }
+}
+
+// times are in microseconds
+impl FrameStart {
+    pub fn render_time_us(&self) -> u64 {
+        self.render_time
+    }
+
+    pub fn simulation_time_us(&self) -> u64 {
+        self.simulation_time
+    }
+
+    pub fn paused_simulation_time_us(&self) -> u64 {
+        self.paused_simulation_time
+    }
+}
+
+const MS_TO_KMH: f32 = 3.6;
+
+impl FrameEnd {
+    // truck speed in m/s, negative when reversing
+    pub fn speed_ms(&self) -> f32 {
+        self.speed
+    }
+
+    // truck speed in km/h, negative when reversing
+    pub fn speed_kmh(&self) -> f32 {
+        self.speed * MS_TO_KMH
+    }
+
+    // engine revolutions per minute
+    pub fn rpm(&self) -> f32 {
+        self.engine_rpm
+    }
+
+    // selected gear, zero is neutral and reverse gears are negative
+    pub fn gear(&self) -> i32 {
+        self.engine_gear as i32
+    }
+
+    // steering input from the player, <-1,1> positive steers left
+    pub fn steering(&self) -> f32 {
+        self.input_steering
+    }
+
+    // throttle input from the player, <0,1>
+    pub fn throttle(&self) -> f32 {
+        self.input_throttle
+    }
+
+    // brake input from the player, <0,1>
+    pub fn brake(&self) -> f32 {
+        self.input_brake
+    }
+
+    // clutch input from the player, <0,1>
+    pub fn clutch(&self) -> f32 {
+        self.input_clutch
+    }
+
+    // cruise control speed in m/s, zero when disabled
+    pub fn cruise_control_ms(&self) -> f32 {
+        self.cruise_control
+    }
+
+    // cruise control speed in km/h, zero when disabled
+    pub fn cruise_control_kmh(&self) -> f32 {
+        self.cruise_control * MS_TO_KMH
+    }
+
+    // speed limit of the current road in m/s, zero when there is no limit
+    pub fn speed_limit_ms(&self) -> f32 {
+        self.navigation_speed_limit
+    }
+
+    // speed limit of the current road in km/h, zero when there is no limit
+    pub fn speed_limit_kmh(&self) -> f32 {
+        self.navigation_speed_limit * MS_TO_KMH
+    }
+
+    // velocity in m/s in the truck space, x right, y up and z backwards
+    pub fn local_velocity(&self) -> FVector {
+        self.local_linear_velocity
+    }
+
+    // truck position in meters and orientation in the world space
+    pub fn world_placement(&self) -> DPlacement {
+        self.world_placement
+    }
+}
+
+// blends two samples, t is in <0,1> from self to next. continuous values are interpolated
+// linearly, discrete values (gear, flags, set speeds) are held from self
+pub trait Interpolate {
+    fn interpolate(&self, next: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, next: &f32, t: f32) -> f32 {
+        self + (next - self) * t
+    }
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, next: &f64, t: f32) -> f64 {
+        self + (next - self) * t as f64
+    }
+}
+
+impl Interpolate for FVector {
+    fn interpolate(&self, next: &FVector, t: f32) -> FVector {
+        FVector {
+            x: self.x.interpolate(&next.x, t),
+            y: self.y.interpolate(&next.y, t),
+            z: self.z.interpolate(&next.z, t),
+        }
+    }
+}
+
+impl Interpolate for DVector {
+    fn interpolate(&self, next: &DVector, t: f32) -> DVector {
+        DVector {
+            x: self.x.interpolate(&next.x, t),
+            y: self.y.interpolate(&next.y, t),
+            z: self.z.interpolate(&next.z, t),
+        }
+    }
+}
+
+// angles are in unit range, take the short way around
+fn interpolate_angle(from: f32, to: f32, t: f32) -> f32 {
+    let delta = (to - from + 0.5).rem_euclid(1.0) - 0.5;
+    (from + delta * t).rem_euclid(1.0)
+}
+
+impl Interpolate for Euler {
+    fn interpolate(&self, next: &Euler, t: f32) -> Euler {
+        Euler {
+            heading: interpolate_angle(self.heading, next.heading, t),
+            pitch: interpolate_angle(self.pitch, next.pitch, t),
+            roll: interpolate_angle(self.roll, next.roll, t),
+        }
+    }
+}
+
+impl Interpolate for FPlacement {
+    fn interpolate(&self, next: &FPlacement, t: f32) -> FPlacement {
+        FPlacement {
+            position: self.position.interpolate(&next.position, t),
+            orientation: self.orientation.interpolate(&next.orientation, t),
+        }
+    }
+}
+
+impl Interpolate for DPlacement {
+    fn interpolate(&self, next: &DPlacement, t: f32) -> DPlacement {
+        DPlacement {
+            position: self.position.interpolate(&next.position, t),
+            orientation: self.orientation.interpolate(&next.orientation, t),
+            padding: self.padding,
+        }
+    }
+}
+
+impl Interpolate for FrameEnd {
+    fn interpolate(&self, next: &FrameEnd, t: f32) -> FrameEnd {
+        FrameEnd {
+            speed: self.speed.interpolate(&next.speed, t),
+            engine_rpm: self.engine_rpm.interpolate(&next.engine_rpm, t),
+            input_steering: self.input_steering.interpolate(&next.input_steering, t),
+            input_throttle: self.input_throttle.interpolate(&next.input_throttle, t),
+            input_brake: self.input_brake.interpolate(&next.input_brake, t),
+            input_clutch: self.input_clutch.interpolate(&next.input_clutch, t),
+            effective_steering: self
+                .effective_steering
+                .interpolate(&next.effective_steering, t),
+            effective_throttle: self
+                .effective_throttle
+                .interpolate(&next.effective_throttle, t),
+            effective_brake: self.effective_brake.interpolate(&next.effective_brake, t),
+            effective_clutch: self.effective_clutch.interpolate(&next.effective_clutch, t),
+            cabin_angular_velocity: self
+                .cabin_angular_velocity
+                .interpolate(&next.cabin_angular_velocity, t),
+            cabin_angular_acceleration: self
+                .cabin_angular_acceleration
+                .interpolate(&next.cabin_angular_acceleration, t),
+            local_linear_velocity: self
+                .local_linear_velocity
+                .interpolate(&next.local_linear_velocity, t),
+            local_angular_velocity: self
+                .local_angular_velocity
+                .interpolate(&next.local_angular_velocity, t),
+            local_linear_acceleration: self
+                .local_linear_acceleration
+                .interpolate(&next.local_linear_acceleration, t),
+            local_angular_acceleration: self
+                .local_angular_acceleration
+                .interpolate(&next.local_angular_acceleration, t),
+            cabin_offset: self.cabin_offset.interpolate(&next.cabin_offset, t),
+            head_offset: self.head_offset.interpolate(&next.head_offset, t),
+            world_placement: self.world_placement.interpolate(&next.world_placement, t),
+            // gear, cruise control and speed limit change in steps
+            ..*self
+        }
+    }
+}
+
+// safe view of the event data, selected by the packet type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ets2Payload {
+    FrameStart(FrameStart),
+    FrameEnd(FrameEnd),
+    Paused,
+    Started,
+    Configuration,
+    Gameplay,
+    NotValid,
+}
+
+impl Ets2Payload {
+    // None when the input is not an ets2 telemetry packet
+    pub fn from_input(input: &dyn Input) -> Option<Ets2Payload> {
+        input
+            .as_any()
+            .downcast_ref::<TelemetryPacket>()
+            .map(|packet| packet.event_payload())
+    }
+
+    pub fn frame_end(&self) -> Option<&FrameEnd> {
+        match self {
+            Ets2Payload::FrameEnd(frame_end) => Some(frame_end),
+            _ => None,
+        }
+    }
+
+    pub fn frame_start(&self) -> Option<&FrameStart> {
+        match self {
+            Ets2Payload::FrameStart(frame_start) => Some(frame_start),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 #[repr(C)]
-pub union event_data {
-    pub frame_end_data: frame_end,
-    pub frame_start_data: frame_start,
+pub union EventData {
+    pub frame_end_data: FrameEnd,
+    pub frame_start_data: FrameStart,
     pub no_data: u32,
 }
 #[derive(Clone, Copy)]
 #[repr(C)]
-pub struct telemetry_packet {
+pub struct TelemetryPacket {
     pub type_: u32,
     pub length: u32,
     pub id: u64,
     pub time: u64,
-    pub data: event_data,
+    pub data: EventData,
+    // set by the input source when the packet is read, not part of the shared memory layout
+    pub capture: CaptureStamp,
 }
 
-impl telemetry_packet {
-    pub fn new() -> telemetry_packet {
-        let x: telemetry_packet = unsafe { mem::zeroed() };
+impl TelemetryPacket {
+    pub fn new() -> TelemetryPacket {
+        let x: TelemetryPacket = unsafe { mem::zeroed() };
         x
     }
+
+    pub fn event(&self) -> ETS2Event {
+        ETS2Event::new(self.type_)
+    }
+
+    // the union member is only read when the type and length say it was written
+    pub fn event_payload(&self) -> Ets2Payload {
+        match self.event() {
+            ETS2Event::FrameStart if self.length as usize >= mem::size_of::<FrameStart>() => {
+                Ets2Payload::FrameStart(unsafe { self.data.frame_start_data })
+            }
+            ETS2Event::FrameEnd if self.length as usize >= mem::size_of::<FrameEnd>() => {
+                Ets2Payload::FrameEnd(unsafe { self.data.frame_end_data })
+            }
+            ETS2Event::FrameStart | ETS2Event::FrameEnd | ETS2Event::NotValid => {
+                Ets2Payload::NotValid
+            }
+            ETS2Event::Paused => Ets2Payload::Paused,
+            ETS2Event::Started => Ets2Payload::Started,
+            ETS2Event::Configuration => Ets2Payload::Configuration,
+            ETS2Event::Gameplay => Ets2Payload::Gameplay,
+        }
+    }
+}
+
+impl Default for TelemetryPacket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// packet as written by the telemetry plugin
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct SharedMemoryPacket {
+    type_: u32,
+    length: u32,
+    id: u64,
+    time: u64,
+    data: EventData,
+}
+
+// shared memory layout read by Packet::parser, used by producers to write packets
+#[repr(C)]
+struct SharedMemoryPair(bool, SharedMemoryPacket);
+
+// bytes read by Packet::parser, a smaller memory file can not hold a packet
+pub const SHARED_MEMORY_SIZE: usize = mem::size_of::<SharedMemoryPair>();
+
+// bytes of the event data union, a packet never carries a larger payload
+pub const MAX_PAYLOAD_SIZE: usize = mem::size_of::<EventData>();
+
+impl TelemetryPacket {
+    pub fn shared_memory_bytes(&self, is_alive: bool) -> Vec<u8> {
+        let packet = SharedMemoryPacket {
+            type_: self.type_,
+            length: self.length,
+            id: self.id,
+            time: self.time,
+            data: self.data,
+        };
+
+        let mut data = vec![0u8; mem::size_of::<SharedMemoryPair>()];
+        let packet_offset = data.len() - mem::size_of::<SharedMemoryPacket>();
+
+        data[0] = is_alive as u8;
+        unsafe {
+            ptr::copy_nonoverlapping(
+                &packet as *const SharedMemoryPacket as *const u8,
+                data[packet_offset..].as_mut_ptr(),
+                mem::size_of::<SharedMemoryPacket>(),
+            )
+        };
+
+        data
+    }
 }
 
-impl fmt::Debug for telemetry_packet {
+impl fmt::Debug for TelemetryPacket {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("telemetry_packet")
             .field("type", &self.type_)
             .field("length", &self.length)
             .field("id", &self.id)
             .field("time", &self.time)
+            .field("capture", &self.capture)
             .finish()
     }
 }
 
-impl Packet for telemetry_packet {
+impl Packet for TelemetryPacket {
     fn parser(&mut self, address: *mut c_void) -> bool {
-        #[repr(C)]
-        struct Pair(bool, telemetry_packet);
+        let SharedMemoryPair(is_alive, packet) = unsafe { ptr::read(address as *const _) };
+        *self = TelemetryPacket {
+            type_: packet.type_,
+            length: packet.length,
+            id: packet.id,
+            time: packet.time,
+            data: packet.data,
+            capture: CaptureStamp::default(),
+        };
+        is_alive
+    }
 
-        let rdata: Pair = unsafe { ptr::read(address as *const _) };
-        *self = rdata.1; //telemetry_packet { ..packet };
-        rdata.0
+    fn set_capture(&mut self, capture: CaptureStamp) {
+        self.capture = capture;
+    }
+
+    fn decode(&mut self, header: &InputHeader, payload: &[u8]) {
+        *self = TelemetryPacket::new();
+
+        self.id = header.sequence;
+        self.type_ = header.event_code;
+        self.time = header.game_time;
+        self.length = header.payload_size;
+
+        let size = cmp::min(payload.len(), mem::size_of::<EventData>());
+        unsafe {
+            ptr::copy_nonoverlapping(
+                payload.as_ptr(),
+                &mut self.data as *mut EventData as *mut u8,
+                size,
+            )
+        };
     }
 }
 
-impl Input for telemetry_packet {
-    fn input_type(&self) -> InputType {
-        InputType::Telemetry
+impl Input for TelemetryPacket {
+    fn header(&self) -> InputHeader {
+        InputHeader {
+            source: InputType::Telemetry,
+            sequence: self.id,
+            event: self.event().to_eventgame(),
+            event_code: self.type_,
+            game_time: self.time,
+            capture: self.capture,
+            payload_size: self.length,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 
-    fn event_type(&self) -> EventGame {
-        ETS2Event::new(self.type_).to_eventgame()
+    fn payload(&self) -> &[u8] {
+        // length is reported by the producer, never read past the union
+        let size = cmp::min(self.length as usize, mem::size_of::<EventData>());
+
+        unsafe { slice::from_raw_parts(&self.data as *const EventData as *const u8, size) }
     }
 
-    fn header(&self) -> (u64, u32, u64, u32) {
-        (self.id, self.type_, self.time, self.length)
+    fn serialize(&self, _image_encoding: ImageEncoding) -> Result<Serialized, ServiceError> {
+        let event = ETS2Event::new(self.type_);
+
+        let mut value = json!({
+            "id": self.id,
+            "type": self.type_,
+            "event": event.name(),
+            "time": self.time,
+            "length": self.length,
+            "capture_sequence": self.capture.sequence,
+            "capture_time": self.capture.time.as_micros() as u64,
+        });
+
+        // union member is selected by the event type
+        let data = match self.event_payload() {
+            Ets2Payload::FrameStart(frame_start) => {
+                serde_json::to_value(frame_start).map(|data| Some(("frame_start", data)))
+            }
+            Ets2Payload::FrameEnd(frame_end) => {
+                serde_json::to_value(frame_end).map(|data| Some(("frame_end", data)))
+            }
+            _ => Ok(None),
+        }
+        .map_err(|err| ServiceError::Serialization(Box::new(err)))?;
+
+        if let Some((key, data)) = data {
+            value[key] = data;
+        }
+
+        Ok(Serialized::Json(value))
     }
 }
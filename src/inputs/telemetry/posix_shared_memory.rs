@@ -0,0 +1,585 @@
+use crate::queue::InputSender;
+use crate::utils::{join_thread, thread_finished};
+use crate::{
+    posix_get_last_error, CancellationToken, CaptureSequence, InputProcessMethod, ServiceError,
+    SHUTDOWN_POLL_INTERVAL,
+};
+use std::ffi::{c_void, CString};
+use std::{io, mem, ptr};
+
+use std::result::Result;
+
+use std::thread;
+use std::time::Duration;
+
+use crate::telemetry::{PacketParser, SelectGame, ValidationReport};
+
+// default object names, same as the named win32 objects used by the ets2 telemetry plugin
+pub const DEFAULT_OBJECT_NAME: &str = "ETS2Telemetry";
+
+// size of the memory file created by a producer, large enough for every telemetry packet
+pub const MEMORY_FILE_SIZE: usize = 4096;
+
+//posix names have to start with a slash
+fn object_name(name: &str, suffix: &str) -> CString {
+    CString::new(format!("/{}{}", name, suffix)).expect("object name contains a nul byte")
+}
+
+//use to setup posix inter process communication and sychronization objects.
+// mirrors the win32 protocol: a memory file, a mutex and a read and write event,
+// all implemented with named process-shared semaphores
+struct InterProcessCommunication {
+    name: String,
+    owner: bool,
+    memory_file_fd: Option<libc::c_int>,
+    memory_file_start_address: Option<*mut c_void>,
+    memory_file_size: usize,
+    // inode of the memory file, a restarted producer creates a new one
+    memory_file_inode: Option<libc::ino_t>,
+    mutex_obj: Option<*mut libc::sem_t>,
+    read_event_obj: Option<*mut libc::sem_t>,
+    write_event_obj: Option<*mut libc::sem_t>,
+}
+
+impl InterProcessCommunication {
+    pub fn new(name: &str) -> InterProcessCommunication {
+        InterProcessCommunication {
+            name: String::from(name),
+            owner: false,
+            memory_file_fd: None,
+            memory_file_start_address: None,
+            memory_file_size: 0,
+            memory_file_inode: None,
+            mutex_obj: None,
+            read_event_obj: None,
+            write_event_obj: None,
+        }
+    }
+
+    // client side, open the objects created by the producer. the memory file has to hold at least
+    // min_size bytes, the packet layout read from it
+    pub fn connect(&mut self, min_size: usize) -> Result<(), ServiceError> {
+        // open named memory file in read write mode, the mutex lives outside the mapping
+        let memory_file_name = object_name(&self.name, "");
+        let fd = unsafe { libc::shm_open(memory_file_name.as_ptr(), libc::O_RDWR, 0) };
+
+        if fd == -1 {
+            return Err(posix_get_last_error("shm_open"));
+        }
+
+        self.memory_file_fd = Some(fd);
+
+        // map the entire memory file, size is decided by the producer
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } == -1 {
+            return Err(posix_get_last_error("fstat"));
+        }
+
+        let size = stat.st_size as usize;
+        if size < min_size {
+            return Err(ServiceError::Ipc {
+                call: String::from("fstat - memory file too small"),
+                source: io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} bytes, the packet layout needs {}", size, min_size),
+                ),
+            });
+        }
+
+        self.map(size)?;
+        self.memory_file_inode = Some(stat.st_ino);
+
+        self.mutex_obj = Some(Self::open_semaphore(&self.name, "Mutex", 0, 0)?);
+        // client use this object to signal when it has read the data
+        self.read_event_obj = Some(Self::open_semaphore(&self.name, "ReadEvent", 0, 0)?);
+        // server use this object to signal when it has written data to memory file
+        self.write_event_obj = Some(Self::open_semaphore(&self.name, "WriteEvent", 0, 0)?);
+
+        Ok(())
+    }
+
+    // producer side, create the objects. stale objects from a previous producer are removed
+    pub fn create(&mut self, size: usize) -> Result<(), ServiceError> {
+        self.owner = true;
+        Self::unlink(&self.name);
+
+        let memory_file_name = object_name(&self.name, "");
+        let fd = unsafe {
+            libc::shm_open(
+                memory_file_name.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT | libc::O_EXCL,
+                0o600,
+            )
+        };
+
+        if fd == -1 {
+            return Err(posix_get_last_error("shm_open"));
+        }
+
+        self.memory_file_fd = Some(fd);
+
+        if unsafe { libc::ftruncate(fd, size as libc::off_t) } == -1 {
+            return Err(posix_get_last_error("ftruncate"));
+        }
+
+        self.map(size)?;
+
+        self.mutex_obj = Some(Self::open_semaphore(&self.name, "Mutex", libc::O_CREAT, 1)?);
+        // no packet has been written, the reader is ready for the first one
+        self.read_event_obj = Some(Self::open_semaphore(
+            &self.name,
+            "ReadEvent",
+            libc::O_CREAT,
+            1,
+        )?);
+        self.write_event_obj = Some(Self::open_semaphore(
+            &self.name,
+            "WriteEvent",
+            libc::O_CREAT,
+            0,
+        )?);
+
+        Ok(())
+    }
+
+    fn map(&mut self, size: usize) -> Result<(), ServiceError> {
+        let address = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                self.memory_file_fd.unwrap(),
+                0,
+            )
+        };
+
+        if address == libc::MAP_FAILED {
+            return Err(posix_get_last_error("mmap"));
+        }
+
+        self.memory_file_start_address = Some(address);
+        self.memory_file_size = size;
+
+        Ok(())
+    }
+
+    fn open_semaphore(
+        name: &str,
+        suffix: &str,
+        flags: libc::c_int,
+        initial: u32,
+    ) -> Result<*mut libc::sem_t, ServiceError> {
+        let sem_name = object_name(name, suffix);
+        let sem = unsafe {
+            libc::sem_open(
+                sem_name.as_ptr(),
+                flags,
+                0o600 as libc::c_uint,
+                initial as libc::c_uint,
+            )
+        };
+
+        if sem == libc::SEM_FAILED {
+            return Err(posix_get_last_error("sem_open"));
+        }
+
+        Ok(sem)
+    }
+
+    fn unlink(name: &str) {
+        unsafe {
+            libc::shm_unlink(object_name(name, "").as_ptr());
+            libc::sem_unlink(object_name(name, "Mutex").as_ptr());
+            libc::sem_unlink(object_name(name, "ReadEvent").as_ptr());
+            libc::sem_unlink(object_name(name, "WriteEvent").as_ptr());
+        }
+    }
+
+    // blocks until the semaphore is available, returns false when the timeout elapsed
+    fn wait(sem: *mut libc::sem_t, timeout: Duration) -> Result<bool, ServiceError> {
+        let mut deadline: libc::timespec = unsafe { mem::zeroed() };
+        unsafe { libc::clock_gettime(libc::CLOCK_REALTIME, &mut deadline) };
+
+        let nanos = deadline.tv_nsec as u64 + timeout.subsec_nanos() as u64;
+        deadline.tv_sec += (timeout.as_secs() + nanos / 1_000_000_000) as libc::time_t;
+        deadline.tv_nsec = (nanos % 1_000_000_000) as _;
+
+        loop {
+            if unsafe { libc::sem_timedwait(sem, &deadline) } == 0 {
+                return Ok(true);
+            }
+
+            match std::io::Error::last_os_error().raw_os_error() {
+                Some(libc::ETIMEDOUT) => return Ok(false),
+                Some(libc::EINTR) => continue,
+                _ => return Err(posix_get_last_error("sem_timedwait")),
+            }
+        }
+    }
+
+    fn post(sem: *mut libc::sem_t, debug: &str) -> Result<(), ServiceError> {
+        if unsafe { libc::sem_post(sem) } == -1 {
+            return Err(posix_get_last_error(debug));
+        }
+        Ok(())
+    }
+
+    // client side. blocks until the server process has signaled the write event and the mutex is available.
+    // returns false when no data was written or the mutex was not released within the timeout
+    pub fn acquire_written(&self, timeout: Duration) -> Result<bool, ServiceError> {
+        if !Self::wait(self.write_event_obj.unwrap(), timeout)? {
+            return Ok(false);
+        }
+
+        if !Self::wait(self.mutex_obj.unwrap(), timeout)? {
+            // hand the write event back, the data is read on the next call
+            Self::post(self.write_event_obj.unwrap(), "sem_post - write event")?;
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    // client side. true when the memory file has been removed or replaced, i.e. the producer
+    // restarted and waits on new objects
+    pub fn is_stale(&self) -> bool {
+        let memory_file_name = object_name(&self.name, "");
+        let fd = unsafe { libc::shm_open(memory_file_name.as_ptr(), libc::O_RDONLY, 0) };
+        if fd == -1 {
+            return true;
+        }
+
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        let result = unsafe { libc::fstat(fd, &mut stat) };
+        unsafe { libc::close(fd) };
+
+        result == -1 || Some(stat.st_ino) != self.memory_file_inode
+    }
+
+    // client side. signal the server process that the data has been read and release the mutex
+    pub fn release_read(&self) -> Result<(), ServiceError> {
+        Self::post(self.read_event_obj.unwrap(), "sem_post - read event")?;
+        Self::post(self.mutex_obj.unwrap(), "sem_post - mutex")
+    }
+
+    // producer side. blocks until the client has read the previous data, then copies
+    // the data into the memory file and signals the write event.
+    // returns false when the client did not read or release the mutex within the timeout
+    pub fn write(&self, data: &[u8], timeout: Duration) -> Result<bool, ServiceError> {
+        assert!(data.len() <= self.memory_file_size);
+
+        if !Self::wait(self.read_event_obj.unwrap(), timeout)? {
+            return Ok(false);
+        }
+
+        if !Self::wait(self.mutex_obj.unwrap(), timeout)? {
+            // hand the read event back, the next call writes
+            Self::post(self.read_event_obj.unwrap(), "sem_post - read event")?;
+            return Ok(false);
+        }
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                data.as_ptr(),
+                self.memory_file_start_address.unwrap() as *mut u8,
+                data.len(),
+            )
+        };
+
+        Self::post(self.mutex_obj.unwrap(), "sem_post - mutex")?;
+        Self::post(self.write_event_obj.unwrap(), "sem_post - write event")?;
+
+        Ok(true)
+    }
+
+    pub fn start_address(&self) -> *mut c_void {
+        self.memory_file_start_address.unwrap()
+    }
+
+    pub fn release(&mut self) {
+        if let Some(address) = self.memory_file_start_address.take() {
+            unsafe { libc::munmap(address, self.memory_file_size) };
+        }
+
+        if let Some(fd) = self.memory_file_fd.take() {
+            unsafe { libc::close(fd) };
+        }
+        self.memory_file_inode = None;
+
+        for sem in [
+            self.mutex_obj.take(),
+            self.read_event_obj.take(),
+            self.write_event_obj.take(),
+        ]
+        .iter()
+        .flatten()
+        {
+            unsafe { libc::sem_close(*sem) };
+        }
+
+        if self.owner {
+            Self::unlink(&self.name);
+        }
+    }
+}
+
+impl Drop for InterProcessCommunication {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+// producer side of the protocol, creates the named objects and writes raw packets.
+// used to emulate the telemetry plugin
+pub struct SharedMemoryProducer {
+    ipc: InterProcessCommunication,
+}
+
+impl SharedMemoryProducer {
+    pub fn create(name: &str) -> Result<SharedMemoryProducer, ServiceError> {
+        let mut ipc = InterProcessCommunication::new(name);
+        ipc.create(MEMORY_FILE_SIZE)?;
+
+        Ok(SharedMemoryProducer { ipc })
+    }
+
+    // blocks until the reader consumed the previous write, returns false on timeout
+    pub fn write(&self, data: &[u8], timeout: Duration) -> Result<bool, ServiceError> {
+        self.ipc.write(data, timeout)
+    }
+}
+
+// reads packets until stopped or the producer marks a packet as not alive
+fn read_loop(
+    ipc: &InterProcessCommunication,
+    shutdown: &CancellationToken,
+    p_paser: &mut PacketParser,
+    tx: &InputSender,
+) -> Result<(), ServiceError> {
+    let base_address = ipc.start_address();
+
+    loop {
+        if shutdown.is_cancelled() {
+            println!("stopping telemetry loop");
+            return Ok(());
+        }
+
+        //blocks until server process has signaled write event and mutex available, or timeout
+        if !ipc.acquire_written(SHUTDOWN_POLL_INTERVAL)? {
+            // a restarted producer never signals the objects held here, fail so that the
+            // restart policy reconnects
+            if ipc.is_stale() {
+                return Err(ServiceError::Disconnected(String::from(
+                    "telemetry producer restarted",
+                )));
+            }
+            continue;
+        }
+
+        // copy packet. plus awareness control loop can stop itself when telemetry broadcaster stops
+        let (is_alive, packet) = p_paser.validated(base_address);
+
+        // signal read event and release mutex so that server process can update shared memory
+        ipc.release_read()?;
+
+        // corrupt packets are counted by the validator and not forwarded
+        if let Some(packet) = packet {
+            if tx.send(packet).is_err() {
+                return Err(ServiceError::Disconnected(String::from("telemetry output")));
+            }
+        }
+
+        // check if loop should exit based on packet paser
+        if !is_alive {
+            return Ok(());
+        }
+    }
+}
+
+pub struct PosixSharedMemory {
+    transmitter: Option<InputSender>,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+    shutdown: CancellationToken,
+    p_paser: PacketParser,
+    name: String,
+}
+
+impl PosixSharedMemory {
+    pub fn new(game: SelectGame) -> PosixSharedMemory {
+        PosixSharedMemory::with_name(game, DEFAULT_OBJECT_NAME)
+    }
+
+    // connect to objects created under a different name than the telemetry plugin default
+    pub fn with_name(game: SelectGame, name: &str) -> PosixSharedMemory {
+        PosixSharedMemory {
+            transmitter: None,
+            handle: None,
+            shutdown: CancellationToken::new(),
+            p_paser: PacketParser::new(game),
+            name: String::from(name),
+        }
+    }
+}
+
+impl InputProcessMethod for PosixSharedMemory {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.transmitter.is_none() {
+            return Err(ServiceError::TransmitterNotSet);
+        }
+
+        //copy variable so struct can keep ownership of its members
+        self.shutdown = CancellationToken::new();
+        let shutdown = self.shutdown.clone();
+        let mut p_paser = self.p_paser.clone();
+        let name = self.name.clone();
+
+        let tx = self.transmitter.take().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut ipc = InterProcessCommunication::new(&name);
+
+            if let Err(err) = ipc.connect(p_paser.game().shared_memory_size()) {
+                println!("failed to init interprocesscommunication {:?}", err);
+                return Err(err);
+            }
+
+            let result = read_loop(&ipc, &shutdown, &mut p_paser, &tx);
+
+            //release interprocesscommunication handles
+            ipc.release();
+            result
+        });
+
+        self.handle = Some(handle);
+        Ok(())
+    }
+    fn stop(&mut self) {
+        self.shutdown.cancel();
+    }
+    fn join(&mut self) -> Result<(), ServiceError> {
+        // take ownership of handle and join
+        join_thread(self.handle.take())
+    }
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+    fn validation_report(&self) -> Option<ValidationReport> {
+        Some(self.p_paser.validator().report())
+    }
+    fn method(&self) -> &str {
+        "posix shared memory"
+    }
+    fn set_transmitter(&mut self, transmitter: InputSender) {
+        self.transmitter = Some(transmitter);
+    }
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.p_paser.set_sequence(sequence);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::queue::unbounded;
+    use crate::telemetry::EventGame;
+    use crate::Input;
+
+    // writes the (is_alive, TelemetryPacket) layout, packet starts at offset 8
+    fn frame(is_alive: bool, type_: u32, id: u64) -> Vec<u8> {
+        let length = SelectGame::ETS2.expected_length(type_).unwrap();
+
+        let mut data = vec![0u8; 248];
+        data[0] = is_alive as u8;
+        data[8..12].copy_from_slice(&type_.to_ne_bytes());
+        data[12..16].copy_from_slice(&length.to_ne_bytes());
+        data[16..24].copy_from_slice(&id.to_ne_bytes());
+        data
+    }
+
+    #[test]
+    fn reader_receives_every_packet_until_not_alive() {
+        let name = format!("ControlKitTest{}", std::process::id());
+
+        let producer = SharedMemoryProducer::create(&name).unwrap();
+
+        let (tx, rx) = unbounded();
+        let mut reader = PosixSharedMemory::with_name(SelectGame::ETS2, &name);
+        reader.set_transmitter(tx);
+        reader.start().unwrap();
+
+        let timeout = Duration::from_secs(5);
+        for id in 0..100 {
+            assert!(producer.write(&frame(true, 1, id), timeout).unwrap());
+        }
+        assert!(producer.write(&frame(false, 2, 100), timeout).unwrap());
+
+        reader.join().unwrap();
+        drop(producer);
+
+        let packets: Vec<Box<dyn Input + Send>> = rx.iter().collect();
+
+        assert_eq!(packets.len(), 101);
+        for (id, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.header().sequence, id as u64);
+        }
+        assert!(packets[0].event_type() == EventGame::FrameStartEvent);
+        assert!(packets[100].event_type() == EventGame::FrameEndEvent);
+
+        let report = reader.validation_report().unwrap();
+        assert_eq!(report.counters.forwarded, 101);
+        assert_eq!(report.counters.dropped(), 0);
+    }
+
+    #[test]
+    fn reader_rejects_a_memory_file_smaller_than_a_packet() {
+        let name = format!("ControlKitSmall{}", std::process::id());
+        InterProcessCommunication::unlink(&name);
+
+        let memory_file_name = object_name(&name, "");
+        unsafe {
+            let fd = libc::shm_open(
+                memory_file_name.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT | libc::O_EXCL,
+                0o600,
+            );
+            assert!(fd != -1);
+            assert!(libc::ftruncate(fd, 16) != -1);
+            libc::close(fd);
+        }
+
+        let (tx, _rx) = unbounded();
+        let mut reader = PosixSharedMemory::with_name(SelectGame::ETS2, &name);
+        reader.set_transmitter(tx);
+        reader.start().unwrap();
+
+        let result = reader.join();
+        InterProcessCommunication::unlink(&name);
+        assert!(matches!(result, Err(ServiceError::Ipc { .. })));
+    }
+
+    #[test]
+    fn reader_fails_when_the_producer_restarts() {
+        let name = format!("ControlKitRestart{}", std::process::id());
+
+        let producer = SharedMemoryProducer::create(&name).unwrap();
+
+        let (tx, rx) = unbounded();
+        let mut reader = PosixSharedMemory::with_name(SelectGame::ETS2, &name);
+        reader.set_transmitter(tx);
+        reader.start().unwrap();
+
+        let timeout = Duration::from_secs(5);
+        for id in 0..10 {
+            assert!(producer.write(&frame(true, 1, id), timeout).unwrap());
+        }
+
+        // the new producer unlinks and recreates every object
+        let producer = SharedMemoryProducer::create(&name).unwrap();
+
+        assert!(matches!(reader.join(), Err(ServiceError::Disconnected(_))));
+        drop(producer);
+
+        assert_eq!(rx.iter().count(), 10);
+    }
+}
@@ -0,0 +1,382 @@
+use crate::queue::{input_queue, DropCounters, InputQueueConfig, InputReceiver, InputSender};
+use crate::telemetry::{PacketParser, PacketValidator, SelectGame, ValidationReport};
+use crate::utils::{join_thread, thread_finished};
+use crate::{
+    CancellationToken, CaptureSequence, Input, InputHeader, InputProcessMethod, InputType, Process,
+    ServiceError,
+};
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// file layout, all values little endian
+// header: magic (4 bytes) | version (u16) | game code (u16)
+// record: id (u64) | type (u32) | time (u64) | length (u32) | stored (u32) | payload (stored bytes)
+// length is the one reported by the producer, the payload is cut at the size of the packet data
+pub const RECORDING_MAGIC: &[u8; 4] = b"CKTR";
+pub const RECORDING_VERSION: u16 = 2;
+
+// writes telemetry packets to a recording file
+pub struct TelemetryWriter {
+    writer: BufWriter<File>,
+}
+
+impl TelemetryWriter {
+    pub fn create<P: AsRef<Path>>(path: P, game: SelectGame) -> io::Result<TelemetryWriter> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(RECORDING_MAGIC)?;
+        writer.write_all(&RECORDING_VERSION.to_le_bytes())?;
+        writer.write_all(&game.code().to_le_bytes())?;
+
+        Ok(TelemetryWriter { writer })
+    }
+
+    pub fn write(&mut self, input: &dyn Input) -> io::Result<()> {
+        let header = input.header();
+        let payload = input.payload();
+
+        self.writer.write_all(&header.sequence.to_le_bytes())?;
+        self.writer.write_all(&header.event_code.to_le_bytes())?;
+        self.writer.write_all(&header.game_time.to_le_bytes())?;
+        self.writer.write_all(&header.payload_size.to_le_bytes())?;
+        self.writer
+            .write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.writer.write_all(payload)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// reads telemetry packets from a recording file
+pub struct TelemetryReader {
+    reader: BufReader<File>,
+    parser: PacketParser,
+}
+
+impl TelemetryReader {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<TelemetryReader> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+
+        if &header[0..4] != RECORDING_MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "not a telemetry recording",
+            ));
+        }
+
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != RECORDING_VERSION {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unsupported recording version {}", version),
+            ));
+        }
+
+        let game = SelectGame::from_code(u16::from_le_bytes([header[6], header[7]]))
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "unknown game"))?;
+
+        Ok(TelemetryReader {
+            reader,
+            parser: PacketParser::new(game),
+        })
+    }
+
+    pub fn game(&self) -> SelectGame {
+        self.parser.game()
+    }
+
+    // packets are stamped from this sequence, i.e. to continue the numbering of a previous replay
+    pub fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.parser.set_sequence(sequence);
+    }
+
+    // shares the validation report of the parser
+    pub fn parser(&self) -> PacketParser {
+        self.parser.clone()
+    }
+
+    // returns None at the end of the recording, a recording that ends within a record is an error
+    pub fn next_packet(&mut self) -> io::Result<Option<Box<dyn Input + Send>>> {
+        let mut record = [0u8; 28];
+        let mut read = 0;
+
+        while read < record.len() {
+            match self.reader.read(&mut record[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "recording ends with a partial record",
+                    ))
+                }
+                Ok(n) => read += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        let header = InputHeader {
+            sequence: u64::from_le_bytes(record[0..8].try_into().unwrap()),
+            event_code: u32::from_le_bytes(record[8..12].try_into().unwrap()),
+            game_time: u64::from_le_bytes(record[12..20].try_into().unwrap()),
+            payload_size: u32::from_le_bytes(record[20..24].try_into().unwrap()),
+            ..InputHeader::new(InputType::Telemetry)
+        };
+
+        // the size comes from the file, a corrupt record must not allocate more than a packet
+        let stored = u32::from_le_bytes(record[24..28].try_into().unwrap()) as usize;
+        if stored > self.game().max_payload_size() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("record {} payload of {} bytes", header.sequence, stored),
+            ));
+        }
+
+        let mut payload = vec![0u8; stored];
+        self.reader.read_exact(&mut payload)?;
+
+        Ok(Some(self.parser.record(&header, &payload)))
+    }
+}
+
+// Responsible for persisting telemetry inputs. Passes every input on to the output transmitter when set
+pub struct Recorder {
+    path: PathBuf,
+    game: SelectGame,
+    input_transmitter: InputSender,
+    input_receiver: Option<InputReceiver>,
+    output_transmitter: Option<InputSender>,
+    shutdown: CancellationToken,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+}
+
+impl Recorder {
+    pub fn new<P: AsRef<Path>>(path: P, game: SelectGame) -> Recorder {
+        Recorder::with_queue_config(path, game, &InputQueueConfig::default())
+    }
+
+    pub fn with_queue_config<P: AsRef<Path>>(
+        path: P,
+        game: SelectGame,
+        config: &InputQueueConfig,
+    ) -> Recorder {
+        let (input_transmitter, input_receiver) = input_queue(config);
+        Recorder {
+            path: path.as_ref().to_path_buf(),
+            game,
+            input_transmitter,
+            input_receiver: Some(input_receiver),
+            output_transmitter: None,
+            shutdown: CancellationToken::new(),
+            handle: None,
+        }
+    }
+
+    //return cloned transmitter
+    pub fn get_input_transmitter(&self) -> InputSender {
+        self.input_transmitter.clone()
+    }
+
+    pub fn set_output_transmitter(&mut self, transmitter: InputSender) {
+        self.output_transmitter = Some(transmitter);
+    }
+
+    // inputs discarded because the recorder fell behind
+    pub fn dropped(&self) -> DropCounters {
+        self.input_transmitter.dropped()
+    }
+}
+
+impl Process for Recorder {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_none() {
+            return Err(ServiceError::AlreadyActive);
+        }
+
+        let mut writer =
+            TelemetryWriter::create(&self.path, self.game).map_err(ServiceError::Io)?;
+
+        let receiver = self.input_receiver.take().unwrap();
+        let transmitter = self.output_transmitter.take();
+
+        let shutdown = CancellationToken::new();
+        self.shutdown = shutdown.clone();
+
+        let process = move || {
+            // inputs queued before stop are still recorded
+            while let Some(inputs) = shutdown.recv_all(&receiver) {
+                for input in inputs {
+                    if let InputType::Telemetry = input.input_type() {
+                        writer.write(input.as_ref())?;
+                    }
+
+                    if let Some(tx) = transmitter.as_ref() {
+                        if tx.send(input).is_err() {
+                            writer.flush()?;
+                            return Err(ServiceError::Disconnected(String::from(
+                                "recorder output",
+                            )));
+                        }
+                    }
+                }
+            }
+
+            println!("exiting recorder loop");
+            writer.flush()?;
+            Ok(())
+        };
+
+        self.handle = Some(thread::spawn(process));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_some() {
+            return Err(ServiceError::NotActive);
+        }
+        self.shutdown.cancel();
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    // packets are sent with the recorded time between them
+    Original,
+    // recorded time between packets is divided by the factor
+    Accelerated(f64),
+    // no delay between packets
+    AsFastAsPossible,
+}
+
+// replays a recording as a telemetry input, stops at the end of the recording
+pub struct Replay {
+    path: PathBuf,
+    speed: ReplaySpeed,
+    transmitter: Option<InputSender>,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+    shutdown: CancellationToken,
+    // set once the recording has been opened
+    validator: Option<PacketValidator>,
+    sequence: CaptureSequence,
+}
+
+impl Replay {
+    pub fn new<P: AsRef<Path>>(path: P, speed: ReplaySpeed) -> Replay {
+        Replay {
+            path: path.as_ref().to_path_buf(),
+            speed,
+            transmitter: None,
+            handle: None,
+            shutdown: CancellationToken::new(),
+            validator: None,
+            sequence: CaptureSequence::new(),
+        }
+    }
+}
+
+impl ReplaySpeed {
+    // wall clock delay between two recorded inputs, time is in microseconds
+    pub fn delay(&self, previous: u64, current: u64) -> Duration {
+        let recorded = Duration::from_micros(current.saturating_sub(previous));
+
+        match *self {
+            ReplaySpeed::Original => recorded,
+            ReplaySpeed::Accelerated(factor) if factor > 0.0 => recorded.div_f64(factor),
+            ReplaySpeed::Accelerated(_) | ReplaySpeed::AsFastAsPossible => Duration::from_secs(0),
+        }
+    }
+}
+
+impl InputProcessMethod for Replay {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.transmitter.is_none() {
+            return Err(ServiceError::TransmitterNotSet);
+        }
+
+        let mut reader = TelemetryReader::open(&self.path).map_err(ServiceError::Io)?;
+        reader.set_capture_sequence(self.sequence.clone());
+        let mut parser = reader.parser();
+        self.validator = Some(parser.validator().clone());
+
+        self.shutdown = CancellationToken::new();
+        let shutdown = self.shutdown.clone();
+        let speed = self.speed;
+        let tx = self.transmitter.take().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut previous: Option<u64> = None;
+            let mut deadline = Instant::now();
+
+            loop {
+                let packet = match reader.next_packet()? {
+                    Some(packet) => packet,
+                    None => break,
+                };
+
+                let time = packet.header().game_time;
+                if let Some(previous) = previous {
+                    deadline += speed.delay(previous, time);
+                }
+                previous = Some(time);
+
+                // stop wakes the sleep up, long gaps do not delay it
+                let wait = deadline.saturating_duration_since(Instant::now());
+                if shutdown.wait_timeout(wait) {
+                    println!("stopping replay loop");
+                    return Ok(());
+                }
+
+                if let Some(packet) = parser.validate(packet) {
+                    if tx.send(packet).is_err() {
+                        return Err(ServiceError::Disconnected(String::from("replay output")));
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        self.handle = Some(handle);
+        Ok(())
+    }
+    fn stop(&mut self) {
+        self.shutdown.cancel();
+    }
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+    fn validation_report(&self) -> Option<ValidationReport> {
+        self.validator.as_ref().map(|validator| validator.report())
+    }
+    fn method(&self) -> &str {
+        "recording replay"
+    }
+    fn set_transmitter(&mut self, transmitter: InputSender) {
+        self.transmitter = Some(transmitter);
+    }
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.sequence = sequence;
+    }
+}
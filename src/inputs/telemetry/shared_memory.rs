@@ -7,16 +7,19 @@ use bindings::{
     windows::BOOL,
 };
 
-use crate::{str_to_wstring, windows_get_last_error, Input, InputProcessMethod, ServiceError};
+use crate::queue::InputSender;
+use crate::utils::{join_thread, thread_finished};
+use crate::{
+    str_to_wstring, windows_get_last_error, CancellationToken, CaptureSequence, Input,
+    InputProcessMethod, ServiceError, SHUTDOWN_POLL_INTERVAL,
+};
 use std::ffi::c_void;
 
 use std::result::Result;
 
-use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
 use std::thread;
 
-use crate::telemetry::{DataPair, PacketParser, SelectGame};
+use crate::telemetry::{PacketParser, SelectGame, ValidationReport};
 //use to setup windows inter process communication and sychronization objects
 struct InterProcessCommunication {
     hmapping_obj: Option<HANDLE>,
@@ -128,10 +131,92 @@ impl InterProcessCommunication {
     }
 }
 
+// reads packets until stopped or the telemetry broadcaster marks a packet as not alive
+fn read_loop(
+    ipc: &InterProcessCommunication,
+    shutdown: &CancellationToken,
+    p_paser: &mut PacketParser,
+    tx: &InputSender,
+) -> Result<(), ServiceError> {
+    // create handle array to await for multiple objects
+    let mut wait_handles: [isize; 2] = [0; 2]; //initialize with zeros
+
+    wait_handles[0] = ipc.hmutex_obj.unwrap().0;
+    wait_handles[1] = ipc.hwrite_event_obj.unwrap().0;
+
+    // conviences
+    let wait_handles = wait_handles.as_ptr();
+    let base_address = ipc.memory_file_start_address.unwrap();
+
+    // wake up regularly to check for shutdown
+    let timeout = SHUTDOWN_POLL_INTERVAL.as_millis() as u32;
+
+    loop {
+        if shutdown.is_cancelled() {
+            println!("stopping telemetry loop");
+            return Ok(());
+        }
+
+        //blocks until mutex available and server process has signaled read event, or timeout
+        let dwait_result =
+            unsafe { WaitForMultipleObjects(2, wait_handles, BOOL::from(true), timeout) };
+
+        // WAIT_TIMEOUT
+        if dwait_result == 0x00000102 {
+            continue;
+        }
+
+        // all failure cases
+        if dwait_result != 0x00000000 {
+            println!("failure couldn't aquire all shared memory handles");
+            windows_get_last_error("WaitForMultipleObjects")?;
+            return Err(ServiceError::Ipc {
+                call: String::from("WaitForMultipleObjects"),
+                source: std::io::Error::from_raw_os_error(dwait_result as i32),
+            });
+        }
+
+        // Reset server process WriteEvent to non-signaled. When execution continues to next iteration the function will block again until
+        // the server process sets the WriteEvent to signaled.
+        let success = unsafe { ResetEvent(ipc.hwrite_event_obj.unwrap()) };
+        if !success.as_bool() {
+            windows_get_last_error("ResetEvent - write event")?;
+        }
+
+        // copy packet. plus awareness control loop can stop itself when telemetry broadcaster stops
+        let (is_alive, packet) = p_paser.validated(base_address);
+
+        // Set client process ReadEvent to signaled. The server process blocks until the client process sets the ReadEvent to signaled before updating
+        // the shared memory with telemetry data
+        let success = unsafe { SetEvent(ipc.hread_event_obj.unwrap()) };
+        if !success.as_bool() {
+            windows_get_last_error("SetEvent - read event")?;
+        }
+
+        // Release Mutex so that server process can update shared memory
+        let success = unsafe { ReleaseMutex(ipc.hmutex_obj.unwrap()) };
+        if !success.as_bool() {
+            windows_get_last_error("ReleaseMutex")?;
+        }
+
+        // corrupt packets are counted by the validator and not forwarded
+        if let Some(packet) = packet {
+            if tx.send(packet).is_err() {
+                return Err(ServiceError::Disconnected(String::from("telemetry output")));
+            }
+        }
+
+        // check if loop should exit based on packet paser
+        if !is_alive {
+            return Ok(());
+        }
+    }
+}
+
 pub struct SharedMemory {
-    transmitter: Option<Sender<Box<dyn Input + Send>>>,
-    handle: Option<thread::JoinHandle<()>>,
-    sentinal: Arc<Mutex<bool>>,
+    transmitter: Option<InputSender>,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+    shutdown: CancellationToken,
     p_paser: PacketParser,
 }
 
@@ -140,7 +225,7 @@ impl SharedMemory {
         SharedMemory {
             transmitter: None,
             handle: None,
-            sentinal: Arc::new(Mutex::new(false)),
+            shutdown: CancellationToken::new(),
             p_paser: PacketParser::new(game),
         }
     }
@@ -154,8 +239,9 @@ impl InputProcessMethod for SharedMemory {
         }
 
         //copy variable so struct can keep ownership of its members
-        let sentinal = Arc::clone(&self.sentinal);
-        let p_paser = self.p_paser.clone();
+        self.shutdown = CancellationToken::new();
+        let shutdown = self.shutdown.clone();
+        let mut p_paser = self.p_paser.clone();
         //let sel_game = self.selected_game.clone();
 
         let tx = self.transmitter.take().unwrap();
@@ -163,109 +249,42 @@ impl InputProcessMethod for SharedMemory {
         let handle = thread::spawn(move || {
             let mut ipc = InterProcessCommunication::new();
 
-            match ipc.connect() {
-                Err(err) => {
-                    println!("failed to init interprocesscommunication {:?}", err);
-                }
-                Ok(_) => {
-                    // create handle array to await for multiple objects
-                    let mut wait_handles: [isize; 2] = [0; 2]; //initialize with zeros
-
-                    wait_handles[0] = ipc.hmutex_obj.unwrap().0;
-                    wait_handles[1] = ipc.hwrite_event_obj.unwrap().0;
-
-                    // conviences
-                    let wait_handles = wait_handles.as_ptr();
-                    let base_address = ipc.memory_file_start_address.unwrap();
-
-                    //set loop sentinal value, use to exit infinite loop
-                    {
-                        *sentinal.lock().unwrap() = true;
-                    }
-
-                    loop {
-                        //check sentinal condition
-                        if !*sentinal.lock().unwrap() {
-                            println!("stopping telemetry loop");
-                            break;
-                        }
-
-                        //blocks until mutex available and server process has signaled read event
-                        let dwait_result = unsafe {
-                            WaitForMultipleObjects(
-                                2,
-                                wait_handles,
-                                BOOL::from(true),
-                                u32::max_value(),
-                            )
-                        };
-
-                        match dwait_result {
-                            //successfull case
-                            0x00000000 => {
-                                // Reset server process WriteEvent to non-signaled. When execution continues to next iteration the function will block again until
-                                // the server process sets the WriteEvent to signaled.
-                                let success = unsafe { ResetEvent(ipc.hwrite_event_obj.unwrap()) };
-                                if !success.as_bool() {
-                                    windows_get_last_error("ResetEvent - write event").unwrap();
-                                }
-
-                                // copy packet. plus awareness control loop can stop itself when telemetry broadcaster stops
-                                let DataPair(is_alive, packet) = p_paser.data(base_address);
-
-                                match tx.send(packet) {
-                                    Err(err) => {
-                                        println!("shared memory loop transmit error {}", err);
-                                    }
-                                    _ => (),
-                                }
-
-                                // Set client process ReadEvent to signaled. The server process blocks until the client process sets the ReadEvent to signaled before updating
-                                // the shared memory with telemetry data
-                                let success = unsafe { SetEvent(ipc.hread_event_obj.unwrap()) };
-                                if !success.as_bool() {
-                                    windows_get_last_error("SetEvent - read event").unwrap();
-                                }
-
-                                // Release Mutex so that server process can update shared memory
-                                let success = unsafe { ReleaseMutex(ipc.hmutex_obj.unwrap()) };
-                                if !success.as_bool() {
-                                    windows_get_last_error("ReleaseMutex").unwrap();
-                                }
-
-                                // check if loop should exit based on packet paser
-                                if !is_alive {
-                                    *sentinal.lock().unwrap() = false;
-                                }
-                            }
-                            // all failure cases
-                            _ => {
-                                println!("failure couldn't aquire all shared memory handles");
-                                windows_get_last_error("WaitForMultipleObjects").unwrap();
-                            }
-                        }
-                    }
-                }
+            if let Err(err) = ipc.connect() {
+                println!("failed to init interprocesscommunication {:?}", err);
+                ipc.release();
+                return Err(err);
             }
 
+            let result = read_loop(&ipc, &shutdown, &mut p_paser, &tx);
+
             //release interprocesscommunicatio handles
             ipc.release();
+            result
         });
 
         self.handle = Some(handle);
         Ok(())
     }
     fn stop(&mut self) {
-        *self.sentinal.lock().unwrap() = false;
+        self.shutdown.cancel();
     }
-    fn join(&mut self) {
+    fn join(&mut self) -> Result<(), ServiceError> {
         // take ownership of handle and join
-        self.handle.take().unwrap().join().unwrap();
+        join_thread(self.handle.take())
+    }
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+    fn validation_report(&self) -> Option<ValidationReport> {
+        Some(self.p_paser.validator().report())
     }
     fn method(&self) -> &str {
         "memory-mapped file"
     }
-    fn set_transmitter(&mut self, transmitter: Sender<Box<dyn Input + Send>>) {
+    fn set_transmitter(&mut self, transmitter: InputSender) {
         self.transmitter = Some(transmitter);
     }
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.p_paser.set_sequence(sequence);
+    }
 }
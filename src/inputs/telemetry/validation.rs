@@ -0,0 +1,245 @@
+use crate::telemetry::SelectGame;
+use crate::Input;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+// number of recent errors kept in the report
+const RECENT_ERRORS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationError {
+    // type is not a known game event, packet dropped
+    InvalidType {
+        id: u64,
+        type_: u32,
+    },
+    // length does not match the payload size of the type, packet dropped
+    LengthMismatch {
+        id: u64,
+        type_: u32,
+        expected: u32,
+        actual: u32,
+    },
+    // id was already received, packet dropped
+    DuplicateId {
+        id: u64,
+    },
+    // id is older than the last received id, packet dropped
+    OutOfOrderId {
+        id: u64,
+        last: u64,
+    },
+    // ids between expected and actual were never received, packet forwarded
+    IdGap {
+        expected: u64,
+        actual: u64,
+    },
+    // time is earlier than the time of the previous packet, packet forwarded
+    TimeWentBackwards {
+        id: u64,
+        previous: u64,
+        current: u64,
+    },
+}
+
+impl ValidationError {
+    // dropped packets are not forwarded to synchronization
+    pub fn is_dropped(&self) -> bool {
+        !matches!(
+            self,
+            ValidationError::IdGap { .. } | ValidationError::TimeWentBackwards { .. }
+        )
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::InvalidType { id, type_ } => {
+                write!(f, "packet {} has invalid type {}", id, type_)
+            }
+            ValidationError::LengthMismatch {
+                id,
+                type_,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "packet {} of type {} has length {}, expected {}",
+                id, type_, actual, expected
+            ),
+            ValidationError::DuplicateId { id } => write!(f, "packet {} received twice", id),
+            ValidationError::OutOfOrderId { id, last } => {
+                write!(f, "packet {} received after packet {}", id, last)
+            }
+            ValidationError::IdGap { expected, actual } => write!(
+                f,
+                "packets {} to {} missing",
+                expected,
+                actual.saturating_sub(1)
+            ),
+            ValidationError::TimeWentBackwards {
+                id,
+                previous,
+                current,
+            } => write!(
+                f,
+                "packet {} time {} is before previous time {}",
+                id, current, previous
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ValidationCounters {
+    // packets forwarded, including packets with warnings
+    pub forwarded: u64,
+    pub invalid_type: u64,
+    pub length_mismatch: u64,
+    pub duplicate_id: u64,
+    pub out_of_order_id: u64,
+    pub id_gaps: u64,
+    // total number of ids never received
+    pub missing_ids: u64,
+    pub time_went_backwards: u64,
+    // producer started over at id zero
+    pub producer_restarts: u64,
+}
+
+impl ValidationCounters {
+    pub fn dropped(&self) -> u64 {
+        self.invalid_type + self.length_mismatch + self.duplicate_id + self.out_of_order_id
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ValidationReport {
+    pub counters: ValidationCounters,
+    // most recent errors, oldest first
+    pub recent: VecDeque<ValidationError>,
+}
+
+impl ValidationReport {
+    fn push(&mut self, error: ValidationError) {
+        match error {
+            ValidationError::InvalidType { .. } => self.counters.invalid_type += 1,
+            ValidationError::LengthMismatch { .. } => self.counters.length_mismatch += 1,
+            ValidationError::DuplicateId { .. } => self.counters.duplicate_id += 1,
+            ValidationError::OutOfOrderId { .. } => self.counters.out_of_order_id += 1,
+            ValidationError::IdGap { expected, actual } => {
+                self.counters.id_gaps += 1;
+                self.counters.missing_ids += actual - expected;
+            }
+            ValidationError::TimeWentBackwards { .. } => self.counters.time_went_backwards += 1,
+        }
+
+        if self.recent.len() == RECENT_ERRORS {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(error);
+    }
+}
+
+// checks every packet against the payload size of its type and the previous packet.
+// clones share the report
+#[derive(Debug, Clone)]
+pub struct PacketValidator {
+    game: SelectGame,
+    last_id: Option<u64>,
+    last_time: Option<u64>,
+    report: Arc<Mutex<ValidationReport>>,
+}
+
+impl PacketValidator {
+    pub fn new(game: SelectGame) -> PacketValidator {
+        PacketValidator {
+            game,
+            last_id: None,
+            last_time: None,
+            report: Arc::new(Mutex::new(ValidationReport::default())),
+        }
+    }
+
+    // snapshot of the counters and recent errors
+    pub fn report(&self) -> ValidationReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    // Ok with the warnings when the packet should be forwarded, Err when it should be dropped
+    pub fn validate(&mut self, input: &dyn Input) -> Result<Vec<ValidationError>, ValidationError> {
+        let header = input.header();
+        let (id, type_, time, length) = (
+            header.sequence,
+            header.event_code,
+            header.game_time,
+            header.payload_size,
+        );
+        let mut report = self.report.lock().unwrap();
+
+        let error = if !self.game.is_game_event(type_) {
+            Some(ValidationError::InvalidType { id, type_ })
+        } else {
+            match self.game.expected_length(type_) {
+                Some(expected) if expected != length => Some(ValidationError::LengthMismatch {
+                    id,
+                    type_,
+                    expected,
+                    actual: length,
+                }),
+                _ => match self.last_id {
+                    // producer restarted, continuity starts over
+                    Some(last) if id == 0 && last != 0 => {
+                        report.counters.producer_restarts += 1;
+                        self.last_time = None;
+                        None
+                    }
+                    Some(last) if id == last => Some(ValidationError::DuplicateId { id }),
+                    Some(last) if id < last => Some(ValidationError::OutOfOrderId { id, last }),
+                    _ => None,
+                },
+            }
+        };
+
+        if let Some(error) = error {
+            report.push(error.clone());
+            return Err(error);
+        }
+
+        let mut warnings = Vec::new();
+
+        // no id follows u64::MAX, there is no gap to report
+        if let Some(expected) = self.last_id.and_then(|last| last.checked_add(1)) {
+            if id > expected {
+                warnings.push(ValidationError::IdGap {
+                    expected,
+                    actual: id,
+                });
+            }
+        }
+
+        if let Some(previous) = self.last_time {
+            if time < previous {
+                warnings.push(ValidationError::TimeWentBackwards {
+                    id,
+                    previous,
+                    current: time,
+                });
+            }
+        }
+
+        for warning in warnings.iter() {
+            report.push(warning.clone());
+        }
+        report.counters.forwarded += 1;
+
+        self.last_id = Some(id);
+        self.last_time = Some(time);
+
+        Ok(warnings)
+    }
+}
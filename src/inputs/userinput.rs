@@ -0,0 +1,88 @@
+use crate::queue::InputSender;
+use crate::telemetry::ReplaySpeed;
+use crate::{CaptureSequence, InputProcessMethod, Process, ServiceError};
+use std::path::Path;
+
+#[path = "userinput/user_event.rs"]
+mod user_event;
+pub use user_event::{UserEvent, UserEventKind};
+
+#[path = "userinput/evdev.rs"]
+mod evdev;
+pub use evdev::{
+    EvdevEvent, EventFileReader, EVENT_SIZE, EV_ABS, EV_KEY, EV_REL, EV_SYN, SYN_DROPPED,
+};
+
+#[path = "userinput/controller.rs"]
+mod controller;
+pub use controller::{
+    Binding, Control, ControllerAggregator, ControllerMapping, ControllerSnapshot, ControllerState,
+    SNAPSHOT_SIZE,
+};
+
+#[path = "userinput/event_file.rs"]
+mod event_file;
+use event_file::EventFileReplay;
+
+// evdev devices are only available on linux
+#[cfg(target_os = "linux")]
+#[path = "userinput/evdev_device.rs"]
+mod evdev_device;
+#[cfg(target_os = "linux")]
+use evdev_device::EvdevDevices;
+
+// keyboard and controller input, every key, button and axis change is a UserEvent
+pub struct UserInput {
+    user_input: Box<dyn InputProcessMethod + Send>,
+}
+
+impl UserInput {
+    // one path per device, i.e. /dev/input/by-id/usb-Logitech_G29-event-joystick.
+    // the device index of a UserEvent is the position of its path
+    #[cfg(target_os = "linux")]
+    pub fn via_evdev<P: AsRef<Path>>(devices: &[P]) -> UserInput {
+        UserInput {
+            user_input: Box::new(EvdevDevices::new(devices)),
+        }
+    }
+
+    // replay recorded event files, one file per device, i.e. `cat /dev/input/event3 > wheel.evdev`
+    pub fn via_event_files<P: AsRef<Path>>(files: &[P], speed: ReplaySpeed) -> UserInput {
+        UserInput {
+            user_input: Box::new(EventFileReplay::new(files, speed)),
+        }
+    }
+
+    pub fn get_method(&self) -> &str {
+        self.user_input.method()
+    }
+
+    pub fn set_transmitter(&mut self, transmitter: InputSender) {
+        self.user_input.set_transmitter(transmitter);
+    }
+
+    // share one sequence between the processes of a restart factory, see Telemetry
+    pub fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.user_input.set_capture_sequence(sequence);
+    }
+}
+
+impl Process for UserInput {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        self.user_input.start()?;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        self.user_input.stop();
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        self.user_input.join()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.user_input.is_finished()
+    }
+}
@@ -0,0 +1,378 @@
+use super::{UserEvent, UserEventKind};
+use crate::synchronization::{Group, GroupingPolicy};
+use crate::telemetry::EventGame;
+use crate::{CaptureStamp, ImageEncoding, Input, InputHeader, InputType, Serialized, ServiceError};
+use serde_json::json;
+use std::any::Any;
+
+// linux/input-event-codes.h, used by the keyboard mapping
+const KEY_W: u16 = 17;
+const KEY_A: u16 = 30;
+const KEY_S: u16 = 31;
+const KEY_D: u16 = 32;
+const KEY_SPACE: u16 = 57;
+
+// steering, throttle, brake, clutch as little endian f32, buttons as little endian u64
+pub const SNAPSHOT_SIZE: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Control {
+    // -1.0 full left, 1.0 full right
+    Steering,
+    // 0.0 released, 1.0 fully pressed
+    Throttle,
+    Brake,
+    Clutch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    // absolute axis reporting values between min and max. inverted axes report max when released,
+    // i.e. the pedals of most wheels
+    Axis {
+        device: u16,
+        code: u16,
+        min: i32,
+        max: i32,
+        inverted: bool,
+    },
+    // key or button, the control is set to value while pressed, i.e. -1.0 to steer left
+    Key {
+        device: u16,
+        code: u16,
+        value: f32,
+    },
+}
+
+impl Binding {
+    fn matches(&self, event: &UserEvent) -> bool {
+        match *self {
+            Binding::Axis { device, code, .. } => {
+                event.kind == UserEventKind::Axis && event.device == device && event.code == code
+            }
+            Binding::Key { device, code, .. } => {
+                matches!(event.kind, UserEventKind::Key | UserEventKind::Button)
+                    && event.device == device
+                    && event.code == code
+            }
+        }
+    }
+
+    // contribution of the event to the control
+    fn value(&self, control: Control, event: &UserEvent) -> f32 {
+        match *self {
+            Binding::Axis {
+                min, max, inverted, ..
+            } => {
+                if max <= min {
+                    return 0.0;
+                }
+                let mut t = (event.value.clamp(min, max) - min) as f32 / (max - min) as f32;
+                if inverted {
+                    t = 1.0 - t;
+                }
+                match control {
+                    Control::Steering => t * 2.0 - 1.0,
+                    _ => t,
+                }
+            }
+            Binding::Key { value, .. } if event.is_pressed() => value,
+            Binding::Key { .. } => 0.0,
+        }
+    }
+}
+
+// which device events drive the controls and buttons of the snapshot
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ControllerMapping {
+    controls: Vec<(Control, Binding)>,
+    // (device, code), bit i of ControllerState::buttons is buttons[i]
+    buttons: Vec<(u16, u16)>,
+}
+
+impl ControllerMapping {
+    pub fn new() -> ControllerMapping {
+        ControllerMapping::default()
+    }
+
+    // WASD driving on the keyboard at the device index, space is button 0
+    pub fn keyboard(device: u16) -> ControllerMapping {
+        let key = |code: u16, value: f32| Binding::Key {
+            device,
+            code,
+            value,
+        };
+
+        ControllerMapping::new()
+            .with_binding(Control::Throttle, key(KEY_W, 1.0))
+            .with_binding(Control::Brake, key(KEY_S, 1.0))
+            .with_binding(Control::Steering, key(KEY_A, -1.0))
+            .with_binding(Control::Steering, key(KEY_D, 1.0))
+            .with_button(device, KEY_SPACE)
+    }
+
+    // a control can have several bindings, their values are added up and clamped
+    pub fn with_binding(mut self, control: Control, binding: Binding) -> ControllerMapping {
+        self.controls.push((control, binding));
+        self
+    }
+
+    // the bit of the button is the number of buttons added before, at most 64 buttons
+    pub fn with_button(mut self, device: u16, code: u16) -> ControllerMapping {
+        assert!(self.buttons.len() < 64, "at most 64 buttons can be mapped");
+        self.buttons.push((device, code));
+        self
+    }
+}
+
+// controller state at one instant, the action label of a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ControllerState {
+    pub steering: f32,
+    pub throttle: f32,
+    pub brake: f32,
+    pub clutch: f32,
+    // pressed mapped buttons, bit i is the i-th button of the mapping
+    pub buttons: u64,
+}
+
+impl ControllerState {
+    pub fn is_pressed(&self, button: usize) -> bool {
+        button < 64 && self.buttons & (1 << button) != 0
+    }
+
+    pub fn control(&self, control: Control) -> f32 {
+        match control {
+            Control::Steering => self.steering,
+            Control::Throttle => self.throttle,
+            Control::Brake => self.brake,
+            Control::Clutch => self.clutch,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; SNAPSHOT_SIZE] {
+        let mut bytes = [0u8; SNAPSHOT_SIZE];
+        bytes[0..4].copy_from_slice(&self.steering.to_le_bytes());
+        bytes[4..8].copy_from_slice(&self.throttle.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.brake.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.clutch.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.buttons.to_le_bytes());
+        bytes
+    }
+}
+
+// applies user events to the controller state of a mapping
+#[derive(Debug, Clone)]
+struct Tracker {
+    mapping: ControllerMapping,
+    // last value of every binding
+    values: Vec<f32>,
+    buttons: u64,
+}
+
+impl Tracker {
+    fn new(mapping: ControllerMapping) -> Tracker {
+        Tracker {
+            values: vec![0.0; mapping.controls.len()],
+            mapping,
+            buttons: 0,
+        }
+    }
+
+    fn apply(&mut self, event: &UserEvent) {
+        if event.kind == UserEventKind::Dropped {
+            self.reset(event.device);
+            return;
+        }
+
+        for (i, (control, binding)) in self.mapping.controls.iter().enumerate() {
+            if binding.matches(event) {
+                self.values[i] = binding.value(*control, event);
+            }
+        }
+
+        if !matches!(event.kind, UserEventKind::Key | UserEventKind::Button) {
+            return;
+        }
+
+        for (bit, button) in self.mapping.buttons.iter().enumerate() {
+            if *button == (event.device, event.code) {
+                if event.is_pressed() {
+                    self.buttons |= 1 << bit;
+                } else {
+                    self.buttons &= !(1 << bit);
+                }
+            }
+        }
+    }
+
+    // events of the device were lost, i.e. a key release. release its bindings and buttons
+    // instead of keeping them pressed until they change again
+    fn reset(&mut self, device: u16) {
+        for (i, (_, binding)) in self.mapping.controls.iter().enumerate() {
+            let bound = match *binding {
+                Binding::Axis { device, .. } | Binding::Key { device, .. } => device,
+            };
+            if bound == device {
+                self.values[i] = 0.0;
+            }
+        }
+
+        for (bit, button) in self.mapping.buttons.iter().enumerate() {
+            if button.0 == device {
+                self.buttons &= !(1 << bit);
+            }
+        }
+    }
+
+    fn state(&self) -> ControllerState {
+        let control = |control: Control| {
+            let sum: f32 = self
+                .mapping
+                .controls
+                .iter()
+                .zip(self.values.iter())
+                .filter(|((bound, _), _)| *bound == control)
+                .map(|(_, value)| *value)
+                .sum();
+
+            match control {
+                Control::Steering => sum.clamp(-1.0, 1.0),
+                _ => sum.clamp(0.0, 1.0),
+            }
+        };
+
+        ControllerState {
+            steering: control(Control::Steering),
+            throttle: control(Control::Throttle),
+            brake: control(Control::Brake),
+            clutch: control(Control::Clutch),
+            buttons: self.buttons,
+        }
+    }
+}
+
+// controller state at the start of a game frame
+#[derive(Debug, Clone, Copy)]
+pub struct ControllerSnapshot {
+    pub state: ControllerState,
+    // packet id and game time of the FrameStart
+    pub frame_id: u64,
+    pub game_time: u64,
+    // sequence of the snapshot, capture time of the FrameStart
+    pub capture: CaptureStamp,
+    raw: [u8; SNAPSHOT_SIZE],
+}
+
+impl ControllerSnapshot {
+    pub fn new(
+        state: ControllerState,
+        frame_id: u64,
+        game_time: u64,
+        capture: CaptureStamp,
+    ) -> ControllerSnapshot {
+        ControllerSnapshot {
+            state,
+            frame_id,
+            game_time,
+            capture,
+            raw: state.to_bytes(),
+        }
+    }
+
+    // None when the input is not a controller snapshot
+    pub fn from_input(input: &dyn Input) -> Option<&ControllerSnapshot> {
+        input.as_any().downcast_ref::<ControllerSnapshot>()
+    }
+}
+
+impl Input for ControllerSnapshot {
+    fn header(&self) -> InputHeader {
+        InputHeader {
+            sequence: self.capture.sequence,
+            game_time: self.game_time,
+            capture: self.capture,
+            payload_size: SNAPSHOT_SIZE as u32,
+            ..InputHeader::new(InputType::User)
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    // state as little endian bytes, see SNAPSHOT_SIZE
+    fn payload(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn serialize(&self, _image_encoding: ImageEncoding) -> Result<Serialized, ServiceError> {
+        Ok(Serialized::Json(json!({
+            "kind": "controller",
+            "frame_id": self.frame_id,
+            "time": self.game_time,
+            "steering": self.state.steering,
+            "throttle": self.state.throttle,
+            "brake": self.state.brake,
+            "clutch": self.state.clutch,
+            "buttons": self.state.buttons,
+            "capture_sequence": self.capture.sequence,
+            "capture_time": self.capture.time.as_micros() as u64,
+        })))
+    }
+}
+
+// replaces the user events with one ControllerSnapshot per game frame, pushed to the inner
+// policy right after the FrameStart. every other input is passed on unchanged.
+//
+// the state has to follow every event, wrap the PauseGate and not the other way around:
+// ControllerAggregator::new(mapping, PauseGate::new(FrameGrouping::new()))
+pub struct ControllerAggregator<P> {
+    tracker: Tracker,
+    inner: P,
+    snapshots: u64,
+}
+
+impl<P: GroupingPolicy> ControllerAggregator<P> {
+    pub fn new(mapping: ControllerMapping, inner: P) -> ControllerAggregator<P> {
+        ControllerAggregator {
+            tracker: Tracker::new(mapping),
+            inner,
+            snapshots: 0,
+        }
+    }
+
+    // state after the events received so far
+    pub fn state(&self) -> ControllerState {
+        self.tracker.state()
+    }
+}
+
+impl<P: GroupingPolicy> GroupingPolicy for ControllerAggregator<P> {
+    fn push(&mut self, input: Box<dyn Input + Send>) -> Vec<Group> {
+        if let Some(event) = UserEvent::from_input(input.as_ref()) {
+            self.tracker.apply(event);
+            return Vec::new();
+        }
+
+        let header = input.header();
+        let mut groups = self.inner.push(input);
+
+        if header.source == InputType::Telemetry && header.event == EventGame::FrameStartEvent {
+            self.snapshots += 1;
+            let snapshot = ControllerSnapshot::new(
+                self.tracker.state(),
+                header.sequence,
+                header.game_time,
+                CaptureStamp::new(self.snapshots, header.capture.time),
+            );
+            groups.extend(self.inner.push(Box::new(snapshot)));
+        }
+
+        groups
+    }
+
+    fn flush(&mut self) -> Vec<Group> {
+        self.inner.flush()
+    }
+}
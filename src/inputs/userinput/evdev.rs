@@ -0,0 +1,138 @@
+use super::UserEventKind;
+
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::mem;
+use std::path::Path;
+use std::time::Duration;
+
+// the kernel stamps events with two longs (tv_sec, tv_usec), 8 bytes each on 64 bit, 4 on 32 bit
+const TIME_SIZE: usize = mem::size_of::<isize>();
+
+// size of the linux struct input_event: tv_sec, tv_usec, type, code, value.
+// 24 bytes on 64 bit, 16 bytes on 32 bit
+pub const EVENT_SIZE: usize = 2 * TIME_SIZE + 8;
+
+// event types, see linux/input-event-codes.h
+pub const EV_SYN: u16 = 0x00;
+pub const EV_KEY: u16 = 0x01;
+pub const EV_REL: u16 = 0x02;
+pub const EV_ABS: u16 = 0x03;
+
+// the kernel buffer of the device overflowed, events have been lost
+pub const SYN_DROPPED: u16 = 3;
+
+// key codes in these ranges are gamepad, joystick and wheel buttons, all other key codes are keys
+const BTN_MISC: u16 = 0x100;
+const KEY_OK: u16 = 0x160;
+const BTN_TRIGGER_HAPPY: u16 = 0x2c0;
+const BTN_TRIGGER_HAPPY40: u16 = 0x2e7;
+
+// one raw event as read from /dev/input/eventN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvdevEvent {
+    // kernel timestamp, wall clock unless the device clock was changed
+    pub time: Duration,
+    pub type_: u16,
+    pub code: u16,
+    pub value: i32,
+}
+
+impl EvdevEvent {
+    pub fn new(time: Duration, type_: u16, code: u16, value: i32) -> EvdevEvent {
+        EvdevEvent {
+            time,
+            type_,
+            code,
+            value,
+        }
+    }
+
+    // native byte order, same as the kernel
+    pub fn from_bytes(bytes: &[u8; EVENT_SIZE]) -> EvdevEvent {
+        let (time, rest) = bytes.split_at(2 * TIME_SIZE);
+
+        let sec = isize::from_ne_bytes(time[..TIME_SIZE].try_into().unwrap()).max(0) as u64;
+        let usec =
+            isize::from_ne_bytes(time[TIME_SIZE..].try_into().unwrap()).clamp(0, 999_999) as u64;
+
+        EvdevEvent {
+            time: Duration::from_secs(sec) + Duration::from_micros(usec),
+            type_: u16::from_ne_bytes(rest[0..2].try_into().unwrap()),
+            code: u16::from_ne_bytes(rest[2..4].try_into().unwrap()),
+            value: i32::from_ne_bytes(rest[4..8].try_into().unwrap()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> [u8; EVENT_SIZE] {
+        let mut bytes = [0u8; EVENT_SIZE];
+        let (time, rest) = bytes.split_at_mut(2 * TIME_SIZE);
+        time[..TIME_SIZE].copy_from_slice(&(self.time.as_secs() as isize).to_ne_bytes());
+        time[TIME_SIZE..].copy_from_slice(&(self.time.subsec_micros() as isize).to_ne_bytes());
+        rest[0..2].copy_from_slice(&self.type_.to_ne_bytes());
+        rest[2..4].copy_from_slice(&self.code.to_ne_bytes());
+        rest[4..8].copy_from_slice(&self.value.to_ne_bytes());
+        bytes
+    }
+
+    // None for synchronization, apart from SYN_DROPPED, and every other event that is not passed on
+    pub fn kind(&self) -> Option<UserEventKind> {
+        match self.type_ {
+            EV_KEY if is_button(self.code) => Some(UserEventKind::Button),
+            EV_KEY => Some(UserEventKind::Key),
+            EV_ABS => Some(UserEventKind::Axis),
+            EV_REL => Some(UserEventKind::Relative),
+            EV_SYN if self.code == SYN_DROPPED => Some(UserEventKind::Dropped),
+            _ => None,
+        }
+    }
+
+    pub fn is_dropped(&self) -> bool {
+        self.type_ == EV_SYN && self.code == SYN_DROPPED
+    }
+}
+
+fn is_button(code: u16) -> bool {
+    (BTN_MISC..KEY_OK).contains(&code) || (BTN_TRIGGER_HAPPY..=BTN_TRIGGER_HAPPY40).contains(&code)
+}
+
+// reads events from a recorded event file, i.e. `cat /dev/input/event3 > wheel.evdev`
+pub struct EventFileReader<R> {
+    reader: R,
+}
+
+impl EventFileReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<EventFileReader<BufReader<File>>> {
+        Ok(EventFileReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: Read> EventFileReader<R> {
+    pub fn new(reader: R) -> EventFileReader<R> {
+        EventFileReader { reader }
+    }
+
+    // None at the end of the file, a partial event at the end is an error
+    pub fn next_event(&mut self) -> io::Result<Option<EvdevEvent>> {
+        let mut bytes = [0u8; EVENT_SIZE];
+        let mut read = 0;
+
+        while read < EVENT_SIZE {
+            match self.reader.read(&mut bytes[read..]) {
+                Ok(0) if read == 0 => return Ok(None),
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "event file ends with a partial event",
+                    ))
+                }
+                Ok(n) => read += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(Some(EvdevEvent::from_bytes(&bytes)))
+    }
+}
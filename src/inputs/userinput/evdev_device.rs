@@ -0,0 +1,189 @@
+use super::{EvdevEvent, UserEvent, EVENT_SIZE};
+use crate::queue::InputSender;
+use crate::utils::{join_thread, thread_finished};
+use crate::{
+    posix_get_last_error, CancellationToken, CaptureSequence, InputProcessMethod, ServiceError,
+    SHUTDOWN_POLL_INTERVAL,
+};
+
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+// events read from a device at once
+const READ_EVENTS: usize = 64;
+
+// the event layout of EvdevEvent must match the kernel
+const _: () = assert!(EVENT_SIZE == std::mem::size_of::<libc::input_event>());
+
+// reads /dev/input/eventN devices, i.e. keyboard, wheel and gamepad. the devices are not grabbed,
+// the game still receives every event. reading requires access to the device, i.e. the input group
+pub struct EvdevDevices {
+    paths: Vec<PathBuf>,
+    transmitter: Option<InputSender>,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+    shutdown: CancellationToken,
+    sequence: CaptureSequence,
+}
+
+impl EvdevDevices {
+    pub fn new<P: AsRef<Path>>(paths: &[P]) -> EvdevDevices {
+        EvdevDevices {
+            paths: paths
+                .iter()
+                .map(|path| path.as_ref().to_path_buf())
+                .collect(),
+            transmitter: None,
+            handle: None,
+            shutdown: CancellationToken::new(),
+            sequence: CaptureSequence::new(),
+        }
+    }
+}
+
+// reads every pending event of the device, the device is non blocking
+fn read_device(
+    device: u16,
+    file: &mut File,
+    sequence: &CaptureSequence,
+    tx: &InputSender,
+) -> Result<(), ServiceError> {
+    let mut buffer = [0u8; EVENT_SIZE * READ_EVENTS];
+
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(()),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            // i.e. ENODEV when the device has been unplugged
+            Err(err) => return Err(ServiceError::Io(err)),
+        };
+
+        if read == 0 {
+            return Ok(());
+        }
+
+        let acquired = Instant::now();
+
+        // the kernel only returns whole events
+        for bytes in buffer[..read].chunks_exact(EVENT_SIZE) {
+            let mut raw = [0u8; EVENT_SIZE];
+            raw.copy_from_slice(bytes);
+            let event = EvdevEvent::from_bytes(&raw);
+
+            if event.is_dropped() {
+                println!("device {} dropped events, reading too slow", device);
+            }
+
+            // SYN_DROPPED is passed on so that the pressed keys of the device are released,
+            // other synchronization events are not
+            if let Some(user_event) = UserEvent::new(device, event, sequence.stamp_at(acquired)) {
+                if tx.send(Box::new(user_event)).is_err() {
+                    return Err(ServiceError::Disconnected(String::from(
+                        "user input output",
+                    )));
+                }
+            }
+        }
+    }
+}
+
+fn read_loop(
+    files: &mut [File],
+    shutdown: &CancellationToken,
+    sequence: &CaptureSequence,
+    tx: &InputSender,
+) -> Result<(), ServiceError> {
+    let mut fds: Vec<libc::pollfd> = files
+        .iter()
+        .map(|file| libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+
+    loop {
+        if shutdown.is_cancelled() {
+            println!("stopping user input loop");
+            return Ok(());
+        }
+
+        //blocks until a device has events, or timeout
+        let ready = unsafe {
+            libc::poll(
+                fds.as_mut_ptr(),
+                fds.len() as libc::nfds_t,
+                SHUTDOWN_POLL_INTERVAL.as_millis() as libc::c_int,
+            )
+        };
+
+        if ready == -1 {
+            if std::io::Error::last_os_error().kind() == ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(posix_get_last_error("poll"));
+        }
+
+        for (device, (fd, file)) in fds.iter().zip(files.iter_mut()).enumerate() {
+            if fd.revents & libc::POLLIN != 0 {
+                read_device(device as u16, file, sequence, tx)?;
+            } else if fd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+                return Err(ServiceError::Io(std::io::Error::new(
+                    ErrorKind::BrokenPipe,
+                    format!("input device {} is no longer available", device),
+                )));
+            }
+        }
+    }
+}
+
+impl InputProcessMethod for EvdevDevices {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.transmitter.is_none() {
+            return Err(ServiceError::TransmitterNotSet);
+        }
+
+        // missing devices and permissions fail the start, not the thread
+        let mut files = Vec::with_capacity(self.paths.len());
+        for path in self.paths.iter() {
+            let file = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)?;
+            files.push(file);
+        }
+
+        self.shutdown = CancellationToken::new();
+        let shutdown = self.shutdown.clone();
+        let sequence = self.sequence.clone();
+        let tx = self.transmitter.take().unwrap();
+
+        let handle = thread::spawn(move || read_loop(&mut files, &shutdown, &sequence, &tx));
+
+        self.handle = Some(handle);
+        Ok(())
+    }
+    fn stop(&mut self) {
+        self.shutdown.cancel();
+    }
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+    fn method(&self) -> &str {
+        "evdev devices"
+    }
+    fn set_transmitter(&mut self, transmitter: InputSender) {
+        self.transmitter = Some(transmitter);
+    }
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.sequence = sequence;
+    }
+}
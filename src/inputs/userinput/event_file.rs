@@ -0,0 +1,159 @@
+use super::{EvdevEvent, EventFileReader, UserEvent};
+use crate::queue::InputSender;
+use crate::telemetry::ReplaySpeed;
+use crate::utils::{join_thread, thread_finished};
+use crate::{CancellationToken, CaptureSequence, InputProcessMethod, ServiceError};
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+// replays recorded evdev event files as user input, one file per device.
+// events of all files are merged by their kernel timestamp, stops at the end of the files
+pub struct EventFileReplay {
+    paths: Vec<PathBuf>,
+    speed: ReplaySpeed,
+    transmitter: Option<InputSender>,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+    shutdown: CancellationToken,
+    sequence: CaptureSequence,
+}
+
+impl EventFileReplay {
+    pub fn new<P: AsRef<Path>>(paths: &[P], speed: ReplaySpeed) -> EventFileReplay {
+        EventFileReplay {
+            paths: paths
+                .iter()
+                .map(|path| path.as_ref().to_path_buf())
+                .collect(),
+            speed,
+            transmitter: None,
+            handle: None,
+            shutdown: CancellationToken::new(),
+            sequence: CaptureSequence::new(),
+        }
+    }
+}
+
+// next event of every device, None once the file has ended
+struct Devices {
+    readers: Vec<EventFileReader<BufReader<File>>>,
+    next: Vec<Option<EvdevEvent>>,
+}
+
+impl Devices {
+    fn open(paths: &[PathBuf]) -> Result<Devices, ServiceError> {
+        let mut readers = Vec::with_capacity(paths.len());
+        let mut next = Vec::with_capacity(paths.len());
+
+        for path in paths {
+            let mut reader = EventFileReader::open(path)?;
+            next.push(reader.next_event()?);
+            readers.push(reader);
+        }
+
+        Ok(Devices { readers, next })
+    }
+
+    // earliest event of all devices, ties go to the first device
+    fn pop(&mut self) -> Result<Option<(u16, EvdevEvent)>, ServiceError> {
+        let device = self
+            .next
+            .iter()
+            .enumerate()
+            .filter_map(|(device, event)| event.map(|event| (device, event.time)))
+            .min_by_key(|(_, time)| *time)
+            .map(|(device, _)| device);
+
+        let device = match device {
+            Some(device) => device,
+            None => return Ok(None),
+        };
+
+        let event = self.next[device].take().unwrap();
+        self.next[device] = self.readers[device].next_event()?;
+
+        Ok(Some((device as u16, event)))
+    }
+}
+
+impl InputProcessMethod for EventFileReplay {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.transmitter.is_none() {
+            return Err(ServiceError::TransmitterNotSet);
+        }
+
+        // missing files fail the start, not the thread
+        let mut devices = Devices::open(&self.paths)?;
+
+        self.shutdown = CancellationToken::new();
+        let shutdown = self.shutdown.clone();
+        let speed = self.speed;
+        let sequence = self.sequence.clone();
+        let tx = self.transmitter.take().unwrap();
+
+        let handle = thread::spawn(move || {
+            let mut previous: Option<u64> = None;
+            let mut deadline = Instant::now();
+
+            while let Some((device, event)) = devices.pop()? {
+                if event.is_dropped() {
+                    println!("device {} dropped events while recording", device);
+                }
+
+                // synchronization and other events are not passed on and do not delay
+                if event.kind().is_none() {
+                    continue;
+                }
+
+                let time = event.time.as_micros() as u64;
+                if let Some(previous) = previous {
+                    deadline += speed.delay(previous, time);
+                }
+                previous = Some(time);
+
+                let wait = deadline.saturating_duration_since(Instant::now());
+                if shutdown.wait_timeout(wait) {
+                    println!("stopping user input replay loop");
+                    return Ok(());
+                }
+
+                let user_event = match UserEvent::new(device, event, sequence.stamp()) {
+                    Some(user_event) => user_event,
+                    None => continue,
+                };
+
+                if tx.send(Box::new(user_event)).is_err() {
+                    return Err(ServiceError::Disconnected(String::from(
+                        "user input replay output",
+                    )));
+                }
+            }
+
+            Ok(())
+        });
+
+        self.handle = Some(handle);
+        Ok(())
+    }
+    fn stop(&mut self) {
+        self.shutdown.cancel();
+    }
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+    fn method(&self) -> &str {
+        "evdev event file replay"
+    }
+    fn set_transmitter(&mut self, transmitter: InputSender) {
+        self.transmitter = Some(transmitter);
+    }
+    fn set_capture_sequence(&mut self, sequence: CaptureSequence) {
+        self.sequence = sequence;
+    }
+}
@@ -0,0 +1,109 @@
+use super::{EvdevEvent, EVENT_SIZE};
+use crate::{CaptureStamp, ImageEncoding, Input, InputHeader, InputType, Serialized, ServiceError};
+use serde_json::json;
+use std::any::Any;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserEventKind {
+    // keyboard key, value 0 released, 1 pressed, 2 autorepeat
+    Key,
+    // gamepad, joystick or wheel button, value 0 released, 1 pressed
+    Button,
+    // absolute axis position in device units, i.e. steering, pedals or sticks
+    Axis,
+    // relative axis change, i.e. mouse movement or scroll wheel
+    Relative,
+    // SYN_DROPPED, the device buffer overflowed and events were lost. the state of its keys,
+    // buttons and axes is unknown until they change again
+    Dropped,
+}
+
+impl UserEventKind {
+    pub fn name(&self) -> &str {
+        match self {
+            UserEventKind::Key => "key",
+            UserEventKind::Button => "button",
+            UserEventKind::Axis => "axis",
+            UserEventKind::Relative => "relative",
+            UserEventKind::Dropped => "dropped",
+        }
+    }
+}
+
+// one key, button or axis change of an input device
+#[derive(Debug, Clone, Copy)]
+pub struct UserEvent {
+    // index of the device in the list given to the user input service
+    pub device: u16,
+    pub kind: UserEventKind,
+    // evdev code, i.e. KEY_W, BTN_SOUTH or ABS_X
+    pub code: u16,
+    pub value: i32,
+    // kernel timestamp of the event
+    pub time: Duration,
+    pub capture: CaptureStamp,
+    raw: [u8; EVENT_SIZE],
+}
+
+impl UserEvent {
+    // None when the event is not a key, button, axis or dropped event
+    pub fn new(device: u16, event: EvdevEvent, capture: CaptureStamp) -> Option<UserEvent> {
+        Some(UserEvent {
+            device,
+            kind: event.kind()?,
+            code: event.code,
+            value: event.value,
+            time: event.time,
+            capture,
+            raw: event.to_bytes(),
+        })
+    }
+
+    // None when the input is not a user event
+    pub fn from_input(input: &dyn Input) -> Option<&UserEvent> {
+        input.as_any().downcast_ref::<UserEvent>()
+    }
+
+    pub fn evdev(&self) -> EvdevEvent {
+        EvdevEvent::from_bytes(&self.raw)
+    }
+
+    // key or button held down, autorepeat included
+    pub fn is_pressed(&self) -> bool {
+        self.value != 0
+    }
+}
+
+impl Input for UserEvent {
+    fn header(&self) -> InputHeader {
+        InputHeader {
+            sequence: self.capture.sequence,
+            event_code: self.code as u32,
+            capture: self.capture,
+            payload_size: EVENT_SIZE as u32,
+            ..InputHeader::new(InputType::User)
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    // the raw evdev event
+    fn payload(&self) -> &[u8] {
+        &self.raw
+    }
+
+    fn serialize(&self, _image_encoding: ImageEncoding) -> Result<Serialized, ServiceError> {
+        Ok(Serialized::Json(json!({
+            "device": self.device,
+            "kind": self.kind.name(),
+            "code": self.code,
+            "value": self.value,
+            "time": self.time.as_micros() as u64,
+            "capture_sequence": self.capture.sequence,
+            "capture_time": self.capture.time.as_micros() as u64,
+        })))
+    }
+}
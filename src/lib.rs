@@ -3,21 +3,59 @@
 // extern crate wio;
 
 mod services;
-pub use services::{Input, InputType, Process, ServiceError, ServiceType, Services};
+pub use services::{
+    Input, InputHeader, InputType, Process, ServiceError, ServiceState, ServiceType, Services,
+};
+
+mod capture;
+pub use capture::{CaptureClock, CaptureSequence, CaptureStamp};
+
+mod cancellation;
+pub use cancellation::{CancellationToken, SHUTDOWN_POLL_INTERVAL};
+
+pub mod queue;
+
+mod dependencies;
+pub use dependencies::DependencyGraph;
+
+mod restart;
+pub use restart::{Backoff, RestartPolicy};
+
+mod serialization;
+pub use serialization::{ImageEncoding, Serialized};
 
 mod utils;
 
-pub use utils::{str_to_wstring, windows_get_last_error};
+#[cfg(unix)]
+pub use utils::posix_get_last_error;
+pub use utils::str_to_wstring;
+#[cfg(windows)]
+pub use utils::windows_get_last_error;
 
 #[path = "inputs/synchronization.rs"]
 pub mod synchronization;
 
+#[path = "inputs/resampling.rs"]
+pub mod resampling;
+
 #[path = "inputs/telemetry.rs"]
 pub mod telemetry;
 
 #[path = "inputs/screencapture.rs"]
 pub mod screencapture;
 
+#[path = "inputs/userinput.rs"]
+pub mod userinput;
+
+#[path = "outputs/dataset.rs"]
+pub mod dataset;
+
+#[path = "outputs/virtual_controller.rs"]
+pub mod virtual_controller;
+
+#[path = "outputs/control_loop.rs"]
+pub mod control_loop;
+
 #[path = "inputs/inputprocessmethod.rs"]
 mod inputprocessmethod;
 pub use inputprocessmethod::InputProcessMethod;
@@ -0,0 +1,305 @@
+use crate::queue::{bounded, GroupReceiver, GroupSender, QueuePolicy};
+use crate::utils::{join_thread, thread_finished};
+use crate::virtual_controller::{Action, OutputBackend, VirtualController};
+use crate::{
+    CancellationToken, CaptureClock, Input, Process, ServiceError, SHUTDOWN_POLL_INTERVAL,
+};
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[path = "control_loop/cruise.rs"]
+mod cruise;
+pub use cruise::{CruiseControl, Limits, PidGains, Setpoint};
+
+// groups captured longer ago are not acted on
+pub const DEFAULT_MAX_LATENCY: Duration = Duration::from_millis(100);
+
+// inputs are released when no actions have been applied for this long, i.e. the policy stalls,
+// telemetry paused or the game stopped
+pub const DEFAULT_WATCHDOG_TIMEOUT: Duration = Duration::from_millis(250);
+
+// decides the actions for a synchronized group: telemetry, image and user inputs of one frame
+pub trait Policy: Send {
+    // no actions keeps the previous state of the controller
+    fn act(&mut self, group: &[Box<dyn Input + Send>]) -> Vec<Action>;
+
+    // called before the next group after the watchdog released every input, i.e. to clear state
+    // that accumulated while nothing was applied
+    fn reset(&mut self) {}
+}
+
+impl<P: Policy + ?Sized> Policy for Box<P> {
+    fn act(&mut self, group: &[Box<dyn Input + Send>]) -> Vec<Action> {
+        (**self).act(group)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ControlCounters {
+    // groups received
+    pub groups: u64,
+    // groups whose actions were applied, rejected groups are not counted
+    pub acted: u64,
+    // groups older than the max latency before or after the policy acted, not applied
+    pub stale: u64,
+    // actions refused by the controller, see ServiceError::InvalidAction
+    pub rejected: u64,
+    // times the watchdog released every input
+    pub releases: u64,
+}
+
+struct Watchdog {
+    // last time actions were applied, or the watchdog released
+    last_action: Instant,
+    released: bool,
+    counters: ControlCounters,
+}
+
+// Responsible for closing the loop: every synchronized group is passed to the policy and its
+// actions are applied to the virtual controller.
+//
+// latency is bounded by keeping only the latest group and skipping groups captured longer than
+// max latency ago, before and after the policy acted. a watchdog thread releases every input
+// when nothing was applied within the watchdog timeout, no groups arrive while telemetry is paused
+pub struct ControlLoop {
+    policy: Option<Box<dyn Policy>>,
+    controller: Arc<Mutex<VirtualController>>,
+    max_latency: Duration,
+    watchdog_timeout: Duration,
+    watchdog: Arc<Mutex<Watchdog>>,
+    input_transmitter: GroupSender,
+    input_receiver: Option<GroupReceiver>,
+    shutdown: CancellationToken,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+}
+
+impl ControlLoop {
+    pub fn new(policy: Box<dyn Policy>, backend: Box<dyn OutputBackend>) -> ControlLoop {
+        let (input_transmitter, input_receiver) = bounded(QueuePolicy::keep_latest());
+        ControlLoop {
+            policy: Some(policy),
+            controller: Arc::new(Mutex::new(VirtualController::new(backend))),
+            max_latency: DEFAULT_MAX_LATENCY,
+            watchdog_timeout: DEFAULT_WATCHDOG_TIMEOUT,
+            watchdog: Arc::new(Mutex::new(Watchdog {
+                last_action: Instant::now(),
+                released: true,
+                counters: ControlCounters::default(),
+            })),
+            input_transmitter,
+            input_receiver: Some(input_receiver),
+            shutdown: CancellationToken::new(),
+            handle: None,
+        }
+    }
+
+    pub fn set_max_latency(&mut self, max_latency: Duration) {
+        self.max_latency = max_latency;
+    }
+
+    pub fn set_watchdog_timeout(&mut self, timeout: Duration) {
+        self.watchdog_timeout = timeout;
+    }
+
+    //return cloned transmitter, use as the synchronization or resampler output transmitter
+    pub fn get_input_transmitter(&self) -> GroupSender {
+        self.input_transmitter.clone()
+    }
+
+    pub fn counters(&self) -> ControlCounters {
+        self.watchdog.lock().unwrap().counters
+    }
+
+    // groups replaced by a newer group before the policy got to them
+    pub fn dropped(&self) -> u64 {
+        self.input_transmitter.dropped().total()
+    }
+}
+
+// time since the latest capture of the group, zero when nothing in the group has been captured
+fn group_age(group: &[Box<dyn Input + Send>]) -> Duration {
+    group
+        .iter()
+        .map(|input| input.capture())
+        .filter(|capture| capture.is_captured())
+        .map(|capture| capture.time)
+        .max()
+        .map_or(Duration::from_secs(0), |time| {
+            CaptureClock::now().saturating_sub(time)
+        })
+}
+
+fn watchdog_loop(
+    watchdog: &Mutex<Watchdog>,
+    controller: &Mutex<VirtualController>,
+    timeout: Duration,
+    shutdown: &CancellationToken,
+) -> Result<(), ServiceError> {
+    while !shutdown.wait_timeout(SHUTDOWN_POLL_INTERVAL.min(timeout)) {
+        let mut state = watchdog.lock().unwrap();
+        if state.released || state.last_action.elapsed() < timeout {
+            continue;
+        }
+
+        println!(
+            "control watchdog: no action within {:?}, releasing inputs",
+            timeout
+        );
+        controller.lock().unwrap().release_all()?;
+        state.released = true;
+        state.last_action = Instant::now();
+        state.counters.releases += 1;
+    }
+
+    Ok(())
+}
+
+impl Process for ControlLoop {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_none() {
+            return Err(ServiceError::AlreadyActive);
+        }
+
+        // create the device up front, a missing /dev/uinput fails the start
+        self.controller.lock().unwrap().open()?;
+
+        let receiver = self.input_receiver.take().unwrap();
+        let mut policy = self.policy.take().unwrap();
+        let controller = self.controller.clone();
+        let watchdog = self.watchdog.clone();
+        let max_latency = self.max_latency;
+        let timeout = self.watchdog_timeout;
+
+        watchdog.lock().unwrap().last_action = Instant::now();
+
+        let shutdown = CancellationToken::new();
+        self.shutdown = shutdown.clone();
+
+        let process = move || {
+            let watchdog_shutdown = CancellationToken::new();
+
+            let result = thread::scope(|scope| {
+                let watchdog_thread = scope.spawn(|| {
+                    let result = watchdog_loop(&watchdog, &controller, timeout, &watchdog_shutdown);
+                    // without a working watchdog the inputs may stay pressed, stop acting
+                    if let Err(err) = &result {
+                        println!("control watchdog failed {}, stopping the control loop", err);
+                        shutdown.cancel();
+                    }
+                    result
+                });
+
+                // a panicking policy must still stop the watchdog, the scope waits for it
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    control_loop(
+                        &receiver,
+                        &mut *policy,
+                        &controller,
+                        &watchdog,
+                        max_latency,
+                        &shutdown,
+                    )
+                }))
+                .unwrap_or_else(|_| Err(ServiceError::Panicked(String::from("control policy"))));
+
+                watchdog_shutdown.cancel();
+                let watchdog_result = watchdog_thread.join().unwrap_or_else(|_| {
+                    Err(ServiceError::Panicked(String::from("control watchdog")))
+                });
+
+                result.and(watchdog_result)
+            });
+
+            println!("exiting control loop");
+
+            // the device must not keep inputs pressed after the service stopped
+            let closed = controller.lock().unwrap().close();
+            result.and(closed)
+        };
+
+        self.handle = Some(thread::spawn(process));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_some() {
+            return Err(ServiceError::NotActive);
+        }
+        self.shutdown.cancel();
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+}
+
+fn control_loop(
+    receiver: &GroupReceiver,
+    policy: &mut dyn Policy,
+    controller: &Mutex<VirtualController>,
+    watchdog: &Mutex<Watchdog>,
+    max_latency: Duration,
+    shutdown: &CancellationToken,
+) -> Result<(), ServiceError> {
+    while let Some(groups) = shutdown.recv_all(receiver) {
+        for group in groups {
+            let stale = group_age(&group) > max_latency;
+            let released = {
+                let mut state = watchdog.lock().unwrap();
+                state.counters.groups += 1;
+                if stale {
+                    state.counters.stale += 1;
+                }
+                state.released
+            };
+
+            if stale {
+                continue;
+            }
+
+            if released {
+                policy.reset();
+            }
+
+            // the policy runs without holding a lock, the watchdog can release while it stalls
+            let actions = policy.act(&group);
+
+            // the policy took too long, the actions are outdated and the inputs may be released
+            if group_age(&group) > max_latency {
+                watchdog.lock().unwrap().counters.stale += 1;
+                continue;
+            }
+
+            let applied = controller.lock().unwrap().apply(&actions);
+
+            let mut state = watchdog.lock().unwrap();
+            match applied {
+                Ok(()) => {
+                    state.counters.acted += 1;
+                    state.last_action = Instant::now();
+                    state.released = false;
+                }
+                // nothing was emitted, rejected actions must not feed the watchdog
+                Err(ServiceError::InvalidAction(action)) => {
+                    println!("control loop: policy returned invalid action {}", action);
+                    state.counters.rejected += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use super::Policy;
+use crate::telemetry::ets2::{Ets2Payload, FrameEnd};
+use crate::virtual_controller::Action;
+use crate::Input;
+
+// where the target speed comes from, speeds in m/s
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setpoint {
+    Fixed(f32),
+    // navigation speed limit of the current road
+    SpeedLimit,
+    // cruise control speed set in the game
+    Cruise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PidGains {
+    // pedal per m/s of error
+    pub kp: f32,
+    // pedal per m of accumulated error
+    pub ki: f32,
+    // pedal per m/s^2, on the measured speed so that setpoint steps do not kick
+    pub kd: f32,
+}
+
+impl Default for PidGains {
+    fn default() -> Self {
+        PidGains {
+            kp: 0.2,
+            ki: 0.05,
+            kd: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    // pedals are clamped to <0,max>
+    pub max_throttle: f32,
+    pub max_brake: f32,
+    // the integral is clamped to <-max,max> m, prevents windup while the pedals saturate
+    pub max_integral: f32,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_throttle: 1.0,
+            max_brake: 1.0,
+            max_integral: 10.0,
+        }
+    }
+}
+
+struct Previous {
+    game_time: u64,
+    speed: f32,
+}
+
+// reference policy, holds the target speed with throttle and brake from frame_end telemetry.
+// steering, gears and keys are left to the player or another policy
+pub struct CruiseControl {
+    setpoint: Setpoint,
+    gains: PidGains,
+    limits: Limits,
+    integral: f32,
+    previous: Option<Previous>,
+}
+
+impl CruiseControl {
+    pub fn new(setpoint: Setpoint) -> CruiseControl {
+        CruiseControl {
+            setpoint,
+            gains: PidGains::default(),
+            limits: Limits::default(),
+            integral: 0.0,
+            previous: None,
+        }
+    }
+
+    pub fn with_gains(mut self, gains: PidGains) -> CruiseControl {
+        self.gains = gains;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> CruiseControl {
+        self.limits = limits;
+        self
+    }
+
+    pub fn setpoint(&self) -> Setpoint {
+        self.setpoint
+    }
+
+    pub fn gains(&self) -> PidGains {
+        self.gains
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    // target speed in m/s, None when there is no speed limit or cruise control is disabled
+    pub fn target(&self, frame_end: &FrameEnd) -> Option<f32> {
+        let target = match self.setpoint {
+            Setpoint::Fixed(speed) => speed,
+            Setpoint::SpeedLimit => frame_end.speed_limit_ms(),
+            Setpoint::Cruise => frame_end.cruise_control_ms(),
+        };
+
+        if target > 0.0 {
+            Some(target)
+        } else {
+            None
+        }
+    }
+
+    // pedal output for the frame, positive throttles and negative brakes. game time in us
+    fn step(&mut self, target: f32, speed: f32, game_time: u64) -> f32 {
+        let error = target - speed;
+
+        // the first frame and frames without game time progress, i.e. a replay restart, are
+        // proportional only
+        let dt = match &self.previous {
+            Some(previous) if game_time > previous.game_time => {
+                Some((game_time - previous.game_time) as f32 / 1_000_000.0)
+            }
+            _ => None,
+        };
+
+        let mut derivative = 0.0;
+        if let (Some(dt), Some(previous)) = (dt, &self.previous) {
+            let max = self.limits.max_integral;
+            self.integral = (self.integral + error * dt).clamp(-max, max);
+            derivative = -(speed - previous.speed) / dt;
+        }
+
+        self.previous = Some(Previous { game_time, speed });
+        self.gains.kp * error + self.gains.ki * self.integral + self.gains.kd * derivative
+    }
+}
+
+impl Policy for CruiseControl {
+    fn act(&mut self, group: &[Box<dyn Input + Send>]) -> Vec<Action> {
+        // the latest frame end, resampled groups may contain several
+        let frame = group.iter().rev().find_map(|input| {
+            let payload = Ets2Payload::from_input(input.as_ref())?;
+            payload
+                .frame_end()
+                .map(|frame_end| (input.header().game_time, *frame_end))
+        });
+
+        let (game_time, frame_end) = match frame {
+            Some(frame) => frame,
+            None => return Vec::new(),
+        };
+
+        let target = match self.target(&frame_end) {
+            Some(target) => target,
+            None => {
+                // coast until there is a target again
+                self.reset();
+                return vec![Action::Throttle(0.0), Action::Brake(0.0)];
+            }
+        };
+
+        let output = self.step(target, frame_end.speed_ms(), game_time);
+        if output >= 0.0 {
+            vec![
+                Action::Throttle(output.min(self.limits.max_throttle)),
+                Action::Brake(0.0),
+            ]
+        } else {
+            vec![
+                Action::Throttle(0.0),
+                Action::Brake((-output).min(self.limits.max_brake)),
+            ]
+        }
+    }
+
+    fn reset(&mut self) {
+        self.integral = 0.0;
+        self.previous = None;
+    }
+}
@@ -0,0 +1,294 @@
+use crate::queue::{bounded, GroupReceiver, GroupSender, QueuePolicy};
+use crate::telemetry::EventGame;
+use crate::utils::{join_thread, thread_finished};
+use crate::{
+    CancellationToken, CaptureClock, ImageEncoding, Input, InputType, Process, Serialized,
+    ServiceError,
+};
+
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const RECORDS_FILE: &str = "records.jsonl";
+pub const INDEX_FILE: &str = "index.jsonl";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const IMAGES_DIR: &str = "images";
+
+// groups waiting to be written
+pub const DEFAULT_QUEUE_CAPACITY: usize = 64;
+
+// Responsible for persisting synchronized groups. Every group becomes one record:
+// a json line with the telemetry and user inputs and the latest screenshot of the group as an
+// image file.
+//
+// <output_dir>/<session>/records.jsonl
+// <output_dir>/<session>/index.jsonl (frame, time and image of every record)
+// <output_dir>/<session>/images/000000.png
+// <output_dir>/<session>/manifest.json (session and number of records)
+//
+// the files are flushed after every batch of groups, a killed writer leaves a readable session
+pub struct DatasetWriter {
+    output_dir: PathBuf,
+    session: String,
+    image_encoding: ImageEncoding,
+    input_transmitter: GroupSender,
+    input_receiver: Option<GroupReceiver>,
+    shutdown: CancellationToken,
+    handle: Option<thread::JoinHandle<Result<(), ServiceError>>>,
+}
+
+impl DatasetWriter {
+    // session is named after the start time, use set_session_name to override
+    pub fn new<P: AsRef<Path>>(output_dir: P) -> DatasetWriter {
+        DatasetWriter::with_queue_policy(output_dir, QueuePolicy::block(DEFAULT_QUEUE_CAPACITY))
+    }
+
+    // policy of the group queue, blocking slows synchronization down when the disk falls behind
+    pub fn with_queue_policy<P: AsRef<Path>>(output_dir: P, policy: QueuePolicy) -> DatasetWriter {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+
+        let (input_transmitter, input_receiver) = bounded(policy);
+        DatasetWriter {
+            output_dir: output_dir.as_ref().to_path_buf(),
+            session: format!("session-{}", seconds),
+            image_encoding: ImageEncoding::Png,
+            input_transmitter,
+            input_receiver: Some(input_receiver),
+            shutdown: CancellationToken::new(),
+            handle: None,
+        }
+    }
+
+    pub fn set_session_name(&mut self, session: &str) {
+        self.session = String::from(session);
+    }
+
+    pub fn set_image_encoding(&mut self, image_encoding: ImageEncoding) {
+        self.image_encoding = image_encoding;
+    }
+
+    // groups discarded because the writer fell behind, always 0 with a blocking policy
+    pub fn dropped(&self) -> u64 {
+        self.input_transmitter.dropped().total()
+    }
+
+    pub fn session_dir(&self) -> PathBuf {
+        self.output_dir.join(&self.session)
+    }
+
+    //return cloned transmitter, use as the synchronization output transmitter
+    pub fn get_input_transmitter(&self) -> GroupSender {
+        self.input_transmitter.clone()
+    }
+}
+
+struct Session {
+    dir: PathBuf,
+    name: String,
+    image_encoding: ImageEncoding,
+    records: BufWriter<File>,
+    index: BufWriter<File>,
+    // records written so far
+    count: usize,
+}
+
+impl Session {
+    fn create(dir: PathBuf, name: String, image_encoding: ImageEncoding) -> io::Result<Session> {
+        // never overwrite a previous session
+        let existing = [MANIFEST_FILE, RECORDS_FILE, INDEX_FILE];
+        if existing.iter().any(|file| dir.join(file).exists()) {
+            return Err(io::Error::new(
+                ErrorKind::AlreadyExists,
+                format!("dataset session {} already exists", dir.display()),
+            ));
+        }
+
+        fs::create_dir_all(dir.join(IMAGES_DIR))?;
+        let records = BufWriter::new(File::create(dir.join(RECORDS_FILE))?);
+        let index = BufWriter::new(File::create(dir.join(INDEX_FILE))?);
+
+        Ok(Session {
+            dir,
+            name,
+            image_encoding,
+            records,
+            index,
+            count: 0,
+        })
+    }
+
+    fn write(&mut self, group: &[Box<dyn Input + Send>]) -> Result<(), ServiceError> {
+        let record = self.count;
+
+        let mut telemetry = Vec::new();
+        let mut user = Vec::new();
+        let mut image = None;
+
+        for input in group.iter() {
+            match input.input_type() {
+                InputType::Telemetry => {
+                    if let Serialized::Json(value) = input.serialize(self.image_encoding)? {
+                        telemetry.push(value);
+                    }
+                }
+                // controller snapshots and raw user events
+                InputType::User => {
+                    if let Serialized::Json(value) = input.serialize(self.image_encoding)? {
+                        user.push(value);
+                    }
+                }
+                // latest screenshot in the group is the matched one
+                InputType::Image => image = Some(input),
+            }
+        }
+
+        let image_capture_time = image.map(|input| input.capture().time.as_micros() as u64);
+
+        let image_file = match image {
+            Some(input) => match input.serialize(self.image_encoding)? {
+                Serialized::Image { encoding, data } => {
+                    let file = format!("{}/{:06}.{}", IMAGES_DIR, record, encoding.extension());
+                    fs::write(self.dir.join(&file), data).map_err(ServiceError::Io)?;
+                    Some(file)
+                }
+                Serialized::Json(_) => None,
+            },
+            None => None,
+        };
+
+        // group is framed by FrameStart and FrameEnd, index the record by the FrameStart. a
+        // resampled or user input group may start with another input, fall back to the first
+        // telemetry input
+        let (frame_id, time, capture_time) = group
+            .iter()
+            .find(|input| input.event_type() == EventGame::FrameStartEvent)
+            .or_else(|| {
+                group
+                    .iter()
+                    .find(|input| matches!(input.input_type(), InputType::Telemetry))
+            })
+            .map(|input| {
+                let header = input.header();
+                (
+                    header.sequence,
+                    header.game_time,
+                    header.capture.time.as_micros() as u64,
+                )
+            })
+            .unwrap_or((0, 0, 0));
+
+        let line = json!({
+            "record": record,
+            "frame_id": frame_id,
+            "time": time,
+            "capture_time": capture_time,
+            "telemetry": telemetry,
+            "user": user,
+            "image": image_file,
+            "image_capture_time": image_capture_time,
+        });
+
+        writeln!(self.records, "{}", line).map_err(ServiceError::Io)?;
+
+        let entry = json!({
+            "record": record,
+            "frame_id": frame_id,
+            "time": time,
+            "inputs": group.len(),
+            "image": image_file,
+        });
+        writeln!(self.index, "{}", entry).map_err(ServiceError::Io)?;
+
+        self.count += 1;
+        Ok(())
+    }
+
+    // flushes the records and the index and rewrites the manifest with the current count
+    fn flush(&mut self) -> Result<(), ServiceError> {
+        self.records.flush().map_err(ServiceError::Io)?;
+        self.index.flush().map_err(ServiceError::Io)?;
+
+        let manifest = json!({
+            "session": self.name,
+            "records_file": RECORDS_FILE,
+            "index_file": INDEX_FILE,
+            "image_encoding": self.image_encoding.extension(),
+            "records": self.count,
+            // capture times are microseconds since this wall clock time
+            "capture_clock_started_at": CaptureClock::started_at()
+                .duration_since(UNIX_EPOCH)
+                .map(|time| time.as_micros() as u64)
+                .unwrap_or(0),
+        });
+
+        let data = serde_json::to_vec_pretty(&manifest)
+            .map_err(|err| ServiceError::Serialization(Box::new(err)))?;
+
+        fs::write(self.dir.join(MANIFEST_FILE), data).map_err(ServiceError::Io)
+    }
+}
+
+impl Process for DatasetWriter {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_none() {
+            return Err(ServiceError::AlreadyActive);
+        }
+
+        let mut session = Session::create(
+            self.session_dir(),
+            self.session.clone(),
+            self.image_encoding,
+        )
+        .map_err(ServiceError::Io)?;
+
+        let receiver = self.input_receiver.take().unwrap();
+
+        let shutdown = CancellationToken::new();
+        self.shutdown = shutdown.clone();
+
+        let process = move || {
+            // groups queued before stop are still written
+            while let Some(groups) = shutdown.recv_all(&receiver) {
+                for group in groups {
+                    if let Err(err) = session.write(&group) {
+                        // keep the records written so far readable
+                        if let Err(err) = session.flush() {
+                            println!("dataset writer manifest error {}", err);
+                        }
+                        return Err(err);
+                    }
+                }
+                session.flush()?;
+            }
+
+            println!("exiting dataset writer loop");
+            session.flush()
+        };
+
+        self.handle = Some(thread::spawn(process));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        if self.input_receiver.is_some() {
+            return Err(ServiceError::NotActive);
+        }
+        self.shutdown.cancel();
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        join_thread(self.handle.take())
+    }
+
+    fn is_finished(&self) -> bool {
+        thread_finished(&self.handle)
+    }
+}
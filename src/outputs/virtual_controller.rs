@@ -0,0 +1,389 @@
+use crate::userinput::{EvdevEvent, EV_ABS, EV_KEY, EV_SYN};
+use crate::{CaptureClock, ServiceError};
+
+use std::collections::BTreeSet;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+// uinput is only available on linux
+#[cfg(target_os = "linux")]
+#[path = "virtual_controller/uinput.rs"]
+mod uinput;
+#[cfg(target_os = "linux")]
+pub use uinput::UinputBackend;
+
+// axes of the virtual device, see linux/input-event-codes.h
+pub const ABS_STEERING: u16 = 0x00; // ABS_X
+pub const ABS_CLUTCH: u16 = 0x06; // ABS_THROTTLE, ABS_CLUTCH does not exist
+pub const ABS_THROTTLE: u16 = 0x09; // ABS_GAS
+pub const ABS_BRAKE: u16 = 0x0a; // ABS_BRAKE
+
+// steering is reported between -AXIS_MAX and AXIS_MAX, pedals between 0 and AXIS_MAX
+pub const AXIS_MAX: i32 = 32_767;
+
+// keyboard keys, KEY_ESC to KEY_MICMUTE
+const KEY_FIRST: u16 = 1;
+const KEY_LAST: u16 = 248;
+// gamepad buttons, BTN_SOUTH to BTN_THUMBR
+const BTN_FIRST: u16 = 0x130;
+const BTN_LAST: u16 = 0x13e;
+
+// gears are h-shifter buttons held while in gear: BTN_TRIGGER_HAPPY1 for the first forward gear,
+// BTN_TRIGGER_HAPPY17 for the first reverse gear. bind them in the game as h-shifter slots
+pub const GEAR_BUTTON_FIRST: u16 = 0x2c0;
+pub const FORWARD_GEARS: i32 = 16;
+pub const REVERSE_GEARS: i32 = 4;
+
+const SYN_REPORT: u16 = 0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    // -1.0 full left, 1.0 full right
+    Steering(f32),
+    // 0.0 released, 1.0 fully pressed
+    Throttle(f32),
+    Brake(f32),
+    Clutch(f32),
+    // 0 neutral, 1 to FORWARD_GEARS forward, -1 to -REVERSE_GEARS reverse
+    Gear(i32),
+    // keyboard key or gamepad button by evdev code, i.e. KEY_E or BTN_SOUTH
+    Key { code: u16, pressed: bool },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisInfo {
+    pub code: u16,
+    pub min: i32,
+    pub max: i32,
+}
+
+// what the virtual device reports, declared when the device is created
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    pub name: String,
+    pub keys: Vec<u16>,
+    pub axes: Vec<AxisInfo>,
+}
+
+impl Capabilities {
+    // wheel with pedals and h-shifter, gamepad buttons and a full keyboard
+    pub fn driving() -> Capabilities {
+        let axis = |code: u16, min: i32| AxisInfo {
+            code,
+            min,
+            max: AXIS_MAX,
+        };
+        let gears = (FORWARD_GEARS + REVERSE_GEARS) as u16;
+
+        Capabilities {
+            name: String::from("control virtual controller"),
+            keys: (KEY_FIRST..=KEY_LAST)
+                .chain(BTN_FIRST..=BTN_LAST)
+                .chain(GEAR_BUTTON_FIRST..GEAR_BUTTON_FIRST + gears)
+                .collect(),
+            axes: vec![
+                axis(ABS_STEERING, -AXIS_MAX),
+                axis(ABS_THROTTLE, 0),
+                axis(ABS_BRAKE, 0),
+                axis(ABS_CLUTCH, 0),
+            ],
+        }
+    }
+}
+
+// where the events of the virtual controller go, i.e. a uinput device or memory for tests
+pub trait OutputBackend: Send {
+    // creates the device, called once before the first emit
+    fn open(&mut self, capabilities: &Capabilities) -> Result<(), ServiceError>;
+
+    // events of one report, the last event is the synchronization event
+    fn emit(&mut self, events: &[EvdevEvent]) -> Result<(), ServiceError>;
+
+    // removes the device
+    fn close(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    capabilities: Option<Capabilities>,
+    reports: Vec<Vec<EvdevEvent>>,
+    closed: bool,
+    failing: bool,
+}
+
+// keeps every report in memory. clones share the reports, keep a clone to inspect them
+#[derive(Debug, Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBackend {
+    pub fn new() -> MockBackend {
+        MockBackend::default()
+    }
+
+    // None until the device has been opened
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.state.lock().unwrap().capabilities.clone()
+    }
+
+    // every emit, in order
+    pub fn reports(&self) -> Vec<Vec<EvdevEvent>> {
+        self.state.lock().unwrap().reports.clone()
+    }
+
+    // every event without the synchronization events
+    pub fn events(&self) -> Vec<EvdevEvent> {
+        self.reports()
+            .into_iter()
+            .flatten()
+            .filter(|event| event.type_ != EV_SYN)
+            .collect()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+
+    // while failing every emit returns an error and is not kept
+    pub fn set_failing(&self, failing: bool) {
+        self.state.lock().unwrap().failing = failing;
+    }
+}
+
+impl OutputBackend for MockBackend {
+    fn open(&mut self, capabilities: &Capabilities) -> Result<(), ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        state.capabilities = Some(capabilities.clone());
+        state.closed = false;
+        Ok(())
+    }
+
+    fn emit(&mut self, events: &[EvdevEvent]) -> Result<(), ServiceError> {
+        let mut state = self.state.lock().unwrap();
+        if state.failing {
+            return Err(ServiceError::Io(io::Error::from(io::ErrorKind::BrokenPipe)));
+        }
+        state.reports.push(events.to_vec());
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), ServiceError> {
+        self.state.lock().unwrap().closed = true;
+        Ok(())
+    }
+}
+
+// turns actions into the events of a virtual wheel, gamepad and keyboard. only changes are
+// emitted, every apply is one report
+pub struct VirtualController {
+    backend: Box<dyn OutputBackend>,
+    capabilities: Capabilities,
+    open: bool,
+    reported: Reported,
+}
+
+// what the device has been sent. apply changes a copy and keeps it only when the emit succeeded
+#[derive(Debug, Clone)]
+struct Reported {
+    // axis values by code
+    axes: Vec<(u16, i32)>,
+    gear: i32,
+    pressed: BTreeSet<u16>,
+}
+
+impl Reported {
+    // a new device starts centered, in neutral and without pressed keys
+    fn new(capabilities: &Capabilities) -> Reported {
+        Reported {
+            axes: capabilities
+                .axes
+                .iter()
+                .map(|axis| (axis.code, 0))
+                .collect(),
+            gear: 0,
+            pressed: BTreeSet::new(),
+        }
+    }
+
+    fn axis(&mut self, code: u16, value: f32, events: &mut Vec<EvdevEvent>) {
+        let value = (value * AXIS_MAX as f32).round() as i32;
+
+        if let Some((_, reported)) = self.axes.iter_mut().find(|(axis, _)| *axis == code) {
+            if *reported != value {
+                *reported = value;
+                events.push(event(EV_ABS, code, value));
+            }
+        }
+    }
+
+    fn shift(&mut self, gear: i32, events: &mut Vec<EvdevEvent>) {
+        if gear == self.gear {
+            return;
+        }
+
+        if let Some(code) = gear_button(self.gear) {
+            events.push(event(EV_KEY, code, 0));
+        }
+        if let Some(code) = gear_button(gear) {
+            events.push(event(EV_KEY, code, 1));
+        }
+        self.gear = gear;
+    }
+
+    fn key(&mut self, code: u16, pressed: bool, events: &mut Vec<EvdevEvent>) {
+        let changed = if pressed {
+            self.pressed.insert(code)
+        } else {
+            self.pressed.remove(&code)
+        };
+
+        if changed {
+            events.push(event(EV_KEY, code, pressed as i32));
+        }
+    }
+}
+
+impl VirtualController {
+    pub fn new(backend: Box<dyn OutputBackend>) -> VirtualController {
+        let capabilities = Capabilities::driving();
+        VirtualController {
+            backend,
+            reported: Reported::new(&capabilities),
+            capabilities,
+            open: false,
+        }
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    // creates the device, apply opens it when it is not open yet
+    pub fn open(&mut self) -> Result<(), ServiceError> {
+        if !self.open {
+            self.backend.open(&self.capabilities)?;
+            self.open = true;
+        }
+        Ok(())
+    }
+
+    // actions are applied in order, invalid actions fail the whole apply without emitting.
+    // when the device can not be opened or the emit fails nothing counts as reported
+    pub fn apply(&mut self, actions: &[Action]) -> Result<(), ServiceError> {
+        for action in actions {
+            self.validate(action)?;
+        }
+
+        let mut reported = self.reported.clone();
+        let mut events = Vec::new();
+        for action in actions {
+            match *action {
+                Action::Steering(value) => {
+                    reported.axis(ABS_STEERING, value.clamp(-1.0, 1.0), &mut events)
+                }
+                Action::Throttle(value) => {
+                    reported.axis(ABS_THROTTLE, value.clamp(0.0, 1.0), &mut events)
+                }
+                Action::Brake(value) => {
+                    reported.axis(ABS_BRAKE, value.clamp(0.0, 1.0), &mut events)
+                }
+                Action::Clutch(value) => {
+                    reported.axis(ABS_CLUTCH, value.clamp(0.0, 1.0), &mut events)
+                }
+                Action::Gear(gear) => reported.shift(gear, &mut events),
+                Action::Key { code, pressed } => reported.key(code, pressed, &mut events),
+            }
+        }
+
+        self.emit(events)?;
+        self.reported = reported;
+        Ok(())
+    }
+
+    // centers the steering, releases the pedals, every key and shifts to neutral
+    pub fn release_all(&mut self) -> Result<(), ServiceError> {
+        let mut actions = vec![
+            Action::Steering(0.0),
+            Action::Throttle(0.0),
+            Action::Brake(0.0),
+            Action::Clutch(0.0),
+            Action::Gear(0),
+        ];
+        actions.extend(self.reported.pressed.iter().map(|code| Action::Key {
+            code: *code,
+            pressed: false,
+        }));
+
+        self.apply(&actions)
+    }
+
+    // releases every input before the device is removed. the device is removed even when the
+    // release fails, the first error is returned
+    pub fn close(&mut self) -> Result<(), ServiceError> {
+        if self.open {
+            let released = self.release_all();
+            self.open = false;
+            // a new device starts without pressed inputs
+            self.reported = Reported::new(&self.capabilities);
+            let closed = self.backend.close();
+            released?;
+            closed?;
+        }
+        Ok(())
+    }
+
+    fn validate(&self, action: &Action) -> Result<(), ServiceError> {
+        let valid = match *action {
+            Action::Steering(value)
+            | Action::Throttle(value)
+            | Action::Brake(value)
+            | Action::Clutch(value) => value.is_finite(),
+            Action::Gear(gear) => (-REVERSE_GEARS..=FORWARD_GEARS).contains(&gear),
+            Action::Key { code, .. } => {
+                self.capabilities.keys.contains(&code) && gear_of(code).is_none()
+            }
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(ServiceError::InvalidAction(format!("{:?}", action)))
+        }
+    }
+
+    fn emit(&mut self, mut events: Vec<EvdevEvent>) -> Result<(), ServiceError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        self.open()?;
+        events.push(event(EV_SYN, SYN_REPORT, 0));
+        self.backend.emit(&events)
+    }
+}
+
+impl Drop for VirtualController {
+    fn drop(&mut self) {
+        // the device must not keep inputs pressed
+        let _ = self.close();
+    }
+}
+
+fn event(type_: u16, code: u16, value: i32) -> EvdevEvent {
+    EvdevEvent::new(CaptureClock::now(), type_, code, value)
+}
+
+fn gear_button(gear: i32) -> Option<u16> {
+    match gear {
+        0 => None,
+        gear if gear > 0 => Some(GEAR_BUTTON_FIRST + (gear - 1) as u16),
+        gear => Some(GEAR_BUTTON_FIRST + (FORWARD_GEARS - gear - 1) as u16),
+    }
+}
+
+fn gear_of(code: u16) -> Option<i32> {
+    (-REVERSE_GEARS..=FORWARD_GEARS).find(|gear| gear_button(*gear) == Some(code))
+}
@@ -0,0 +1,192 @@
+use super::{Capabilities, OutputBackend};
+use crate::userinput::{EvdevEvent, EVENT_SIZE, EV_ABS, EV_KEY, EV_SYN};
+use crate::{posix_get_last_error, ServiceError};
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_UINPUT_PATH: &str = "/dev/uinput";
+
+// ioctl requests of linux/uinput.h, asm-generic encoding (x86, arm)
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const UI_DEV_SETUP: u64 = 0x405c_5503;
+const UI_ABS_SETUP: u64 = 0x401c_5504;
+const UI_SET_EVBIT: u64 = 0x4004_5564;
+const UI_SET_KEYBIT: u64 = 0x4004_5565;
+const UI_SET_ABSBIT: u64 = 0x4004_5567;
+
+const BUS_VIRTUAL: u16 = 0x06;
+const NAME_SIZE: usize = 80;
+
+// struct uinput_setup
+#[repr(C)]
+struct UinputSetup {
+    bustype: u16,
+    vendor: u16,
+    product: u16,
+    version: u16,
+    name: [u8; NAME_SIZE],
+    ff_effects_max: u32,
+}
+
+// struct uinput_abs_setup, code followed by struct input_absinfo
+#[repr(C)]
+struct UinputAbsSetup {
+    code: u16,
+    value: i32,
+    minimum: i32,
+    maximum: i32,
+    fuzz: i32,
+    flat: i32,
+    resolution: i32,
+}
+
+// creates a virtual input device through /dev/uinput, the game sees it like a real controller.
+// requires write access to /dev/uinput, i.e. the input group or a udev rule
+pub struct UinputBackend {
+    path: PathBuf,
+    device: Option<File>,
+}
+
+impl UinputBackend {
+    pub fn new() -> UinputBackend {
+        UinputBackend::with_path(DEFAULT_UINPUT_PATH)
+    }
+
+    pub fn with_path<P: AsRef<Path>>(path: P) -> UinputBackend {
+        UinputBackend {
+            path: path.as_ref().to_path_buf(),
+            device: None,
+        }
+    }
+}
+
+impl Default for UinputBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn ioctl<T>(file: &File, request: u64, arg: T, debug: &str) -> Result<(), ServiceError> {
+    if unsafe { libc::ioctl(file.as_raw_fd(), request as _, arg) } == -1 {
+        return Err(posix_get_last_error(debug));
+    }
+    Ok(())
+}
+
+impl OutputBackend for UinputBackend {
+    fn open(&mut self, capabilities: &Capabilities) -> Result<(), ServiceError> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.path)?;
+
+        ioctl(
+            &file,
+            UI_SET_EVBIT,
+            EV_KEY as libc::c_int,
+            "ioctl UI_SET_EVBIT",
+        )?;
+        for key in capabilities.keys.iter() {
+            ioctl(
+                &file,
+                UI_SET_KEYBIT,
+                *key as libc::c_int,
+                "ioctl UI_SET_KEYBIT",
+            )?;
+        }
+
+        ioctl(
+            &file,
+            UI_SET_EVBIT,
+            EV_ABS as libc::c_int,
+            "ioctl UI_SET_EVBIT",
+        )?;
+        for axis in capabilities.axes.iter() {
+            ioctl(
+                &file,
+                UI_SET_ABSBIT,
+                axis.code as libc::c_int,
+                "ioctl UI_SET_ABSBIT",
+            )?;
+
+            let setup = UinputAbsSetup {
+                code: axis.code,
+                value: 0,
+                minimum: axis.min,
+                maximum: axis.max,
+                fuzz: 0,
+                flat: 0,
+                resolution: 0,
+            };
+            ioctl(
+                &file,
+                UI_ABS_SETUP,
+                &setup as *const UinputAbsSetup,
+                "ioctl UI_ABS_SETUP",
+            )?;
+        }
+
+        let mut setup = UinputSetup {
+            bustype: BUS_VIRTUAL,
+            vendor: 0,
+            product: 0,
+            version: 1,
+            name: [0; NAME_SIZE],
+            ff_effects_max: 0,
+        };
+        // keep the terminating nul
+        let name = capabilities.name.as_bytes();
+        let len = name.len().min(NAME_SIZE - 1);
+        setup.name[..len].copy_from_slice(&name[..len]);
+
+        ioctl(
+            &file,
+            UI_DEV_SETUP,
+            &setup as *const UinputSetup,
+            "ioctl UI_DEV_SETUP",
+        )?;
+        ioctl(
+            &file,
+            UI_DEV_CREATE,
+            0 as libc::c_int,
+            "ioctl UI_DEV_CREATE",
+        )?;
+
+        self.device = Some(file);
+        Ok(())
+    }
+
+    fn emit(&mut self, events: &[EvdevEvent]) -> Result<(), ServiceError> {
+        let device = match self.device.as_mut() {
+            Some(device) => device,
+            None => return Err(ServiceError::NotActive),
+        };
+
+        let mut bytes = Vec::with_capacity(events.len() * EVENT_SIZE);
+        for event in events {
+            bytes.extend_from_slice(&event.to_bytes());
+        }
+        debug_assert!(events.last().is_some_and(|event| event.type_ == EV_SYN));
+
+        device.write_all(&bytes)?;
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<(), ServiceError> {
+        // closing the file removes the device as well
+        if let Some(device) = self.device.take() {
+            ioctl(
+                &device,
+                UI_DEV_DESTROY,
+                0 as libc::c_int,
+                "ioctl UI_DEV_DESTROY",
+            )?;
+        }
+        Ok(())
+    }
+}
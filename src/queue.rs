@@ -0,0 +1,394 @@
+use crate::{Input, InputType};
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+// what a transmitter does when its lane of the queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backpressure {
+    // wait until the receiver made room, slows the producer down
+    Block,
+    // discard the oldest queued item to make room for the new one
+    DropOldest,
+    // discard the new item, the queued items are kept
+    DropNewest,
+    // only the newest item is kept, a new item replaces everything queued. capacity is ignored
+    KeepLatest,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueuePolicy {
+    pub capacity: usize,
+    pub backpressure: Backpressure,
+}
+
+impl QueuePolicy {
+    pub fn new(capacity: usize, backpressure: Backpressure) -> QueuePolicy {
+        QueuePolicy {
+            capacity: capacity.max(1),
+            backpressure,
+        }
+    }
+
+    pub fn block(capacity: usize) -> QueuePolicy {
+        QueuePolicy::new(capacity, Backpressure::Block)
+    }
+
+    pub fn drop_oldest(capacity: usize) -> QueuePolicy {
+        QueuePolicy::new(capacity, Backpressure::DropOldest)
+    }
+
+    pub fn drop_newest(capacity: usize) -> QueuePolicy {
+        QueuePolicy::new(capacity, Backpressure::DropNewest)
+    }
+
+    pub fn keep_latest() -> QueuePolicy {
+        QueuePolicy::new(1, Backpressure::KeepLatest)
+    }
+}
+
+// queue policy per input type, every input type gets its own lane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputQueueConfig {
+    pub user: QueuePolicy,
+    pub telemetry: QueuePolicy,
+    pub image: QueuePolicy,
+}
+
+impl InputQueueConfig {
+    pub fn policy(&self, input_type: InputType) -> QueuePolicy {
+        match input_type {
+            InputType::User => self.user,
+            InputType::Telemetry => self.telemetry,
+            InputType::Image => self.image,
+        }
+    }
+}
+
+impl Default for InputQueueConfig {
+    // telemetry frames the groups and must not be lost, the shared memory producer waits instead.
+    // user events are replayed into the controller state, a lost release would keep a key pressed:
+    // the evdev reader waits and the kernel reports the overflow with SYN_DROPPED.
+    // only the newest screenshot is useful, older ones are replaced
+    fn default() -> Self {
+        InputQueueConfig {
+            user: QueuePolicy::block(1024),
+            telemetry: QueuePolicy::block(4096),
+            image: QueuePolicy::keep_latest(),
+        }
+    }
+}
+
+pub type InputSender = QueueSender<Box<dyn Input + Send>>;
+pub type InputReceiver = QueueReceiver<Box<dyn Input + Send>>;
+
+// synchronized groups, passed from synchronization to the outputs
+pub type GroupSender = QueueSender<Vec<Box<dyn Input + Send>>>;
+pub type GroupReceiver = QueueReceiver<Vec<Box<dyn Input + Send>>>;
+
+fn input_lane(input_type: InputType) -> usize {
+    match input_type {
+        InputType::User => 0,
+        InputType::Telemetry => 1,
+        InputType::Image => 2,
+    }
+}
+
+// signature has to match the queue item type
+#[allow(clippy::borrowed_box)]
+fn classify_input(input: &Box<dyn Input + Send>) -> usize {
+    input_lane(input.input_type())
+}
+
+fn single_lane<T>(_: &T) -> usize {
+    0
+}
+
+// bounded queue for inputs with one lane per input type, inputs are received in the order they were sent
+pub fn input_queue(config: &InputQueueConfig) -> (InputSender, InputReceiver) {
+    // lane order matches input_lane
+    let policies = vec![config.user, config.telemetry, config.image];
+    queue(policies, classify_input)
+}
+
+// bounded queue with a single lane
+pub fn bounded<T>(policy: QueuePolicy) -> (QueueSender<T>, QueueReceiver<T>) {
+    queue(vec![policy], single_lane)
+}
+
+// never blocks or drops, only use when the receiver is known to keep up
+pub fn unbounded<T>() -> (QueueSender<T>, QueueReceiver<T>) {
+    bounded(QueuePolicy::block(usize::MAX))
+}
+
+fn queue<T>(
+    policies: Vec<QueuePolicy>,
+    classify: fn(&T) -> usize,
+) -> (QueueSender<T>, QueueReceiver<T>) {
+    let counters = DropCounters {
+        lanes: Arc::new(policies.iter().map(|_| AtomicU64::new(0)).collect()),
+    };
+
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            lanes: policies
+                .into_iter()
+                .map(|policy| Lane {
+                    policy,
+                    items: VecDeque::new(),
+                })
+                .collect(),
+            sequence: 0,
+            transmitters: 1,
+            receiver_alive: true,
+        }),
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+        classify,
+        counters,
+    });
+
+    (
+        QueueSender {
+            shared: shared.clone(),
+        },
+        QueueReceiver { shared },
+    )
+}
+
+// number of items discarded by the queue, per lane. clones share the counters
+#[derive(Debug, Clone)]
+pub struct DropCounters {
+    lanes: Arc<Vec<AtomicU64>>,
+}
+
+impl DropCounters {
+    pub fn lane(&self, lane: usize) -> u64 {
+        self.lanes
+            .get(lane)
+            .map(|count| count.load(Ordering::Relaxed))
+            .unwrap_or(0)
+    }
+
+    // only meaningful for queues created with input_queue
+    pub fn input(&self, input_type: InputType) -> u64 {
+        self.lane(input_lane(input_type))
+    }
+
+    pub fn total(&self) -> u64 {
+        self.lanes
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    fn add(&self, lane: usize, count: u64) {
+        self.lanes[lane].fetch_add(count, Ordering::Relaxed);
+    }
+}
+
+struct Lane<T> {
+    policy: QueuePolicy,
+    // sequence number keeps the send order across lanes
+    items: VecDeque<(u64, T)>,
+}
+
+struct State<T> {
+    lanes: Vec<Lane<T>>,
+    sequence: u64,
+    transmitters: usize,
+    receiver_alive: bool,
+}
+
+impl<T> State<T> {
+    fn pop(&mut self) -> Option<T> {
+        let lane = self
+            .lanes
+            .iter_mut()
+            .filter(|lane| !lane.items.is_empty())
+            .min_by_key(|lane| lane.items.front().unwrap().0)?;
+        lane.items.pop_front().map(|(_, item)| item)
+    }
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    classify: fn(&T) -> usize,
+    counters: DropCounters,
+}
+
+// transmitting end of a bounded queue, same send signature as mpsc::Sender
+pub struct QueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> QueueSender<T> {
+    // Err when the receiver has been dropped. an item discarded by the lane policy is not an error
+    pub fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let lane = (self.shared.classify)(&item);
+        let mut state = self.shared.state.lock().unwrap();
+
+        if !state.receiver_alive {
+            return Err(SendError(item));
+        }
+
+        let policy = state.lanes[lane].policy;
+        let full = |state: &State<T>| state.lanes[lane].items.len() >= policy.capacity;
+
+        match policy.backpressure {
+            Backpressure::Block => {
+                while full(&state) && state.receiver_alive {
+                    state = self.shared.not_full.wait(state).unwrap();
+                }
+                if !state.receiver_alive {
+                    return Err(SendError(item));
+                }
+            }
+            Backpressure::DropOldest => {
+                if full(&state) {
+                    state.lanes[lane].items.pop_front();
+                    self.shared.counters.add(lane, 1);
+                }
+            }
+            Backpressure::DropNewest => {
+                if full(&state) {
+                    self.shared.counters.add(lane, 1);
+                    return Ok(());
+                }
+            }
+            Backpressure::KeepLatest => {
+                let replaced = state.lanes[lane].items.len() as u64;
+                state.lanes[lane].items.clear();
+                if replaced > 0 {
+                    self.shared.counters.add(lane, replaced);
+                }
+            }
+        }
+
+        let sequence = state.sequence;
+        state.sequence += 1;
+        state.lanes[lane].items.push_back((sequence, item));
+        self.shared.not_empty.notify_one();
+        Ok(())
+    }
+
+    pub fn dropped(&self) -> DropCounters {
+        self.shared.counters.clone()
+    }
+}
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().transmitters += 1;
+        QueueSender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for QueueSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.transmitters -= 1;
+        if state.transmitters == 0 {
+            self.shared.not_empty.notify_all();
+        }
+    }
+}
+
+// receiving end of a bounded queue, same receive methods as mpsc::Receiver
+pub struct QueueReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> QueueReceiver<T> {
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.pop() {
+            Some(item) => {
+                self.shared.not_full.notify_all();
+                Ok(item)
+            }
+            None if state.transmitters == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.state.lock().unwrap();
+
+        loop {
+            if let Some(item) = state.pop() {
+                self.shared.not_full.notify_all();
+                return Ok(item);
+            }
+            if state.transmitters == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    // blocks until an item arrives, Err once every transmitter has been dropped and the queue is empty
+    pub fn recv(&self) -> Result<T, RecvError> {
+        let mut state = self.shared.state.lock().unwrap();
+
+        loop {
+            if let Some(item) = state.pop() {
+                self.shared.not_full.notify_all();
+                return Ok(item);
+            }
+            if state.transmitters == 0 {
+                return Err(RecvError);
+            }
+            state = self.shared.not_empty.wait(state).unwrap();
+        }
+    }
+
+    // blocking iterator, ends once every transmitter has been dropped
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    // returns the queued items without blocking
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+
+    pub fn len(&self) -> usize {
+        let state = self.shared.state.lock().unwrap();
+        state.lanes.iter().map(|lane| lane.items.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dropped(&self) -> DropCounters {
+        self.shared.counters.clone()
+    }
+}
+
+impl<T> Drop for QueueReceiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().receiver_alive = false;
+        // blocked transmitters return an error
+        self.shared.not_full.notify_all();
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+// delay before a restart, doubles with every attempt up to max
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Backoff {
+        Backoff { initial, max }
+    }
+
+    // delay before restart number attempt, the first restart is attempt 0
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.min(31));
+        std::cmp::min(self.initial.saturating_mul(factor), self.max)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff::new(Duration::from_millis(100), Duration::from_secs(10))
+    }
+}
+
+// what Services does when the thread of a service ends without stop being called.
+// max_retries counts consecutive restarts, unlimited when None. a restarted service that stays
+// running for backoff.max counts from zero again
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum RestartPolicy {
+    #[default]
+    Never,
+    // restart when the thread ended with an error
+    OnFailure {
+        backoff: Backoff,
+        max_retries: Option<u32>,
+    },
+    // restart whenever the thread ended, i.e. the telemetry producer stopped
+    Always {
+        backoff: Backoff,
+        max_retries: Option<u32>,
+    },
+}
+
+impl RestartPolicy {
+    // delay before the next restart, None when the service should stay down
+    pub fn next_restart(&self, failed: bool, restarts: u32) -> Option<Duration> {
+        let (backoff, max_retries) = match self {
+            RestartPolicy::Never => return None,
+            RestartPolicy::OnFailure { .. } if !failed => return None,
+            RestartPolicy::OnFailure {
+                backoff,
+                max_retries,
+            }
+            | RestartPolicy::Always {
+                backoff,
+                max_retries,
+            } => (backoff, max_retries),
+        };
+
+        match max_retries {
+            Some(max) if restarts >= *max => None,
+            _ => Some(backoff.delay(restarts)),
+        }
+    }
+
+    // how long a restarted service has to keep running before the restarts are reset
+    pub fn stable_period(&self) -> Option<Duration> {
+        match self {
+            RestartPolicy::Never => None,
+            RestartPolicy::OnFailure { backoff, .. } | RestartPolicy::Always { backoff, .. } => {
+                Some(backoff.max)
+            }
+        }
+    }
+}
@@ -0,0 +1,26 @@
+// portable formats for inputs, json for data packets and png/jpeg for images
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageEncoding {
+    Png,
+    // quality between 1 and 100
+    Jpeg(u8),
+}
+
+impl ImageEncoding {
+    pub fn extension(&self) -> &str {
+        match self {
+            ImageEncoding::Png => "png",
+            ImageEncoding::Jpeg(_) => "jpg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Serialized {
+    Json(serde_json::Value),
+    Image {
+        encoding: ImageEncoding,
+        data: Vec<u8>,
+    },
+}
@@ -1,6 +1,13 @@
-use crate::telemetry::EventGame;
-use std::collections::HashMap;
+#[cfg(windows)]
+use crate::screencapture::CaptureError;
+use crate::telemetry::{EventGame, ValidationError};
+use crate::{CaptureStamp, DependencyGraph, ImageEncoding, RestartPolicy, Serialized};
+use std::any::Any;
+use std::error::Error;
 use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::time::Instant;
 
 //responsible for starting and stoping services
 // data is passed between service via message passing channels
@@ -12,10 +19,129 @@ pub enum ServiceError {
     AlreadyActive,
     //errpr when, called to stop a service that is not active
     NotActive,
-    //windows api call failed,
-    WindowsGetLastError(i32),
     // transmitter (for data passing between thread) has not been set for service
     TransmitterNotSet,
+    // service could not be started, source contains the reason
+    StartFailed {
+        service: ServiceType,
+        source: Box<ServiceError>,
+    },
+    // shared memory, semaphore, mutex or event call failed. source contains the windows error code or errno
+    Ipc {
+        call: String,
+        source: io::Error,
+    },
+    // telemetry packet failed validation
+    InvalidPacket(ValidationError),
+    // screen capture api failed
+    #[cfg(windows)]
+    Capture(CaptureError),
+    // service thread ended with an error, source contains the reason. shared with Services::failures
+    Failed {
+        service: ServiceType,
+        source: Arc<ServiceError>,
+    },
+    // adding the dependency would create a cycle, contains the cycle
+    DependencyCycle(Vec<ServiceType>),
+    // a dependency of the service has been added but is not running
+    DependencyNotRunning {
+        service: ServiceType,
+        dependency: ServiceType,
+    },
+    // lifecycle transition not allowed from the current state
+    InvalidTransition {
+        service: ServiceType,
+        from: ServiceState,
+        to: ServiceState,
+    },
+    // service thread panicked, contains the panic message
+    Panicked(String),
+    // the receiving or transmitting end of a channel has hung up
+    Disconnected(String),
+    // reading or writing a file failed
+    Io(io::Error),
+    // input could not be converted to a portable format
+    Serialization(Box<dyn Error + Send + Sync>),
+    // action is not supported by the output device, contains the action
+    InvalidAction(String),
+    // service configuration is out of range, contains the reason
+    InvalidConfig(String),
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::AlreadyActive => write!(f, "service is already active"),
+            ServiceError::NotActive => write!(f, "service is not active"),
+            ServiceError::TransmitterNotSet => write!(f, "transmitter has not been set"),
+            ServiceError::StartFailed { service, .. } => {
+                write!(f, "failed to start service {:?}", service)
+            }
+            ServiceError::Failed { service, .. } => write!(f, "service {:?} failed", service),
+            ServiceError::DependencyCycle(cycle) => {
+                write!(f, "service dependencies form a cycle {:?}", cycle)
+            }
+            ServiceError::DependencyNotRunning {
+                service,
+                dependency,
+            } => write!(
+                f,
+                "service {:?} depends on {:?} which is not running",
+                service, dependency
+            ),
+            ServiceError::InvalidTransition { service, from, to } => write!(
+                f,
+                "service {:?} cannot go from {:?} to {:?}",
+                service, from, to
+            ),
+            ServiceError::Panicked(message) => write!(f, "service thread panicked: {}", message),
+            ServiceError::Ipc { call, .. } => write!(f, "ipc call {} failed", call),
+            ServiceError::InvalidPacket(_) => write!(f, "invalid telemetry packet"),
+            #[cfg(windows)]
+            ServiceError::Capture(_) => write!(f, "screen capture failed"),
+            ServiceError::Disconnected(channel) => write!(f, "{} channel disconnected", channel),
+            ServiceError::Io(_) => write!(f, "i/o error"),
+            ServiceError::Serialization(_) => write!(f, "serialization failed"),
+            ServiceError::InvalidAction(action) => write!(f, "invalid action {}", action),
+            ServiceError::InvalidConfig(reason) => write!(f, "invalid configuration, {}", reason),
+        }
+    }
+}
+
+// the cause is returned by source, not repeated in the message
+impl Error for ServiceError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ServiceError::StartFailed { source, .. } => Some(source.as_ref()),
+            ServiceError::Failed { source, .. } => Some(source.as_ref()),
+            ServiceError::Ipc { source, .. } => Some(source),
+            ServiceError::InvalidPacket(err) => Some(err),
+            #[cfg(windows)]
+            ServiceError::Capture(err) => Some(err),
+            ServiceError::Io(err) => Some(err),
+            ServiceError::Serialization(err) => Some(err.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ServiceError {
+    fn from(err: io::Error) -> Self {
+        ServiceError::Io(err)
+    }
+}
+
+impl From<ValidationError> for ServiceError {
+    fn from(err: ValidationError) -> Self {
+        ServiceError::InvalidPacket(err)
+    }
+}
+
+#[cfg(windows)]
+impl From<CaptureError> for ServiceError {
+    fn from(err: CaptureError) -> Self {
+        ServiceError::Capture(err)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
@@ -24,122 +150,628 @@ pub enum ServiceType {
     TelemetryInput,     // could be different games, for now only ets2_telemetry
     ScreenCaptureInput, // screenshot
     SynchronizeInputs,  // use to synchronize and groupify the inputs
+    ResampleInputs,     // synchronized groups on a fixed clock
+    RecordInputs,       // persist inputs to disk for replay
+    DatasetOutput,      // persist synchronized groups as a dataset
+    ControlOutput,      // apply policy actions to the virtual controller
                         // use display the user and telemetry outputs and image
 }
 pub trait Process {
     fn start(&mut self) -> Result<(), ServiceError>;
     fn stop(&mut self) -> Result<(), ServiceError>;
-    fn join(&mut self);
+    // blocks until the service thread ended, returns the result of the thread
+    fn join(&mut self) -> Result<(), ServiceError>;
+    // true when the service thread ended and has not been joined yet. required, poll_services only
+    // restarts services that report their end
+    fn is_finished(&self) -> bool;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum InputType {
     User,
     Telemetry,
     Image,
 }
 
+// same fields for every input, fields that do not apply to an input are 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputHeader {
+    pub source: InputType,
+    // assigned by the producer: packet id for telemetry, capture sequence for other inputs
+    pub sequence: u64,
+    pub event: EventGame,
+    // raw event type of the game
+    pub event_code: u32,
+    // game time in microseconds
+    pub game_time: u64,
+    // set by the input source at acquisition
+    pub capture: CaptureStamp,
+    pub payload_size: u32,
+}
+
+impl InputHeader {
+    pub fn new(source: InputType) -> InputHeader {
+        InputHeader {
+            source,
+            sequence: 0,
+            event: EventGame::NA,
+            event_code: 0,
+            game_time: 0,
+            capture: CaptureStamp::default(),
+            payload_size: 0,
+        }
+    }
+}
+
 // input method type could be shared memory, pipe ,
 pub trait Input {
-    fn input_type(&self) -> InputType;
-    fn event_type(&self) -> EventGame;
-    fn header(&self) -> (u64, u32, u64, u32);
+    fn header(&self) -> InputHeader;
+
+    fn input_type(&self) -> InputType {
+        self.header().source
+    }
+
+    fn event_type(&self) -> EventGame {
+        self.header().event
+    }
+
+    fn capture(&self) -> CaptureStamp {
+        self.header().capture
+    }
 
-    // implement packet size
+    // use to downcast to the concrete input, i.e. Ets2Payload::from_input
+    fn as_any(&self) -> &dyn Any;
 
-    // serialize input to a portable format (i.e json/xml or jpeg/png)
+    // raw bytes of the input data, excluding the header
+    fn payload(&self) -> &[u8];
+
+    // serialize input to a portable format, json for data and the image_encoding for images
+    fn serialize(&self, image_encoding: ImageEncoding) -> Result<Serialized, ServiceError>;
 
     // serialize to compressed format for miminal on disk size
 }
 
+// lifecycle of a service managed by Services
+//
+// Registered -> Starting
+// Starting -> Running | Failed
+// Running  -> Stopping | Stopped (thread ended on its own) | Failed
+// Stopping -> Stopped | Failed
+// Stopped  -> Starting
+// Failed   -> Starting
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ServiceState {
+    // added with register_service, not started yet
+    Registered,
+    Starting,
+    Running,
+    Stopping,
+    Stopped,
+    Failed,
+}
+
+impl ServiceState {
+    pub fn can_transition_to(&self, next: ServiceState) -> bool {
+        use ServiceState::*;
+
+        matches!(
+            (self, next),
+            (Registered, Starting)
+                | (Starting, Running)
+                | (Starting, Failed)
+                | (Running, Stopping)
+                | (Running, Stopped)
+                | (Running, Failed)
+                | (Stopping, Stopped)
+                | (Stopping, Failed)
+                | (Stopped, Starting)
+                | (Failed, Starting)
+        )
+    }
+
+    // the service thread may still be running
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            ServiceState::Starting | ServiceState::Running | ServiceState::Stopping
+        )
+    }
+}
+
+// creates a new process every time the service is restarted
+type ProcessFactory = Box<dyn FnMut() -> Result<Box<dyn Process>, ServiceError>>;
+
+struct Restart {
+    policy: RestartPolicy,
+    factory: ProcessFactory,
+    // consecutive restarts, see RestartPolicy::stable_period
+    restarts: u32,
+    // when the next restart is due
+    pending: Option<Instant>,
+    // when the latest restart started the service
+    restarted: Option<Instant>,
+}
+
+impl Restart {
+    fn schedule(&mut self, service_type: ServiceType, failed: bool) {
+        self.pending = self
+            .policy
+            .next_restart(failed, self.restarts)
+            .map(|delay| {
+                println!("restarting service {:?} in {:?}", service_type, delay);
+                Instant::now() + delay
+            });
+    }
+
+    // called while the restarted service is running
+    fn reset_if_stable(&mut self, now: Instant) {
+        let stable = match (self.restarted, self.policy.stable_period()) {
+            (Some(restarted), Some(period)) => now.saturating_duration_since(restarted) >= period,
+            _ => false,
+        };
+
+        if stable {
+            self.restarts = 0;
+            self.restarted = None;
+        }
+    }
+}
+
+struct ServiceEntry {
+    service_type: ServiceType,
+    process: Box<dyn Process>,
+    state: ServiceState,
+    restart: Option<Restart>,
+}
+
+impl ServiceEntry {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        println!("Starting serivce {:?}", self.service_type);
+        if let Err(err) = self.process.start() {
+            self.transition(ServiceState::Failed)?;
+            return Err(ServiceError::StartFailed {
+                service: self.service_type,
+                source: Box::new(err),
+            });
+        }
+
+        self.transition(ServiceState::Running)
+    }
+
+    // joins the ended thread, schedules a restart when the policy allows it
+    fn finished(&mut self) -> Result<(), ServiceError> {
+        let result = match self.process.join() {
+            Ok(_) => self.transition(ServiceState::Stopped),
+            Err(err) => self.transition(ServiceState::Failed).and(Err(err)),
+        };
+
+        let service_type = self.service_type;
+        if let Some(restart) = self.restart.as_mut() {
+            restart.schedule(service_type, result.is_err());
+        }
+
+        result
+    }
+
+    // creates and starts a new process when a restart is due
+    fn restart_if_due(&mut self, now: Instant) -> Option<Result<(), ServiceError>> {
+        let restart = self.restart.as_mut()?;
+        if restart.pending? > now {
+            return None;
+        }
+
+        restart.pending = None;
+        restart.restarts += 1;
+        let process = (restart.factory)();
+
+        let result = self
+            .transition(ServiceState::Starting)
+            .and_then(|_| match process {
+                Ok(process) => {
+                    self.process = process;
+                    self.start()
+                }
+                Err(err) => self.transition(ServiceState::Failed).and(Err(err)),
+            });
+
+        let service_type = self.service_type;
+        if let Some(restart) = self.restart.as_mut() {
+            if result.is_err() {
+                restart.schedule(service_type, true);
+            } else {
+                restart.restarted = Some(Instant::now());
+            }
+        }
+
+        Some(result)
+    }
+
+    fn transition(&mut self, next: ServiceState) -> Result<(), ServiceError> {
+        if !self.state.can_transition_to(next) {
+            return Err(ServiceError::InvalidTransition {
+                service: self.service_type,
+                from: self.state,
+                to: next,
+            });
+        }
+
+        self.state = next;
+        Ok(())
+    }
+}
+
 //every services has a process trait, to stop and start the specific services
 // all input services
 pub struct Services {
-    // hashmap to keep all  service objects (contain thread handle)
-    //services: HashMap<ServiceType, Box<dyn Process>>,
-    services_ord: Vec<(ServiceType, Box<dyn Process>)>,
+    // services in start order, stopped and failed services are kept so their state can be queried
+    services_ord: Vec<ServiceEntry>,
+    // services whose thread ended with an error, in order of detection
+    failures: Vec<(ServiceType, Arc<ServiceError>)>,
+    graph: DependencyGraph,
 }
 
 impl Services {
     // create services objects to manage all services
     pub fn new() -> Services {
+        Services::with_dependencies(DependencyGraph::default())
+    }
+
+    pub fn with_dependencies(graph: DependencyGraph) -> Services {
         Services {
-            //services: HashMap::<ServiceType, Box<dyn Process>>::new(),
-            services_ord: Vec::<(ServiceType, Box<dyn Process>)>::new(),
+            services_ord: Vec::new(),
+            failures: Vec::new(),
+            graph,
         }
     }
-    //service needs to impliment process thread
+    //service needs to impliment process thread. replaces a stopped or failed service of the same type
     pub fn add_service(
         &mut self,
         service_type: ServiceType,
-        mut process: Box<dyn Process>,
+        process: Box<dyn Process>,
+    ) -> Result<(), ServiceError> {
+        self.insert(service_type, process, None)
+    }
+
+    // the factory creates the process for the first start and for every restart.
+    // restarts are done by poll_services
+    pub fn add_service_with_restart<F>(
+        &mut self,
+        service_type: ServiceType,
+        policy: RestartPolicy,
+        mut factory: F,
+    ) -> Result<(), ServiceError>
+    where
+        F: FnMut() -> Result<Box<dyn Process>, ServiceError> + 'static,
+    {
+        let process = factory()?;
+        let restart = Restart {
+            policy,
+            factory: Box::new(factory),
+            restarts: 0,
+            pending: None,
+            restarted: None,
+        };
+
+        self.insert(service_type, process, Some(restart))
+    }
+
+    // adds the service without starting it, see start_all_services
+    pub fn register_service(
+        &mut self,
+        service_type: ServiceType,
+        process: Box<dyn Process>,
     ) -> Result<(), ServiceError> {
-        println!("Starting serivce {:?}", service_type);
-        process.start()?;
-        //self.services.insert(service_type, process);
-        self.services_ord.push((service_type, process));
+        if let Some(i) = self.position(service_type) {
+            if self.services_ord[i].state.is_active() {
+                return Err(ServiceError::AlreadyActive);
+            }
+            self.services_ord.remove(i);
+        }
 
+        self.services_ord.push(ServiceEntry {
+            service_type,
+            process,
+            state: ServiceState::Registered,
+            restart: None,
+        });
         Ok(())
     }
 
-    fn get_service(&mut self, service_type: ServiceType) -> Option<Box<dyn Process>> {
-        let res = self
+    // starts the registered services, dependencies before dependents.
+    // stops at the first service that fails to start
+    pub fn start_all_services(&mut self) -> Result<(), ServiceError> {
+        let registered: Vec<ServiceType> = self
             .services_ord
             .iter()
-            .position(|&(service_t, _)| service_t == service_type);
+            .filter(|entry| entry.state == ServiceState::Registered)
+            .map(|entry| entry.service_type)
+            .collect();
+
+        for service_type in self.graph.start_order(&registered) {
+            let i = self.position(service_type).unwrap();
+            self.services_ord[i].transition(ServiceState::Starting)?;
+            self.services_ord[i].start()?;
+        }
+
+        Ok(())
+    }
+
+    // dependency of the service must be running before the service starts
+    pub fn declare_dependency(
+        &mut self,
+        service: ServiceType,
+        depends_on: ServiceType,
+    ) -> Result<(), ServiceError> {
+        self.graph.add(service, depends_on)
+    }
+
+    // the service receives the output of depends_on, see declare_dependency
+    pub fn declare_receiver(
+        &mut self,
+        service: ServiceType,
+        depends_on: ServiceType,
+    ) -> Result<(), ServiceError> {
+        self.graph.add_receiver(service, depends_on)
+    }
+
+    pub fn dependency_graph(&self) -> &DependencyGraph {
+        &self.graph
+    }
+
+    fn insert(
+        &mut self,
+        service_type: ServiceType,
+        process: Box<dyn Process>,
+        restart: Option<Restart>,
+    ) -> Result<(), ServiceError> {
+        // dependencies that have been added have to be running
+        for dependency in self.graph.dependencies(service_type) {
+            match self.status(dependency) {
+                Some(state) if !state.is_active() => {
+                    return Err(ServiceError::DependencyNotRunning {
+                        service: service_type,
+                        dependency,
+                    })
+                }
+                _ => (),
+            }
+        }
+
+        let entry = match self.position(service_type) {
+            Some(i) => {
+                let entry = &mut self.services_ord[i];
+                if entry.state.is_active() {
+                    return Err(ServiceError::AlreadyActive);
+                }
+                entry.transition(ServiceState::Starting)?;
+                entry.process = process;
+                entry.restart = restart;
+                entry
+            }
+            None => {
+                self.services_ord.push(ServiceEntry {
+                    service_type,
+                    process,
+                    state: ServiceState::Starting,
+                    restart,
+                });
+                self.services_ord.last_mut().unwrap()
+            }
+        };
+
+        entry.start()
+    }
+
+    fn position(&self, service_type: ServiceType) -> Option<usize> {
+        self.services_ord
+            .iter()
+            .position(|entry| entry.service_type == service_type)
+    }
 
-        if res.is_none() {
-            None
-        } else {
-            Some(self.services_ord.remove(res.unwrap()).1)
+    // running service, NotActive when the service is not running
+    fn running(&mut self, service_type: ServiceType) -> Result<&mut ServiceEntry, ServiceError> {
+        match self.position(service_type) {
+            Some(i) if self.services_ord[i].state == ServiceState::Running => {
+                Ok(&mut self.services_ord[i])
+            }
+            _ => Err(ServiceError::NotActive),
         }
     }
+
+    // state of the service, None when the service was never added
+    pub fn status(&self, service_type: ServiceType) -> Option<ServiceState> {
+        self.position(service_type)
+            .map(|i| self.services_ord[i].state)
+    }
+
+    // every service with its state, in start order
+    pub fn list(&self) -> Vec<(ServiceType, ServiceState)> {
+        self.services_ord
+            .iter()
+            .map(|entry| (entry.service_type, entry.state))
+            .collect()
+    }
+
     //stops a running service else nothing
     pub fn stop_service(&mut self, service_type: ServiceType) -> Result<(), ServiceError> {
-        // if !self.services.contains_key(&service_type) {
-        //     return Err(ServiceError::NotActive);
-        // }
+        // a stopped service is not restarted
+        if let Some(i) = self.position(service_type) {
+            if let Some(restart) = self.services_ord[i].restart.as_mut() {
+                if restart.pending.take().is_some() {
+                    return Ok(());
+                }
+            }
+        }
 
-        let service = self.get_service(service_type);
-        if service.is_none() {
-            return Err(ServiceError::NotActive);
+        self.running(service_type)?;
+
+        // services sending to this service are stopped first, services receiving its output after
+        let mut stopping = vec![service_type];
+        let mut i = 0;
+        while i < stopping.len() {
+            for dependent in self.graph.dependents(stopping[i]) {
+                if !stopping.contains(&dependent) {
+                    stopping.push(dependent);
+                }
+            }
+            i += 1;
         }
 
-        // let mut p = self.services.remove(&service_type).unwrap();
-        let mut p = service.unwrap();
-        p.stop()?;
+        self.stop_in_order(&stopping)
+    }
 
-        p.join();
-        println!("service stopped {:?}", service_type);
-        Ok(())
+    // stops the running services, senders before the services they send to. every service is
+    // stopped even if one fails, returns the first failure
+    fn stop_in_order(&mut self, services: &[ServiceType]) -> Result<(), ServiceError> {
+        let mut result = Ok(());
+
+        for service_type in self.graph.stop_order(services) {
+            let entry = match self.position(service_type) {
+                Some(i) => &mut self.services_ord[i],
+                None => continue,
+            };
+            if let Some(restart) = entry.restart.as_mut() {
+                restart.pending = None;
+            }
+            if entry.state != ServiceState::Running {
+                continue;
+            }
+
+            let stopped = Self::stop_entry(entry);
+            let stopped = self.record(service_type, stopped);
+
+            if result.is_ok() {
+                result = stopped;
+            }
+        }
+
+        result
+    }
+
+    // stops and joins, the entry ends up stopped or failed
+    fn stop_entry(entry: &mut ServiceEntry) -> Result<(), ServiceError> {
+        entry.transition(ServiceState::Stopping)?;
+
+        match entry.process.stop().and_then(|_| entry.process.join()) {
+            Ok(_) => {
+                println!("service stopped {:?}", entry.service_type);
+                entry.transition(ServiceState::Stopped)
+            }
+            Err(err) => {
+                entry.transition(ServiceState::Failed)?;
+                Err(err)
+            }
+        }
+    }
+
+    // keeps the failure and returns it wrapped with the service type
+    fn record(
+        &mut self,
+        service_type: ServiceType,
+        result: Result<(), ServiceError>,
+    ) -> Result<(), ServiceError> {
+        result.map_err(|err| {
+            println!("service {:?} failed: {}", service_type, err);
+            let err = Arc::new(err);
+            self.failures.push((service_type, Arc::clone(&err)));
+            ServiceError::Failed {
+                service: service_type,
+                source: err,
+            }
+        })
     }
 
-    //stop all services
+    //stop all running services, senders before the services they send to. every service is stopped even if one fails.
+    // returns the first failure
     pub fn stop_all_services(&mut self) -> Result<(), ServiceError> {
-        for (k, v) in self.services_ord.iter_mut().rev() {
-            v.stop()?;
+        let services: Vec<ServiceType> = self
+            .services_ord
+            .iter()
+            .map(|entry| entry.service_type)
+            .collect();
+
+        self.stop_in_order(&services)
+    }
+
+    // joins services whose thread has ended and restarts services whose restart is due.
+    // returns the services that ended or failed to restart with an error, the error is available through failure()
+    pub fn poll_services(&mut self) -> Vec<ServiceType> {
+        let mut failed = Vec::new();
+        let now = Instant::now();
+
+        for i in 0..self.services_ord.len() {
+            let entry = &mut self.services_ord[i];
+            let service_type = entry.service_type;
 
-            v.join();
-            println!("service stopped {:?}", k);
+            let running = entry.state == ServiceState::Running;
+            let result = if running && entry.process.is_finished() {
+                entry.finished()
+            } else if running {
+                if let Some(restart) = entry.restart.as_mut() {
+                    restart.reset_if_stable(now);
+                }
+                continue;
+            } else if let Some(result) = entry.restart_if_due(now) {
+                result
+            } else {
+                continue;
+            };
+
+            if result.is_err() {
+                failed.push(service_type);
+            }
+            // already returned through failed
+            let _ = self.record(service_type, result);
         }
 
-        Ok(())
+        failed
+    }
+
+    // number of consecutive restarts done by poll_services, None when the service has no restart
+    // policy. reset once a restarted service stayed running for RestartPolicy::stable_period
+    pub fn restarts(&self, service_type: ServiceType) -> Option<u32> {
+        let i = self.position(service_type)?;
+        self.services_ord[i]
+            .restart
+            .as_ref()
+            .map(|restart| restart.restarts)
+    }
+
+    // true when poll_services will restart the service
+    pub fn restart_pending(&self, service_type: ServiceType) -> bool {
+        self.position(service_type)
+            .and_then(|i| self.services_ord[i].restart.as_ref())
+            .is_some_and(|restart| restart.pending.is_some())
+    }
+
+    // most recent failure of the service
+    pub fn failure(&self, service_type: ServiceType) -> Option<&ServiceError> {
+        self.failures
+            .iter()
+            .rev()
+            .find(|(service_t, _)| *service_t == service_type)
+            .map(|(_, err)| err.as_ref())
+    }
+
+    // every failure of a service thread, oldest first
+    pub fn failures(&self) -> Vec<(ServiceType, &ServiceError)> {
+        self.failures
+            .iter()
+            .map(|(service_type, err)| (*service_type, err.as_ref()))
+            .collect()
     }
 
     //block until telemetry service is done
     pub fn block_until_telemetry_finished(&mut self) -> Result<(), ServiceError> {
-        // if !self.services.contains_key(&ServiceType::TelemetryInput) {
-        //     return Err(ServiceError::NotActive);
-        // }
+        let entry = self.running(ServiceType::TelemetryInput)?;
+        let result = entry.finished();
 
-        let service = self.get_service(ServiceType::TelemetryInput);
-        if service.is_none() {
-            return Err(ServiceError::NotActive);
-        }
-        let mut service_handle = service.unwrap(); //self.services.remove(&ServiceType::TelemetryInput).unwrap();
-
-        service_handle.join();
+        self.record(ServiceType::TelemetryInput, result)
+    }
+}
 
-        Ok(())
+impl Default for Services {
+    fn default() -> Self {
+        Self::new()
     }
 }
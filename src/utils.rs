@@ -1,5 +1,7 @@
 use crate::ServiceError;
+#[cfg(windows)]
 use bindings::windows::win32::debug::GetLastError;
+use std::thread::JoinHandle;
 
 pub fn str_to_wstring(name: &str) -> Vec<u16> {
     let mut wstring: Vec<u16> = String::from(name).encode_utf16().collect();
@@ -7,14 +9,59 @@ pub fn str_to_wstring(name: &str) -> Vec<u16> {
     wstring
 }
 
+#[cfg(windows)]
 pub fn windows_get_last_error(debug: &str) -> Result<(), ServiceError> {
     let error: i32 = unsafe { GetLastError() as i32 };
 
     if error != 0x0 {
         // error is the original windows api error, use log and debug str
         println!("Error calling windows api: {} : {}", debug, error);
-        return Err(ServiceError::WindowsGetLastError(error));
+        return Err(ServiceError::Ipc {
+            call: String::from(debug),
+            source: std::io::Error::from_raw_os_error(error),
+        });
     }
 
     Ok(())
 }
+
+#[cfg(unix)]
+pub fn posix_get_last_error(debug: &str) -> ServiceError {
+    let error = std::io::Error::last_os_error();
+
+    // error is the original errno, use log and debug str
+    println!("Error calling posix api: {} : {}", debug, error);
+    ServiceError::Ipc {
+        call: String::from(debug),
+        source: error,
+    }
+}
+
+// joins a service thread, a panic is returned as ServiceError::Panicked
+pub(crate) fn join_thread(
+    handle: Option<JoinHandle<Result<(), ServiceError>>>,
+) -> Result<(), ServiceError> {
+    let handle = match handle {
+        Some(handle) => handle,
+        None => return Ok(()),
+    };
+
+    match handle.join() {
+        Ok(result) => result,
+        Err(panic) => {
+            let message = if let Some(message) = panic.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = panic.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                String::from("unknown panic")
+            };
+            Err(ServiceError::Panicked(message))
+        }
+    }
+}
+
+// true when the service thread ended and has not been joined yet
+pub(crate) fn thread_finished<T>(handle: &Option<JoinHandle<T>>) -> bool {
+    handle.as_ref().is_some_and(|handle| handle.is_finished())
+}
@@ -0,0 +1,95 @@
+use control::queue::{bounded, input_queue, Backpressure, InputQueueConfig, QueuePolicy};
+use control::{Input, InputType};
+use std::sync::mpsc::RecvTimeoutError;
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{image, packet, FRAME_START};
+
+#[test]
+fn drop_policies_discard_and_count() {
+    let (tx, rx) = bounded(QueuePolicy::drop_oldest(2));
+    for item in 0..5 {
+        tx.send(item).unwrap();
+    }
+    assert_eq!(rx.try_iter().collect::<Vec<u32>>(), vec![3, 4]);
+    assert_eq!(rx.dropped().total(), 3);
+
+    let (tx, rx) = bounded(QueuePolicy::drop_newest(2));
+    for item in 0..5 {
+        tx.send(item).unwrap();
+    }
+    assert_eq!(rx.try_iter().collect::<Vec<u32>>(), vec![0, 1]);
+    assert_eq!(rx.dropped().total(), 3);
+
+    let (tx, rx) = bounded(QueuePolicy::keep_latest());
+    for item in 0..5 {
+        tx.send(item).unwrap();
+    }
+    assert_eq!(rx.try_iter().collect::<Vec<u32>>(), vec![4]);
+    assert_eq!(tx.dropped().total(), 4);
+}
+
+#[test]
+fn block_waits_for_room_and_fails_without_receiver() {
+    let (tx, rx) = bounded(QueuePolicy::block(1));
+    tx.send(0).unwrap();
+
+    let producer = thread::spawn(move || {
+        tx.send(1).unwrap();
+        tx
+    });
+
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(rx.len(), 1);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(0));
+    assert_eq!(rx.recv_timeout(Duration::from_secs(1)), Ok(1));
+    assert_eq!(rx.dropped().total(), 0);
+
+    let tx = producer.join().unwrap();
+    tx.send(2).unwrap();
+    drop(rx);
+    assert!(tx.send(3).is_err());
+
+    let (tx, rx) = bounded::<u32>(QueuePolicy::block(1));
+    drop(tx);
+    assert_eq!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(RecvTimeoutError::Disconnected)
+    );
+}
+
+#[test]
+fn input_queue_applies_policy_per_input_type_and_keeps_order() {
+    let (tx, rx) = input_queue(&InputQueueConfig::default());
+
+    tx.send(packet(FRAME_START, 0, 0)).unwrap();
+    tx.send(image(1, 5)).unwrap();
+    tx.send(packet(FRAME_START, 1, 10)).unwrap();
+    tx.send(image(2, 15)).unwrap();
+    tx.send(image(3, 18)).unwrap();
+    tx.send(packet(FRAME_START, 2, 20)).unwrap();
+
+    let received: Vec<Box<dyn Input + Send>> = rx.try_iter().collect();
+    assert_eq!(received.len(), 4);
+
+    // the replaced screenshots leave their place in the order to the latest one
+    assert_eq!(received[0].header().sequence, 0);
+    assert_eq!(received[1].header().sequence, 1);
+    assert!(matches!(received[2].input_type(), InputType::Image));
+    assert_eq!(received[2].capture().sequence, 3);
+    assert_eq!(received[3].header().sequence, 2);
+
+    let dropped = rx.dropped();
+    assert_eq!(dropped.input(InputType::Image), 2);
+    assert_eq!(dropped.input(InputType::Telemetry), 0);
+    assert_eq!(dropped.total(), 2);
+
+    // telemetry frames the groups and user events build the controller state, neither is lost
+    let config = InputQueueConfig::default();
+    for input_type in [InputType::Telemetry, InputType::User] {
+        assert_eq!(config.policy(input_type).backpressure, Backpressure::Block);
+    }
+}
@@ -0,0 +1,67 @@
+use control::queue::unbounded;
+use control::synchronization::Synchronization;
+use control::{CancellationToken, Process, SHUTDOWN_POLL_INTERVAL};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn cancel_wakes_up_a_waiting_thread() {
+    let token = CancellationToken::new();
+    let waiter = token.clone();
+
+    let started = Instant::now();
+    let handle = thread::spawn(move || waiter.wait_timeout(Duration::from_secs(10)));
+
+    thread::sleep(Duration::from_millis(10));
+    token.cancel();
+
+    assert!(handle.join().unwrap());
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert!(token.is_cancelled());
+}
+
+#[test]
+fn wait_timeout_returns_false_when_not_cancelled() {
+    let token = CancellationToken::new();
+
+    let started = Instant::now();
+    assert!(!token.wait_timeout(Duration::from_millis(5)));
+    assert!(started.elapsed() >= Duration::from_millis(5));
+}
+
+#[test]
+fn recv_all_drains_queued_items_after_cancel() {
+    let token = CancellationToken::new();
+    let (tx, rx) = unbounded();
+
+    tx.send(1).unwrap();
+    tx.send(2).unwrap();
+    assert_eq!(token.recv_all(&rx), Some(vec![1, 2]));
+    assert_eq!(token.recv_all(&rx), Some(vec![]));
+
+    tx.send(3).unwrap();
+    token.cancel();
+    assert_eq!(token.recv_all(&rx), Some(vec![3]));
+    assert_eq!(token.recv_all(&rx), None);
+
+    // every transmitter dropped
+    let (tx, rx) = unbounded::<u32>();
+    drop(tx);
+    assert_eq!(CancellationToken::new().recv_all(&rx), None);
+}
+
+#[test]
+fn idle_synchronization_stops_within_the_poll_interval() {
+    let (tx, _rx) = unbounded();
+    let mut sync = Synchronization::new();
+    sync.set_output_transmitter(tx);
+    sync.start().unwrap();
+
+    thread::sleep(Duration::from_millis(50));
+
+    let started = Instant::now();
+    sync.stop().unwrap();
+    sync.join().unwrap();
+
+    assert!(started.elapsed() < SHUTDOWN_POLL_INTERVAL * 5);
+}
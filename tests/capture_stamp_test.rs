@@ -0,0 +1,70 @@
+use control::screencapture::Pixels;
+use control::telemetry::{PacketParser, SelectGame};
+use control::{CaptureClock, CaptureSequence, CaptureStamp, Input};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{TelemetryBuffer, FRAME_START};
+
+#[test]
+fn sequences_count_per_source_on_a_shared_clock() {
+    let first = CaptureSequence::new();
+    let second = CaptureSequence::new();
+
+    let a = first.stamp();
+    thread::sleep(Duration::from_millis(2));
+    let b = second.stamp();
+    let c = first.clone().stamp();
+
+    assert_eq!((a.sequence, b.sequence, c.sequence), (1, 1, 2));
+    assert_eq!(first.last(), 2);
+    assert!(a.time < b.time && b.time <= c.time);
+    assert!(b.time - a.time >= Duration::from_millis(2));
+
+    // wall clock follows from the anchor, not from reading the wall clock again
+    assert_eq!(c.wall_clock(), CaptureClock::started_at() + c.time);
+    assert!(c.wall_clock() <= SystemTime::now());
+
+    let acquired = Instant::now();
+    thread::sleep(Duration::from_millis(2));
+    let stamp = first.stamp_at(acquired);
+    assert_eq!(stamp.time, CaptureClock::time_of(acquired));
+    assert!(CaptureClock::now() > stamp.time);
+}
+
+#[test]
+fn parser_stamps_packets_when_they_are_read() {
+    let parser = PacketParser::new(SelectGame::ETS2);
+
+    let before = CaptureClock::now();
+    let packets: Vec<Box<dyn Input + Send>> = (0..3)
+        .map(|id| {
+            let mut buffer = TelemetryBuffer::new(true, FRAME_START, 32, id, 0);
+            parser.data(buffer.address()).1
+        })
+        .collect();
+
+    for (index, packet) in packets.iter().enumerate() {
+        let capture = packet.capture();
+        assert!(capture.is_captured());
+        assert_eq!(capture.sequence, index as u64 + 1);
+        assert!(capture.time >= before);
+    }
+
+    // each parser is its own source
+    let mut buffer = TelemetryBuffer::new(true, FRAME_START, 32, 0, 0);
+    let other = PacketParser::new(SelectGame::ETS2).data(buffer.address()).1;
+    assert_eq!(other.capture().sequence, 1);
+}
+
+#[test]
+fn pixels_are_not_captured_until_stamped() {
+    let pixels = Pixels::new(vec![0; 4], 1, 1);
+    assert!(!pixels.capture().is_captured());
+
+    let stamp = CaptureStamp::new(7, Duration::from_millis(30));
+    let pixels = pixels.with_capture(stamp);
+    assert_eq!(pixels.capture(), stamp);
+}
@@ -0,0 +1,191 @@
+use control::control_loop::{ControlLoop, Policy};
+use control::telemetry::ets2::{FrameEnd, TelemetryPacket};
+use control::userinput::{EV_ABS, EV_SYN};
+use control::virtual_controller::{Action, MockBackend, ABS_THROTTLE, AXIS_MAX};
+use control::{CaptureClock, CaptureStamp, Input, Process};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::FRAME_END;
+
+// throttle from the speed of the frame, stalls on the frames listed
+struct SpeedPolicy {
+    stall_at: Vec<u64>,
+    stall: Duration,
+    resets: Arc<AtomicU64>,
+}
+
+impl Policy for SpeedPolicy {
+    fn act(&mut self, group: &[Box<dyn Input + Send>]) -> Vec<Action> {
+        let packet = group[0].as_any().downcast_ref::<TelemetryPacket>().unwrap();
+        if self.stall_at.contains(&packet.id) {
+            thread::sleep(self.stall);
+        }
+
+        let speed = packet.event_payload().frame_end().unwrap().speed;
+        vec![Action::Throttle(speed / 10.0)]
+    }
+
+    fn reset(&mut self) {
+        self.resets.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn group(id: u64, speed: f32, capture: Duration) -> Vec<Box<dyn Input + Send>> {
+    let mut end = TelemetryPacket::new();
+    end.type_ = FRAME_END;
+    end.id = id;
+    end.length = mem::size_of::<FrameEnd>() as u32;
+    end.capture = CaptureStamp::new(id + 1, capture);
+    end.data.frame_end_data = FrameEnd {
+        speed,
+        ..Default::default()
+    };
+    vec![Box::new(end)]
+}
+
+fn throttle(backend: &MockBackend) -> Vec<i32> {
+    backend
+        .events()
+        .iter()
+        .filter(|event| event.type_ == EV_ABS && event.code == ABS_THROTTLE)
+        .map(|event| event.value)
+        .collect()
+}
+
+fn control_loop(
+    stall_at: Vec<u64>,
+    watchdog_timeout: Duration,
+) -> (ControlLoop, MockBackend, Arc<AtomicU64>) {
+    let backend = MockBackend::new();
+    let resets = Arc::new(AtomicU64::new(0));
+    let policy = SpeedPolicy {
+        stall_at,
+        stall: Duration::from_millis(300),
+        resets: resets.clone(),
+    };
+
+    let mut control = ControlLoop::new(Box::new(policy), Box::new(backend.clone()));
+    control.set_max_latency(Duration::from_millis(50));
+    control.set_watchdog_timeout(watchdog_timeout);
+    (control, backend, resets)
+}
+
+#[test]
+fn fresh_groups_are_acted_on_and_stale_groups_skipped() {
+    // the watchdog does not release within the test
+    let (mut control, backend, _) = control_loop(Vec::new(), Duration::from_secs(1));
+    let tx = control.get_input_transmitter();
+    control.start().unwrap();
+
+    tx.send(group(1, 5.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    // captured before the max latency
+    let old = CaptureClock::now();
+    thread::sleep(Duration::from_millis(60));
+    tx.send(group(2, 10.0, old)).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    tx.send(group(3, 10.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    control.stop().unwrap();
+    control.join().unwrap();
+
+    let counters = control.counters();
+    assert_eq!(counters.groups, 3);
+    assert_eq!(counters.acted, 2);
+    assert_eq!(counters.stale, 1);
+    assert_eq!(counters.rejected, 0);
+
+    // half throttle, full throttle, released when the service stopped
+    assert_eq!(throttle(&backend), vec![AXIS_MAX / 2 + 1, AXIS_MAX, 0]);
+    assert!(backend.is_closed());
+}
+
+#[test]
+fn watchdog_releases_inputs_when_the_policy_stalls() {
+    let (mut control, backend, resets) = control_loop(vec![2], Duration::from_millis(100));
+    let tx = control.get_input_transmitter();
+    control.start().unwrap();
+
+    tx.send(group(1, 10.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(20));
+    // the policy stalls longer than the watchdog timeout
+    tx.send(group(2, 10.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    // released while the policy is still stalled
+    assert_eq!(throttle(&backend), vec![AXIS_MAX, 0]);
+    assert_eq!(control.counters().releases, 1);
+
+    thread::sleep(Duration::from_millis(150));
+    tx.send(group(3, 5.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    control.stop().unwrap();
+    control.join().unwrap();
+
+    // the actions of the stalled frame are outdated and not applied
+    assert_eq!(throttle(&backend), vec![AXIS_MAX, 0, AXIS_MAX / 2 + 1, 0]);
+    let counters = control.counters();
+    assert_eq!(counters.acted, 2);
+    assert_eq!(counters.stale, 1);
+    // once before the first frame and once after the release
+    assert_eq!(resets.load(Ordering::SeqCst), 2);
+    for report in backend.reports() {
+        assert_eq!(report.last().unwrap().type_, EV_SYN);
+    }
+}
+
+#[test]
+fn rejected_actions_do_not_feed_the_watchdog() {
+    let (mut control, backend, _) = control_loop(Vec::new(), Duration::from_millis(100));
+    let tx = control.get_input_transmitter();
+    control.start().unwrap();
+
+    tx.send(group(1, 10.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    // a bad speed reading, the policy keeps returning a nan throttle
+    for id in 2..12 {
+        tx.send(group(id, f32::NAN, CaptureClock::now())).unwrap();
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    control.stop().unwrap();
+    control.join().unwrap();
+
+    let counters = control.counters();
+    assert_eq!(counters.acted, 1);
+    assert_eq!(counters.rejected, 10);
+    assert_eq!(counters.releases, 1);
+
+    // released by the watchdog while the actions were rejected
+    assert_eq!(throttle(&backend), vec![AXIS_MAX, 0]);
+}
+
+#[test]
+fn failed_watchdog_releases_stop_the_control_loop() {
+    let (mut control, backend, _) = control_loop(Vec::new(), Duration::from_millis(100));
+    let tx = control.get_input_transmitter();
+    control.start().unwrap();
+
+    tx.send(group(1, 10.0, CaptureClock::now())).unwrap();
+    thread::sleep(Duration::from_millis(20));
+
+    // the device went away, the release of the watchdog fails
+    backend.set_failing(true);
+    thread::sleep(Duration::from_millis(300));
+
+    assert!(control.is_finished());
+    assert!(control.join().is_err());
+    assert!(backend.is_closed());
+    assert_eq!(control.counters().releases, 0);
+}
@@ -0,0 +1,152 @@
+use control::queue::unbounded;
+use control::synchronization::{FrameGrouping, GroupingPolicy, PauseGate, Synchronization};
+use control::userinput::{
+    Binding, Control, ControllerAggregator, ControllerMapping, ControllerSnapshot, EvdevEvent,
+    UserEvent, EV_ABS, EV_KEY, EV_SYN, SYN_DROPPED,
+};
+use control::{CaptureStamp, Input, InputType, Process};
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{packet, FRAME_END, FRAME_START, PAUSED, STARTED};
+
+// linux/input-event-codes.h
+const KEY_W: u16 = 17;
+const KEY_A: u16 = 30;
+const KEY_SPACE: u16 = 57;
+const ABS_X: u16 = 0x00;
+const ABS_Z: u16 = 0x02;
+
+const KEYBOARD: u16 = 0;
+const WHEEL: u16 = 1;
+
+fn user(device: u16, type_: u16, code: u16, value: i32) -> Box<dyn Input + Send> {
+    let event = EvdevEvent::new(Duration::from_secs(0), type_, code, value);
+    Box::new(UserEvent::new(device, event, CaptureStamp::default()).unwrap())
+}
+
+fn mapping() -> ControllerMapping {
+    ControllerMapping::keyboard(KEYBOARD)
+        .with_binding(
+            Control::Steering,
+            Binding::Axis {
+                device: WHEEL,
+                code: ABS_X,
+                min: 0,
+                max: 65_535,
+                inverted: false,
+            },
+        )
+        // pedal reports 255 when released
+        .with_binding(
+            Control::Brake,
+            Binding::Axis {
+                device: WHEEL,
+                code: ABS_Z,
+                min: 0,
+                max: 255,
+                inverted: true,
+            },
+        )
+}
+
+#[test]
+fn every_group_carries_one_controller_snapshot() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let policy = ControllerAggregator::new(mapping(), PauseGate::new(FrameGrouping::new()));
+    let mut sync = Synchronization::with_policy(Box::new(policy));
+    sync.set_output_transmitter(out_transmitter);
+    let tx = sync.get_input_transmitter();
+
+    let inputs = vec![
+        // state follows events received before the start
+        user(KEYBOARD, EV_KEY, KEY_W, 1),
+        packet(STARTED, 0, 0),
+        packet(FRAME_START, 1, 1),
+        user(KEYBOARD, EV_KEY, KEY_A, 1),
+        user(WHEEL, EV_ABS, ABS_X, 65_535),
+        packet(FRAME_END, 2, 2),
+        packet(PAUSED, 3, 3),
+        // released while paused
+        user(KEYBOARD, EV_KEY, KEY_W, 0),
+        user(KEYBOARD, EV_KEY, KEY_SPACE, 1),
+        packet(STARTED, 4, 4),
+        user(WHEEL, EV_ABS, ABS_Z, 0),
+        packet(FRAME_START, 5, 5),
+        user(KEYBOARD, EV_KEY, KEY_SPACE, 2),
+        packet(FRAME_END, 6, 6),
+    ];
+    for input in inputs {
+        tx.send(input).unwrap();
+    }
+
+    sync.start().unwrap();
+    thread::sleep(Duration::from_millis(100));
+    sync.stop().unwrap();
+    sync.join().unwrap();
+
+    let groups: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.try_iter().collect();
+    assert_eq!(groups.len(), 2);
+
+    let snapshots: Vec<&ControllerSnapshot> = groups
+        .iter()
+        .map(|group| {
+            let user: Vec<&Box<dyn Input + Send>> = group
+                .iter()
+                .filter(|input| input.input_type() == InputType::User)
+                .collect();
+            assert_eq!(user.len(), 1);
+            assert_eq!(group.len(), 3);
+
+            // right after the FrameStart, same capture time
+            assert!(ControllerSnapshot::from_input(group[1].as_ref()).is_some());
+            assert_eq!(group[1].capture().time, group[0].capture().time);
+
+            ControllerSnapshot::from_input(group[1].as_ref()).unwrap()
+        })
+        .collect();
+
+    // A pressed and the wheel turned to the right after the FrameStart, not in this snapshot
+    assert_eq!(snapshots[0].frame_id, 1);
+    assert_eq!(snapshots[0].game_time, 1_000);
+    assert_eq!(snapshots[0].state.throttle, 1.0);
+    assert_eq!(snapshots[0].state.steering, 0.0);
+    assert_eq!(snapshots[0].state.brake, 0.0);
+    assert!(!snapshots[0].state.is_pressed(0));
+
+    // A and wheel cancel out, brake fully pressed, space held
+    assert_eq!(snapshots[1].frame_id, 5);
+    assert_eq!(snapshots[1].state.throttle, 0.0);
+    assert_eq!(snapshots[1].state.steering, 0.0);
+    assert_eq!(snapshots[1].state.brake, 1.0);
+    assert!(snapshots[1].state.is_pressed(0));
+    assert_eq!(snapshots[1].capture.sequence, 2);
+}
+
+#[test]
+fn dropped_events_release_the_device_controls() {
+    let mut policy = ControllerAggregator::new(mapping(), FrameGrouping::new());
+
+    let inputs = vec![
+        user(KEYBOARD, EV_KEY, KEY_W, 1),
+        user(KEYBOARD, EV_KEY, KEY_SPACE, 1),
+        user(WHEEL, EV_ABS, ABS_Z, 0),
+    ];
+    for input in inputs {
+        assert!(policy.push(input).is_empty());
+    }
+    assert_eq!(policy.state().throttle, 1.0);
+    assert!(policy.state().is_pressed(0));
+
+    // the release of W and space may have been lost
+    policy.push(user(KEYBOARD, EV_SYN, SYN_DROPPED, 0));
+
+    let state = policy.state();
+    assert_eq!(state.throttle, 0.0);
+    assert!(!state.is_pressed(0));
+    // bound to the other device, kept
+    assert_eq!(state.brake, 1.0);
+}
@@ -0,0 +1,194 @@
+use control::control_loop::{CruiseControl, Limits, PidGains, Policy, Setpoint};
+use control::telemetry::ets2::{FrameEnd, TelemetryPacket};
+use control::virtual_controller::Action;
+use control::Input;
+use std::mem;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{telemetry_packet, FRAME_END, FRAME_START, STARTED};
+
+fn frame_end(id: u64, capture_ms: u64, speed: f32, limit: f32) -> TelemetryPacket {
+    let mut end = telemetry_packet(FRAME_END, id, capture_ms);
+    end.length = mem::size_of::<FrameEnd>() as u32;
+    end.data.frame_end_data = FrameEnd {
+        speed,
+        navigation_speed_limit: limit,
+        ..Default::default()
+    };
+    end
+}
+
+fn group(packets: Vec<TelemetryPacket>) -> Vec<Box<dyn Input + Send>> {
+    packets
+        .into_iter()
+        .map(|packet| Box::new(packet) as Box<dyn Input + Send>)
+        .collect()
+}
+
+fn pedals(throttle: f32, brake: f32) -> Vec<Action> {
+    vec![Action::Throttle(throttle), Action::Brake(brake)]
+}
+
+#[test]
+fn proportional_output_is_saturated_per_pedal() {
+    let mut cruise = CruiseControl::new(Setpoint::SpeedLimit)
+        .with_gains(PidGains {
+            kp: 0.125,
+            ki: 0.0,
+            kd: 0.0,
+        })
+        .with_limits(Limits {
+            max_throttle: 0.75,
+            max_brake: 0.5,
+            max_integral: 10.0,
+        });
+
+    // no frame end, the controller keeps its state
+    assert!(cruise
+        .act(&group(vec![telemetry_packet(FRAME_START, 0, 0)]))
+        .is_empty());
+
+    let frames = [
+        (21.0, 25.0, pedals(0.5, 0.0)),
+        (0.0, 25.0, pedals(0.75, 0.0)),
+        (27.0, 25.0, pedals(0.0, 0.25)),
+        (35.0, 25.0, pedals(0.0, 0.5)),
+        // no speed limit on this road, coast
+        (20.0, 0.0, pedals(0.0, 0.0)),
+    ];
+    for (id, (speed, limit, actions)) in frames.iter().enumerate() {
+        let id = id as u64;
+        let frame = group(vec![
+            telemetry_packet(FRAME_START, id * 2, id * 10),
+            frame_end(id * 2 + 1, id * 10, *speed, *limit),
+        ]);
+        assert_eq!(&cruise.act(&frame), actions);
+    }
+
+    let fixed = CruiseControl::new(Setpoint::Fixed(10.0));
+    let cruise = CruiseControl::new(Setpoint::Cruise);
+    let frame_end = FrameEnd {
+        cruise_control: 22.0,
+        navigation_speed_limit: 25.0,
+        ..Default::default()
+    };
+    assert_eq!(fixed.target(&frame_end), Some(10.0));
+    assert_eq!(cruise.target(&frame_end), Some(22.0));
+    assert_eq!(cruise.target(&FrameEnd::default()), None);
+}
+
+#[test]
+fn integral_follows_game_time_and_is_cleared_by_reset() {
+    let mut cruise = CruiseControl::new(Setpoint::Fixed(25.0))
+        .with_gains(PidGains {
+            kp: 0.0,
+            ki: 0.125,
+            kd: 0.0,
+        })
+        .with_limits(Limits {
+            max_throttle: 1.0,
+            max_brake: 1.0,
+            max_integral: 6.0,
+        });
+
+    let mut act = |id: u64, seconds: u64, speed: f32| {
+        cruise.act(&group(vec![frame_end(id, seconds * 1_000, speed, 0.0)]))
+    };
+
+    // the first frame has no time step
+    assert_eq!(act(0, 0, 21.0), pedals(0.0, 0.0));
+    assert_eq!(act(1, 1, 21.0), pedals(0.5, 0.0));
+    // clamped to the max integral
+    assert_eq!(act(2, 2, 21.0), pedals(0.75, 0.0));
+    // game time did not progress
+    assert_eq!(act(3, 2, 21.0), pedals(0.75, 0.0));
+    assert_eq!(act(4, 3, 29.0), pedals(0.25, 0.0));
+
+    cruise.reset();
+    assert_eq!(
+        cruise.act(&group(vec![frame_end(5, 4_000, 29.0, 0.0)])),
+        pedals(0.0, 0.0)
+    );
+}
+
+// closed loop over a replayed recording: throttle while below the speed limit, brake above it
+#[cfg(unix)]
+#[test]
+fn replayed_telemetry_drives_the_virtual_controller() {
+    use control::control_loop::ControlLoop;
+    use control::synchronization::Synchronization;
+    use control::telemetry::{ReplaySpeed, SelectGame, Telemetry, TelemetryWriter};
+    use control::userinput::EV_ABS;
+    use control::virtual_controller::{MockBackend, ABS_BRAKE, ABS_THROTTLE};
+    use control::{ServiceType, Services};
+
+    let path = std::env::temp_dir().join(format!("control_cruise_{}.cktr", std::process::id()));
+
+    // 20 frames, 20ms apart, accelerating past the speed limit
+    let mut writer = TelemetryWriter::create(&path, SelectGame::ETS2).unwrap();
+    writer.write(&telemetry_packet(STARTED, 0, 0)).unwrap();
+    for frame in 0..20u64 {
+        let time = frame * 20;
+        let speed = frame as f32 * 1.5;
+        writer
+            .write(&telemetry_packet(FRAME_START, frame * 2 + 1, time))
+            .unwrap();
+        writer
+            .write(&frame_end(frame * 2 + 2, time, speed, 25.0))
+            .unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+
+    let backend = MockBackend::new();
+    let cruise = CruiseControl::new(Setpoint::SpeedLimit).with_gains(PidGains {
+        kp: 0.125,
+        ki: 0.0,
+        kd: 0.0,
+    });
+    let control = ControlLoop::new(Box::new(cruise), Box::new(backend.clone()));
+
+    let mut sync = Synchronization::new();
+    sync.set_output_transmitter(control.get_input_transmitter());
+
+    let mut replay = Telemetry::via_replay(&path, ReplaySpeed::Original);
+    replay.set_transmitter(sync.get_input_transmitter());
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::ControlOutput, Box::new(control))
+        .unwrap();
+    cap_sess
+        .add_service(ServiceType::SynchronizeInputs, Box::new(sync))
+        .unwrap();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(replay))
+        .unwrap();
+
+    cap_sess.block_until_telemetry_finished().unwrap();
+    std::thread::sleep(Duration::from_millis(50));
+    cap_sess.stop_all_services().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let pedal = |code: u16| -> Vec<i32> {
+        backend
+            .events()
+            .iter()
+            .filter(|event| event.type_ == EV_ABS && event.code == code)
+            .map(|event| event.value)
+            .collect()
+    };
+
+    let throttle = pedal(ABS_THROTTLE);
+    let brake = pedal(ABS_BRAKE);
+    assert!(throttle.len() > 2);
+    assert!(brake.len() > 1);
+
+    // throttle eases off towards the limit, both pedals are released when the service stops
+    assert!(throttle.windows(2).all(|pair| pair[1] <= pair[0]));
+    assert_eq!(throttle.last(), Some(&0));
+    assert_eq!(brake.last(), Some(&0));
+    assert!(backend.is_closed());
+}
@@ -0,0 +1,188 @@
+use control::dataset::{DatasetWriter, INDEX_FILE, MANIFEST_FILE, RECORDS_FILE};
+use control::screencapture::Pixels;
+use control::userinput::{ControllerSnapshot, ControllerState};
+use control::{CaptureStamp, ImageEncoding, ServiceType, Services};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{packet, FRAME_END, FRAME_START};
+
+fn output_dir(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("control_dataset_{}_{}", test, std::process::id()))
+}
+
+// one json value per line of records.jsonl or index.jsonl
+fn json_lines(path: &Path) -> Vec<serde_json::Value> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn dataset_writer_persists_one_record_per_group() {
+    let dir = output_dir("Records");
+
+    let mut writer = DatasetWriter::new(&dir);
+    writer.set_session_name("drive");
+    writer.set_image_encoding(ImageEncoding::Jpeg(80));
+    let tx = writer.get_input_transmitter();
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::DatasetOutput, Box::new(writer))
+        .unwrap();
+
+    tx.send(vec![
+        packet(FRAME_START, 0, 0),
+        Box::new(Pixels::new(vec![128; 16], 2, 2)),
+        packet(FRAME_END, 1, 1),
+    ])
+    .unwrap();
+    tx.send(vec![packet(FRAME_START, 2, 2), packet(FRAME_END, 3, 3)])
+        .unwrap();
+
+    cap_sess.stop_all_services().unwrap();
+
+    let session = dir.join("drive");
+
+    let records = json_lines(&session.join(RECORDS_FILE));
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["frame_id"], 0);
+    assert_eq!(records[0]["telemetry"].as_array().unwrap().len(), 2);
+    assert_eq!(records[0]["telemetry"][1]["event"], "FrameEnd");
+    assert_eq!(records[0]["image"], "images/000000.jpg");
+    assert_eq!(records[1]["frame_id"], 2);
+    assert!(records[1]["image"].is_null());
+
+    let image = fs::read(session.join("images/000000.jpg")).unwrap();
+    assert_eq!(&image[0..2], &[0xff, 0xd8]);
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(session.join(MANIFEST_FILE)).unwrap()).unwrap();
+    assert_eq!(manifest["session"], "drive");
+    assert_eq!(manifest["records"], 2);
+    assert_eq!(manifest["index_file"], INDEX_FILE);
+
+    let index = json_lines(&session.join(INDEX_FILE));
+    assert_eq!(index.len(), 2);
+    assert_eq!(index[0]["image"], "images/000000.jpg");
+    assert_eq!(index[1]["frame_id"], 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dataset_writer_refuses_existing_session() {
+    let dir = output_dir("Existing");
+
+    let mut writer = DatasetWriter::new(&dir);
+    writer.set_session_name("drive");
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::DatasetOutput, Box::new(writer))
+        .unwrap();
+    cap_sess.stop_all_services().unwrap();
+
+    let mut writer = DatasetWriter::new(&dir);
+    writer.set_session_name("drive");
+
+    let mut cap_sess = Services::new();
+    assert!(cap_sess
+        .add_service(ServiceType::DatasetOutput, Box::new(writer))
+        .is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dataset_writer_indexes_records_by_frame_start() {
+    let dir = output_dir("FrameStart");
+
+    let mut writer = DatasetWriter::new(&dir);
+    writer.set_session_name("drive");
+    let tx = writer.get_input_transmitter();
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::DatasetOutput, Box::new(writer))
+        .unwrap();
+
+    // the image arrived before the frame start
+    tx.send(vec![
+        Box::new(Pixels::new(vec![128; 16], 2, 2)),
+        packet(FRAME_START, 4, 4),
+        packet(FRAME_END, 5, 5),
+    ])
+    .unwrap();
+    // no frame start, the first telemetry input is used
+    tx.send(vec![
+        Box::new(Pixels::new(vec![128; 16], 2, 2)),
+        packet(FRAME_END, 7, 7),
+    ])
+    .unwrap();
+
+    cap_sess.stop_all_services().unwrap();
+
+    let session = dir.join("drive");
+
+    let records = json_lines(&session.join(RECORDS_FILE));
+
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["frame_id"], 4);
+    assert_eq!(records[0]["time"], 4_000);
+    assert_eq!(records[1]["frame_id"], 7);
+    assert_eq!(records[1]["time"], 7_000);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn dataset_writer_keeps_user_inputs_apart_and_flushes_while_running() {
+    let dir = output_dir("User");
+
+    let mut writer = DatasetWriter::new(&dir);
+    writer.set_session_name("drive");
+    let tx = writer.get_input_transmitter();
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::DatasetOutput, Box::new(writer))
+        .unwrap();
+
+    let state = ControllerState {
+        throttle: 0.5,
+        ..Default::default()
+    };
+    let snapshot = ControllerSnapshot::new(state, 0, 0, CaptureStamp::default());
+    tx.send(vec![
+        packet(FRAME_START, 0, 0),
+        Box::new(snapshot),
+        packet(FRAME_END, 1, 1),
+    ])
+    .unwrap();
+    thread::sleep(Duration::from_millis(100));
+
+    // readable before the service stopped
+    let session = dir.join("drive");
+    let records = json_lines(&session.join(RECORDS_FILE));
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0]["telemetry"].as_array().unwrap().len(), 2);
+    assert_eq!(records[0]["user"].as_array().unwrap().len(), 1);
+    assert_eq!(records[0]["user"][0]["kind"], "controller");
+    assert_eq!(json_lines(&session.join(INDEX_FILE)).len(), 1);
+
+    let manifest: serde_json::Value =
+        serde_json::from_slice(&fs::read(session.join(MANIFEST_FILE)).unwrap()).unwrap();
+    assert_eq!(manifest["records"], 1);
+
+    cap_sess.stop_all_services().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+}
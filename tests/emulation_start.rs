@@ -1,9 +1,11 @@
-use std::io::{BufRead, BufReader, Error, ErrorKind};
+use std::io::BufReader;
 use std::process::{Child, ChildStdout, Command, Stdio};
 
 pub struct ETS2Emulation;
 
 impl ETS2Emulation {
+    // requires TelemetryEmulation.exe
+    #[cfg(windows)]
     pub fn start_with_stdout() -> BufReader<ChildStdout> {
         let stdout = Command::new(".\\tests.\\TelemetryEmulation.exe")
             .stdout(Stdio::piped())
@@ -15,6 +17,7 @@ impl ETS2Emulation {
         BufReader::new(stdout)
     }
 
+    #[cfg(windows)]
     pub fn start() -> Child {
         let child = Command::new(".\\tests.\\TelemetryEmulation.exe")
             .stdout(Stdio::null())
@@ -25,4 +28,20 @@ impl ETS2Emulation {
 
         child
     }
+
+    // in-repo emulator, shared memory objects are created under name so tests can run in parallel
+    #[cfg(target_os = "linux")]
+    pub fn start_named_with_stdout(name: &str, args: &[&str]) -> (Child, BufReader<ChildStdout>) {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ets2_emulator"))
+            .arg("--name")
+            .arg(name)
+            .args(args)
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let stdout = child.stdout.take().unwrap();
+
+        (child, BufReader::new(stdout))
+    }
 }
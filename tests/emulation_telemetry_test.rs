@@ -0,0 +1,111 @@
+#![cfg(target_os = "linux")]
+
+use control::queue::unbounded;
+use control::synchronization::Synchronization;
+use control::telemetry::{EventGame, SelectGame, Telemetry};
+use control::{Input, ServiceType, Services};
+use std::io::BufRead;
+
+mod emulation_start;
+
+use emulation_start::ETS2Emulation;
+
+fn unique_name(test: &str) -> String {
+    format!("ControlKit{}{}", test, std::process::id())
+}
+
+#[test]
+fn telemetry_service_receives_every_emulated_packet() {
+    let name = unique_name("Complete");
+    let (mut child, reader) = ETS2Emulation::start_named_with_stdout(&name, &[]);
+    let mut lines = reader.lines();
+
+    // the first packet is written before any reader connects, the objects exist once it is printed
+    let first = lines.next().unwrap().unwrap();
+    assert_eq!(first, "0-5-0-0");
+
+    let (tx, rx) = unbounded();
+    let mut ets2_telemetry = Telemetry::via_shared_memory_named(SelectGame::ETS2, &name);
+    ets2_telemetry.set_transmitter(tx);
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(ets2_telemetry))
+        .unwrap();
+
+    let mut i: u64 = 1;
+    for line in lines {
+        let x = line.expect("irrelevant error - may ignore");
+        let numbers: Vec<u64> = x
+            .split('-')
+            .map(|val| str::parse::<u64>(val).unwrap())
+            .collect();
+
+        assert!(i == numbers[0]);
+        i += 1;
+    }
+
+    assert!(i == 23182);
+
+    cap_sess.block_until_telemetry_finished().unwrap();
+    child.wait().unwrap();
+
+    let packets: Vec<Box<dyn Input + Send>> = rx.iter().collect();
+    assert_eq!(packets.len(), 23182);
+    for (id, packet) in packets.iter().enumerate() {
+        assert_eq!(packet.header().sequence, id as u64);
+    }
+}
+
+#[test]
+fn synchronization_service_skips_scripted_pause() {
+    let name = unique_name("Paused");
+    let (mut child, reader) = ETS2Emulation::start_named_with_stdout(
+        &name,
+        &[
+            "--frames",
+            "20",
+            "--pause-at",
+            "5",
+            "--resume-at",
+            "10",
+            "--rate",
+            "500",
+        ],
+    );
+    let mut lines = reader.lines();
+    lines.next().unwrap().unwrap();
+
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let mut sync = Synchronization::new();
+    let tx = sync.get_input_transmitter();
+    sync.set_output_transmitter(out_transmitter);
+
+    let mut ets2_telemetry = Telemetry::via_shared_memory_named(SelectGame::ETS2, &name);
+    ets2_telemetry.set_transmitter(tx);
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::SynchronizeInputs, Box::new(sync))
+        .unwrap();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(ets2_telemetry))
+        .unwrap();
+
+    // 2 + 20 frames + pause and resume
+    assert_eq!(lines.count(), 43);
+
+    cap_sess.block_until_telemetry_finished().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    cap_sess.stop_all_services().unwrap();
+    child.wait().unwrap();
+
+    let groups: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.iter().collect();
+
+    assert_eq!(groups.len(), 15);
+    for group in groups.iter() {
+        assert!(group.first().unwrap().event_type() == EventGame::FrameStartEvent);
+        assert!(group.last().unwrap().event_type() == EventGame::FrameEndEvent);
+    }
+}
@@ -0,0 +1,63 @@
+use control::screencapture::Pixels;
+use control::telemetry::ets2::{ETS2Event, Ets2Payload, FrameEnd};
+use control::telemetry::{PacketParser, SelectGame};
+use control::Input;
+use std::mem;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{TelemetryBuffer, FRAME_END, FRAME_START, PAUSED};
+
+fn packet(buffer: &mut TelemetryBuffer) -> Box<dyn Input + Send> {
+    PacketParser::new(SelectGame::ETS2).data(buffer.address()).1
+}
+
+#[test]
+fn frame_end_payload_exposes_typed_fields() {
+    assert_eq!(mem::size_of::<FrameEnd>(), 216);
+
+    let mut buffer = TelemetryBuffer::new(true, FRAME_END, 216, 1, 100);
+    buffer.write_data(0, &4u32.to_ne_bytes());
+    buffer.write_data(4, &20.0f32.to_ne_bytes());
+    buffer.write_data(8, &1200.0f32.to_ne_bytes());
+    buffer.write_data(12, &(-0.25f32).to_ne_bytes());
+    buffer.write_data(44, &22.5f32.to_ne_bytes());
+    buffer.write_data(48, &25.0f32.to_ne_bytes());
+    // world_placement.position.x, after 13 floats, padding, 6 vectors and 2 placements
+    buffer.write_data(176, &1234.5f64.to_ne_bytes());
+
+    let input = packet(&mut buffer);
+    let payload = Ets2Payload::from_input(input.as_ref()).unwrap();
+    let frame_end = payload.frame_end().unwrap();
+
+    assert_eq!(frame_end.gear(), 4);
+    assert_eq!(frame_end.speed_ms(), 20.0);
+    assert_eq!(frame_end.speed_kmh(), 72.0);
+    assert_eq!(frame_end.rpm(), 1200.0);
+    assert_eq!(frame_end.steering(), -0.25);
+    assert_eq!(frame_end.cruise_control_kmh(), 81.0);
+    assert_eq!(frame_end.speed_limit_kmh(), 90.0);
+    assert_eq!(frame_end.world_placement().position.x, 1234.5);
+    assert!(payload.frame_start().is_none());
+}
+
+#[test]
+fn payload_is_selected_by_type_and_length() {
+    let mut buffer = TelemetryBuffer::new(true, FRAME_START, 32, 2, 200);
+    buffer.write_data(16, &200u64.to_ne_bytes());
+    let payload = Ets2Payload::from_input(packet(&mut buffer).as_ref()).unwrap();
+    assert_eq!(payload.frame_start().unwrap().simulation_time_us(), 200);
+
+    // a frame end that is too short to hold the data is not read
+    let mut buffer = TelemetryBuffer::new(true, FRAME_END, 8, 3, 300);
+    let payload = Ets2Payload::from_input(packet(&mut buffer).as_ref()).unwrap();
+    assert_eq!(payload, Ets2Payload::NotValid);
+
+    let mut buffer = TelemetryBuffer::new(true, PAUSED, 0, 4, 400);
+    let payload = Ets2Payload::from_input(packet(&mut buffer).as_ref()).unwrap();
+    assert_eq!(payload, Ets2Payload::Paused);
+    assert_eq!(ETS2Event::new(PAUSED), ETS2Event::Paused);
+
+    let pixels = Pixels::new(vec![0; 4], 1, 1);
+    assert!(Ets2Payload::from_input(&pixels).is_none());
+}
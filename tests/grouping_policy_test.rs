@@ -0,0 +1,104 @@
+use control::synchronization::{
+    FrameCount, FrameGrouping, Group, GroupingPolicy, PauseGate, PerImage, TimeWindow,
+};
+use control::Input;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{image, packet, FRAME_END, FRAME_START, PAUSED, STARTED};
+
+// capture times of every group pushed and flushed
+fn run<P: GroupingPolicy>(
+    policy: &mut P,
+    inputs: Vec<Box<dyn Input + Send>>,
+    flush: bool,
+) -> Vec<Vec<u64>> {
+    let mut groups: Vec<Group> = Vec::new();
+    for input in inputs {
+        groups.extend(policy.push(input));
+    }
+    if flush {
+        groups.extend(policy.flush());
+    }
+
+    groups
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|input| input.capture().time.as_millis() as u64)
+                .collect()
+        })
+        .collect()
+}
+
+fn frames() -> Vec<Box<dyn Input + Send>> {
+    vec![
+        packet(FRAME_START, 0, 0),
+        image(2, 1),
+        packet(FRAME_END, 2, 2),
+        packet(FRAME_START, 10, 10),
+        packet(FRAME_END, 12, 12),
+        packet(FRAME_START, 20, 20),
+        image(22, 21),
+        packet(FRAME_END, 22, 22),
+        packet(FRAME_START, 30, 30),
+        image(32, 31),
+    ]
+}
+
+#[test]
+fn frame_grouping_sends_one_group_per_frame() {
+    let groups = run(&mut FrameGrouping::new(), frames(), true);
+    assert_eq!(groups, vec![vec![0, 1, 2], vec![10, 12], vec![20, 21, 22]]);
+}
+
+#[test]
+fn frame_count_drops_the_incomplete_frame_on_flush() {
+    let groups = run(&mut FrameCount::new(2), frames(), true);
+    assert_eq!(groups, vec![vec![0, 1, 2, 10, 12], vec![20, 21, 22]]);
+}
+
+#[test]
+fn time_window_groups_by_capture_time() {
+    let mut inputs = frames();
+    // late image of the first window
+    inputs.insert(4, image(4, 3));
+
+    let groups = run(
+        &mut TimeWindow::new(Duration::from_millis(15)),
+        inputs,
+        true,
+    );
+    assert_eq!(
+        groups,
+        vec![vec![0, 1, 2, 10, 3, 12], vec![20, 21, 22], vec![30, 31]]
+    );
+}
+
+#[test]
+fn per_image_ends_every_group_with_an_image() {
+    let groups = run(&mut PerImage::new(), frames(), true);
+    assert_eq!(
+        groups,
+        vec![vec![0, 1], vec![2, 10, 12, 20, 21], vec![22, 30, 31]]
+    );
+}
+
+#[test]
+fn pause_gate_discards_inputs_while_paused() {
+    let mut inputs = vec![packet(FRAME_START, 0, 0), packet(FRAME_END, 1, 1)];
+    inputs.push(packet(STARTED, 5, 5));
+    inputs.extend(frames());
+    // after the second frame
+    inputs.insert(8, packet(PAUSED, 13, 13));
+    inputs.insert(9, packet(STARTED, 14, 14));
+
+    let mut policy = PauseGate::new(FrameCount::new(3));
+    let groups = run(&mut policy, inputs, false);
+
+    // the pause flushes the completed frames, the frames before the start are discarded
+    assert_eq!(groups, vec![vec![0, 1, 2, 10, 12]]);
+    assert_eq!(run(&mut policy, Vec::new(), true), vec![vec![20, 21, 22]]);
+}
@@ -0,0 +1,149 @@
+use control::telemetry::{PacketParser, SelectGame, ValidationError};
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{TelemetryBuffer, FRAME_END, FRAME_START, PAUSED};
+
+fn length(type_: u32) -> u32 {
+    SelectGame::ETS2.expected_length(type_).unwrap()
+}
+
+#[test]
+fn corrupt_packets_are_dropped_and_counted() {
+    let mut parser = PacketParser::new(SelectGame::ETS2);
+
+    let mut buffers = [
+        TelemetryBuffer::new(true, FRAME_START, length(FRAME_START), 0, 0),
+        // wrong length for the type
+        TelemetryBuffer::new(true, FRAME_END, 12, 1, 10),
+        // unknown type
+        TelemetryBuffer::new(true, 99, 0, 1, 10),
+        TelemetryBuffer::new(true, FRAME_END, length(FRAME_END), 1, 10),
+        // duplicate and out of order ids
+        TelemetryBuffer::new(true, PAUSED, 0, 1, 20),
+        TelemetryBuffer::new(true, PAUSED, 0, 0, 20),
+        TelemetryBuffer::new(false, PAUSED, 0, 2, 20),
+    ];
+
+    let forwarded: Vec<(bool, bool)> = buffers
+        .iter_mut()
+        .map(|buffer| {
+            let (is_alive, packet) = parser.validated(buffer.address());
+            (is_alive, packet.is_some())
+        })
+        .collect();
+
+    assert_eq!(
+        forwarded,
+        vec![
+            (true, true),
+            (true, false),
+            (true, false),
+            (true, true),
+            (true, false),
+            // id zero after other ids is treated as a producer restart
+            (true, true),
+            (false, true),
+        ]
+    );
+
+    let report = parser.validator().report();
+    assert_eq!(report.counters.forwarded, 4);
+    assert_eq!(report.counters.length_mismatch, 1);
+    assert_eq!(report.counters.invalid_type, 1);
+    assert_eq!(report.counters.duplicate_id, 1);
+    assert_eq!(report.counters.producer_restarts, 1);
+    assert_eq!(report.counters.dropped(), 3);
+    // the restart leaves a gap from 1 to 2
+    assert_eq!(report.counters.id_gaps, 1);
+    assert_eq!(
+        report.recent[0],
+        ValidationError::LengthMismatch {
+            id: 1,
+            type_: FRAME_END,
+            expected: 216,
+            actual: 12
+        }
+    );
+}
+
+#[test]
+fn gaps_and_time_going_backwards_are_forwarded_with_warnings() {
+    let mut parser = PacketParser::new(SelectGame::ETS2);
+    let validator = parser.validator().clone();
+
+    let mut first = TelemetryBuffer::new(true, FRAME_START, length(FRAME_START), 3, 100);
+    let mut second = TelemetryBuffer::new(true, FRAME_END, length(FRAME_END), 7, 50);
+    let mut third = TelemetryBuffer::new(true, FRAME_START, length(FRAME_START), 4, 200);
+
+    assert!(parser.validated(first.address()).1.is_some());
+    assert!(parser.validated(second.address()).1.is_some());
+    assert!(parser.validated(third.address()).1.is_none());
+
+    // clones share the report
+    let report = validator.report();
+    assert_eq!(report.counters.id_gaps, 1);
+    assert_eq!(report.counters.missing_ids, 3);
+    assert_eq!(report.counters.time_went_backwards, 1);
+    assert_eq!(report.counters.out_of_order_id, 1);
+    assert_eq!(
+        report.recent,
+        vec![
+            ValidationError::IdGap {
+                expected: 4,
+                actual: 7
+            },
+            ValidationError::TimeWentBackwards {
+                id: 7,
+                previous: 100,
+                current: 50
+            },
+            ValidationError::OutOfOrderId { id: 4, last: 7 },
+        ]
+    );
+}
+
+#[test]
+fn events_without_a_defined_layout_are_not_length_checked() {
+    const CONFIGURATION: u32 = 5;
+    const GAMEPLAY: u32 = 6;
+
+    assert_eq!(SelectGame::ETS2.expected_length(CONFIGURATION), None);
+    assert_eq!(SelectGame::ETS2.expected_length(GAMEPLAY), None);
+
+    let mut parser = PacketParser::new(SelectGame::ETS2);
+
+    // the plugin attached data the layout does not describe
+    let mut configuration = TelemetryBuffer::new(true, CONFIGURATION, 64, 0, 0);
+    let mut gameplay = TelemetryBuffer::new(true, GAMEPLAY, 12, 1, 10);
+    let mut unknown = TelemetryBuffer::new(true, 7, 0, 2, 20);
+
+    assert!(parser.validated(configuration.address()).1.is_some());
+    assert!(parser.validated(gameplay.address()).1.is_some());
+    assert!(parser.validated(unknown.address()).1.is_none());
+
+    let report = parser.validator().report();
+    assert_eq!(report.counters.forwarded, 2);
+    assert_eq!(report.counters.length_mismatch, 0);
+    assert_eq!(report.counters.invalid_type, 1);
+}
+
+#[test]
+fn ids_at_the_end_of_the_range_do_not_overflow() {
+    let mut parser = PacketParser::new(SelectGame::ETS2);
+
+    let mut buffers = [
+        TelemetryBuffer::new(true, FRAME_START, length(FRAME_START), u64::MAX - 1, 0),
+        TelemetryBuffer::new(true, FRAME_END, length(FRAME_END), u64::MAX, 10),
+        // the producer restarted
+        TelemetryBuffer::new(true, FRAME_START, length(FRAME_START), 0, 20),
+    ];
+    for buffer in buffers.iter_mut() {
+        assert!(parser.validated(buffer.address()).1.is_some());
+    }
+
+    let report = parser.validator().report();
+    assert_eq!(report.counters.forwarded, 3);
+    assert_eq!(report.counters.producer_restarts, 1);
+    assert_eq!(report.counters.id_gaps, 0);
+}
@@ -0,0 +1,274 @@
+#![cfg(target_os = "linux")]
+
+use control::queue::unbounded;
+use control::telemetry::{Recorder, ReplaySpeed, SelectGame, Telemetry, TelemetryReader};
+use control::{Input, InputHeader, ServiceType, Services};
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::time::Instant;
+
+mod emulation_start;
+
+use emulation_start::ETS2Emulation;
+
+fn recording_path(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("control_{}_{}.cktr", test, std::process::id()))
+}
+
+// record an emulated session, returns the packets passed on by the recorder
+fn record(test: &str, frames: &str) -> (PathBuf, Vec<Box<dyn Input + Send>>) {
+    let name = format!("ControlKit{}{}", test, std::process::id());
+    let path = recording_path(test);
+
+    let (mut child, reader) = ETS2Emulation::start_named_with_stdout(&name, &["--frames", frames]);
+    let mut lines = reader.lines();
+    lines.next().unwrap().unwrap();
+
+    let (tx, rx) = unbounded();
+
+    let mut recorder = Recorder::new(&path, SelectGame::ETS2);
+    recorder.set_output_transmitter(tx);
+
+    let mut ets2_telemetry = Telemetry::via_shared_memory_named(SelectGame::ETS2, &name);
+    ets2_telemetry.set_transmitter(recorder.get_input_transmitter());
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::RecordInputs, Box::new(recorder))
+        .unwrap();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(ets2_telemetry))
+        .unwrap();
+
+    lines.for_each(drop);
+    cap_sess.block_until_telemetry_finished().unwrap();
+    cap_sess.stop_all_services().unwrap();
+    child.wait().unwrap();
+
+    (path, rx.iter().collect())
+}
+
+fn replay(path: &PathBuf, speed: ReplaySpeed) -> Vec<Box<dyn Input + Send>> {
+    let (tx, rx) = unbounded();
+
+    let mut replay = Telemetry::via_replay(path, speed);
+    replay.set_transmitter(tx);
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(replay))
+        .unwrap();
+    cap_sess.block_until_telemetry_finished().unwrap();
+
+    rx.iter().collect()
+}
+
+#[test]
+fn replay_reproduces_recorded_packets() {
+    let (path, recorded) = record("Reproduce", "100");
+
+    assert_eq!(recorded.len(), 202);
+    assert_eq!(
+        TelemetryReader::open(&path).unwrap().game().code(),
+        SelectGame::ETS2.code()
+    );
+
+    let replayed = replay(&path, ReplaySpeed::AsFastAsPossible);
+
+    assert_eq!(replayed.len(), recorded.len());
+    for (original, replayed) in recorded.iter().zip(replayed.iter()) {
+        // replay is a new source, only the capture stamp differs
+        let header = InputHeader {
+            capture: original.capture(),
+            ..replayed.header()
+        };
+        assert_eq!(original.header(), header);
+        assert_eq!(original.payload(), replayed.payload());
+        assert!(original.event_type() == replayed.event_type());
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn replay_follows_recorded_timing() {
+    // 30 frames of 16.667 ms game time, half a second
+    let (path, recorded) = record("Timing", "30");
+    let duration_us = recorded.last().unwrap().header().game_time;
+    assert!(duration_us > 450_000);
+
+    let started = Instant::now();
+    replay(&path, ReplaySpeed::Accelerated(5.0));
+    let accelerated = started.elapsed().as_micros() as u64;
+
+    assert!(accelerated >= duration_us / 5);
+    assert!(accelerated < duration_us / 2);
+
+    let started = Instant::now();
+    replay(&path, ReplaySpeed::Original);
+    assert!(started.elapsed().as_micros() as u64 >= duration_us);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn replay_rejects_files_that_are_not_recordings() {
+    let path = recording_path("Invalid");
+    std::fs::write(&path, b"not a recording").unwrap();
+
+    assert!(TelemetryReader::open(&path).is_err());
+
+    let (tx, _rx) = unbounded();
+    let mut replay = Telemetry::via_replay(&path, ReplaySpeed::Original);
+    replay.set_transmitter(tx);
+
+    let mut cap_sess = Services::new();
+    assert!(cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(replay))
+        .is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn recordings_keep_the_reported_length_and_reject_oversized_payloads() {
+    use control::telemetry::ets2::{TelemetryPacket, MAX_PAYLOAD_SIZE};
+    use control::telemetry::{TelemetryWriter, RECORDING_MAGIC, RECORDING_VERSION};
+    use std::io::ErrorKind;
+
+    let path = recording_path("Length");
+
+    // the producer reported more than the packet data holds
+    let mut packet = TelemetryPacket::new();
+    packet.type_ = 2;
+    packet.id = 1;
+    packet.length = 4096;
+
+    let mut writer = TelemetryWriter::create(&path, SelectGame::ETS2).unwrap();
+    writer.write(&packet).unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+
+    let mut reader = TelemetryReader::open(&path).unwrap();
+    let replayed = reader.next_packet().unwrap().unwrap();
+    assert_eq!(replayed.header().payload_size, 4096);
+    assert_eq!(replayed.payload().len(), MAX_PAYLOAD_SIZE);
+    assert!(reader.next_packet().unwrap().is_none());
+
+    // a corrupt stored size is not allocated
+    let mut data = Vec::new();
+    data.extend_from_slice(RECORDING_MAGIC);
+    data.extend_from_slice(&RECORDING_VERSION.to_le_bytes());
+    data.extend_from_slice(&SelectGame::ETS2.code().to_le_bytes());
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&216u32.to_le_bytes());
+    data.extend_from_slice(&u32::MAX.to_le_bytes());
+    std::fs::write(&path, data).unwrap();
+
+    let mut reader = TelemetryReader::open(&path).unwrap();
+    match reader.next_packet() {
+        Err(err) => assert_eq!(err.kind(), ErrorKind::InvalidData),
+        Ok(_) => panic!("oversized payload was read"),
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn recordings_ending_within_a_record_header_are_an_error() {
+    use control::telemetry::ets2::TelemetryPacket;
+    use control::telemetry::TelemetryWriter;
+    use std::io::{ErrorKind, Write};
+
+    let path = recording_path("PartialHeader");
+
+    let mut packet = TelemetryPacket::new();
+    packet.type_ = 2;
+    packet.id = 1;
+
+    let mut writer = TelemetryWriter::create(&path, SelectGame::ETS2).unwrap();
+    writer.write(&packet).unwrap();
+    writer.flush().unwrap();
+    drop(writer);
+
+    // the recorder was killed while writing the next record header
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(&[0u8; 10]).unwrap();
+    drop(file);
+
+    let mut reader = TelemetryReader::open(&path).unwrap();
+    assert!(reader.next_packet().unwrap().is_some());
+    match reader.next_packet() {
+        Err(err) => assert_eq!(err.kind(), ErrorKind::UnexpectedEof),
+        Ok(_) => panic!("partial record header was read"),
+    }
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn restarted_replays_continue_the_capture_sequence() {
+    use control::telemetry::ets2::TelemetryPacket;
+    use control::telemetry::TelemetryWriter;
+    use control::{Backoff, CaptureSequence, Process, RestartPolicy, ServiceError};
+    use std::time::Duration;
+
+    let path = recording_path("Sequence");
+
+    let mut writer = TelemetryWriter::create(&path, SelectGame::ETS2).unwrap();
+    for (id, type_) in [(0, 4), (1, 3)] {
+        let mut packet = TelemetryPacket::new();
+        packet.type_ = type_;
+        packet.id = id;
+        writer.write(&packet).unwrap();
+    }
+    writer.flush().unwrap();
+    drop(writer);
+
+    let (tx, rx) = unbounded();
+    let sequence = CaptureSequence::new();
+    let factory = {
+        let path = path.clone();
+        move || -> Result<Box<dyn Process>, ServiceError> {
+            let mut replay = Telemetry::via_replay(&path, ReplaySpeed::AsFastAsPossible);
+            replay.set_transmitter(tx.clone());
+            replay.set_capture_sequence(sequence.clone());
+            Ok(Box::new(replay))
+        }
+    };
+
+    let mut cap_sess = Services::new();
+    let policy = RestartPolicy::Always {
+        backoff: Backoff::new(Duration::from_millis(1), Duration::from_millis(4)),
+        max_retries: Some(1),
+    };
+    cap_sess
+        .add_service_with_restart(ServiceType::TelemetryInput, policy, factory)
+        .unwrap();
+
+    let started = Instant::now();
+    while cap_sess.restarts(ServiceType::TelemetryInput) < Some(1)
+        || cap_sess.restart_pending(ServiceType::TelemetryInput)
+        || cap_sess
+            .status(ServiceType::TelemetryInput)
+            .unwrap()
+            .is_active()
+    {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        cap_sess.poll_services();
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    std::fs::remove_file(&path).unwrap();
+
+    // the restarted replay numbers its packets after the first one
+    let sequences: Vec<u64> = rx
+        .try_iter()
+        .map(|input: Box<dyn Input + Send>| input.capture().sequence)
+        .collect();
+    assert_eq!(sequences, vec![1, 2, 3, 4]);
+}
@@ -0,0 +1,229 @@
+use control::queue::unbounded;
+use control::resampling::Resampler;
+use control::screencapture::Pixels;
+use control::telemetry::ets2::{Ets2Payload, Euler, FrameEnd, Interpolate, TelemetryPacket};
+use control::userinput::{ControllerSnapshot, ControllerState};
+use control::{CaptureStamp, Input, InputType, Process, ServiceError};
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{FRAME_END, FRAME_START};
+
+// one game frame at time us, speed grows 1 m/s per 100 ms, gear changes every 50 ms
+fn frame(id: u64, time: u64, image: bool) -> Vec<Box<dyn Input + Send>> {
+    let mut start = TelemetryPacket::new();
+    start.type_ = FRAME_START;
+    start.length = 32;
+    start.id = id * 2;
+    start.time = time;
+
+    let mut end = TelemetryPacket::new();
+    end.type_ = FRAME_END;
+    end.length = 216;
+    end.id = id * 2 + 1;
+    end.time = time;
+    end.capture = CaptureStamp::new(id + 1, Duration::from_micros(time));
+    end.data.frame_end_data = FrameEnd {
+        engine_gear: 1 + (time / 50_000) as u32,
+        speed: time as f32 / 100_000.0,
+        ..Default::default()
+    };
+
+    let mut group: Vec<Box<dyn Input + Send>> = vec![Box::new(start)];
+    if image {
+        let stamp = CaptureStamp::new(id + 1, Duration::from_micros(time));
+        group.push(Box::new(Pixels::new(vec![0; 4], 1, 1).with_capture(stamp)));
+    }
+    group.push(Box::new(end));
+    group
+}
+
+fn frame_end(input: &dyn Input) -> FrameEnd {
+    *Ets2Payload::from_input(input).unwrap().frame_end().unwrap()
+}
+
+#[test]
+fn groups_are_resampled_on_a_fixed_game_clock() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let mut resampler = Resampler::new(10.0).unwrap();
+    resampler.set_output_transmitter(out_transmitter);
+    let tx = resampler.get_input_transmitter();
+
+    // 60 fps for half a second, an image every fourth frame
+    for id in 0..30 {
+        tx.send(frame(id, id * 16_667, id % 4 == 1)).unwrap();
+    }
+    // one second gap, the clock starts over
+    tx.send(frame(30, 2_000_000, false)).unwrap();
+    tx.send(frame(31, 2_016_667, false)).unwrap();
+
+    resampler.start().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    resampler.stop().unwrap();
+    resampler.join().unwrap();
+
+    let samples: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.try_iter().collect();
+
+    let times: Vec<u64> = samples
+        .iter()
+        .map(|sample| sample.last().unwrap().header().game_time)
+        .collect();
+    assert_eq!(
+        times,
+        vec![0, 100_000, 200_000, 300_000, 400_000, 2_000_000]
+    );
+
+    for sample in samples.iter() {
+        let end = frame_end(sample.last().unwrap().as_ref());
+        let time = sample.last().unwrap().header().game_time;
+
+        // speed is linear in time, interpolation is exact
+        assert!((end.speed - time as f32 / 100_000.0).abs() < 1e-4);
+        assert_eq!(sample.first().unwrap().header().game_time, time);
+    }
+
+    // gear is held from the frame before the tick: 100 ms lies between 83.3 ms (gear 2) and 100.0 ms
+    assert_eq!(
+        frame_end(samples[1].last().unwrap().as_ref()).engine_gear,
+        2
+    );
+    // 200 ms lies between 183.3 ms (gear 4) and 200.0 ms
+    assert_eq!(
+        frame_end(samples[2].last().unwrap().as_ref()).engine_gear,
+        4
+    );
+
+    // no image before the first frame with an image
+    assert_eq!(samples[0].len(), 2);
+    // latest image at 100 ms is the one of frame 5 (83.3 ms)
+    let image = &samples[1][1];
+    assert!(matches!(image.input_type(), InputType::Image));
+    assert_eq!(image.capture().sequence, 6);
+    // images are held until a newer one arrives
+    assert_eq!(samples[5][1].capture().sequence, 30);
+
+    // every sample is a capture of its own
+    for (index, sample) in samples.iter().enumerate() {
+        assert_eq!(sample.first().unwrap().capture().sequence, index as u64 + 1);
+        assert_eq!(sample.last().unwrap().capture().sequence, index as u64 + 1);
+    }
+}
+
+#[test]
+fn upsampled_frames_get_a_capture_sequence_per_sample() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    // 200 Hz between two frames 20 ms apart
+    let mut resampler = Resampler::new(200.0).unwrap();
+    resampler.set_output_transmitter(out_transmitter);
+    let tx = resampler.get_input_transmitter();
+    tx.send(frame(0, 0, false)).unwrap();
+    tx.send(frame(1, 20_000, false)).unwrap();
+
+    resampler.start().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    resampler.stop().unwrap();
+    resampler.join().unwrap();
+
+    let samples: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.try_iter().collect();
+    let stamps: Vec<(u64, u64, u64)> = samples
+        .iter()
+        .map(|sample| {
+            let end = sample.last().unwrap();
+            (
+                end.header().game_time,
+                end.header().sequence,
+                end.capture().sequence,
+            )
+        })
+        .collect();
+
+    // the ticks between the frames are held from frame 0 (packet id 1)
+    assert_eq!(
+        stamps,
+        vec![
+            (0, 1, 1),
+            (5_000, 1, 2),
+            (10_000, 1, 3),
+            (15_000, 1, 4),
+            (20_000, 3, 5)
+        ]
+    );
+}
+
+#[test]
+fn controller_snapshots_are_held_like_images() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let mut resampler = Resampler::new(20.0).unwrap();
+    resampler.set_output_transmitter(out_transmitter);
+    let tx = resampler.get_input_transmitter();
+
+    // the first four frames carry a snapshot, the throttle tells them apart
+    for id in 0..7 {
+        let time = id * 16_667;
+        let mut group = frame(id, time, false);
+        if id < 4 {
+            let state = ControllerState {
+                throttle: id as f32 / 10.0,
+                ..Default::default()
+            };
+            let capture = CaptureStamp::new(id + 1, Duration::from_micros(time));
+            let snapshot = ControllerSnapshot::new(state, id * 2, time, capture);
+            group.insert(1, Box::new(snapshot));
+        }
+        tx.send(group).unwrap();
+    }
+
+    resampler.start().unwrap();
+    thread::sleep(Duration::from_millis(50));
+    resampler.stop().unwrap();
+    resampler.join().unwrap();
+
+    let samples: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.try_iter().collect();
+    let frames: Vec<u64> = samples
+        .iter()
+        .map(|sample| {
+            ControllerSnapshot::from_input(sample[1].as_ref())
+                .unwrap()
+                .frame_id
+        })
+        .collect();
+
+    // 50 ms lies between frame 2 and 3, 100 ms after frame 3 which is the latest snapshot
+    assert_eq!(frames, vec![0, 4, 6]);
+}
+
+#[test]
+fn resampling_rate_must_be_positive_and_finite() {
+    for rate in [0.0, -10.0, f64::NAN, f64::INFINITY].iter() {
+        assert!(matches!(
+            Resampler::new(*rate),
+            Err(ServiceError::InvalidConfig(_))
+        ));
+    }
+}
+
+#[test]
+fn orientation_takes_the_short_way_around() {
+    let from = Euler {
+        heading: 0.95,
+        pitch: 0.1,
+        roll: 0.0,
+    };
+    let to = Euler {
+        heading: 0.05,
+        pitch: 0.2,
+        roll: 0.0,
+    };
+
+    let half = from.interpolate(&to, 0.5);
+    assert!(half.heading.abs() < 1e-5 || (half.heading - 1.0).abs() < 1e-5);
+    assert!((half.pitch - 0.15).abs() < 1e-5);
+
+    let quarter = from.interpolate(&to, 0.25);
+    assert!((quarter.heading - 0.975).abs() < 1e-5);
+}
@@ -0,0 +1,241 @@
+use control::{Backoff, Process, RestartPolicy, ServiceError, ServiceState, ServiceType, Services};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// thread ends right after start, with an error when failing
+struct EndingProcess {
+    failing: bool,
+    finished: bool,
+}
+
+impl Process for EndingProcess {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        self.finished = true;
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        self.finished = false;
+        if self.failing {
+            Err(ServiceError::Disconnected(String::from(
+                "telemetry producer",
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+fn factory(
+    failing: bool,
+    created: &Arc<Mutex<u32>>,
+) -> impl FnMut() -> Result<Box<dyn Process>, ServiceError> {
+    let created = Arc::clone(created);
+    move || {
+        *created.lock().unwrap() += 1;
+        Ok(Box::new(EndingProcess {
+            failing,
+            finished: false,
+        }))
+    }
+}
+
+// thread ends with an error once the lifetime passed
+struct TimedProcess {
+    lifetime: Duration,
+    started: Option<Instant>,
+}
+
+impl Process for TimedProcess {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        self.started = Some(Instant::now());
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        self.started = None;
+        Err(ServiceError::Disconnected(String::from(
+            "telemetry producer",
+        )))
+    }
+
+    fn is_finished(&self) -> bool {
+        self.started
+            .is_some_and(|started| started.elapsed() >= self.lifetime)
+    }
+}
+
+fn backoff() -> Backoff {
+    Backoff::new(Duration::from_millis(1), Duration::from_millis(4))
+}
+
+// polls until the service is down without a pending restart
+fn poll_until_settled(cap_sess: &mut Services, service_type: ServiceType) {
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_secs(5) {
+        cap_sess.poll_services();
+        if !cap_sess.status(service_type).unwrap().is_active()
+            && !cap_sess.restart_pending(service_type)
+        {
+            return;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    panic!("service did not settle");
+}
+
+#[test]
+fn backoff_doubles_up_to_max() {
+    let backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(500));
+
+    assert_eq!(backoff.delay(0), Duration::from_millis(100));
+    assert_eq!(backoff.delay(1), Duration::from_millis(200));
+    assert_eq!(backoff.delay(2), Duration::from_millis(400));
+    assert_eq!(backoff.delay(3), Duration::from_millis(500));
+    assert_eq!(backoff.delay(u32::MAX), Duration::from_millis(500));
+}
+
+#[test]
+fn on_failure_restarts_until_max_retries() {
+    let created = Arc::new(Mutex::new(0));
+    let mut cap_sess = Services::new();
+
+    let policy = RestartPolicy::OnFailure {
+        backoff: backoff(),
+        max_retries: Some(3),
+    };
+    cap_sess
+        .add_service_with_restart(ServiceType::TelemetryInput, policy, factory(true, &created))
+        .unwrap();
+
+    poll_until_settled(&mut cap_sess, ServiceType::TelemetryInput);
+
+    assert_eq!(*created.lock().unwrap(), 4);
+    assert_eq!(cap_sess.restarts(ServiceType::TelemetryInput), Some(3));
+    assert_eq!(
+        cap_sess.status(ServiceType::TelemetryInput),
+        Some(ServiceState::Failed)
+    );
+    assert_eq!(cap_sess.failures().len(), 4);
+}
+
+#[test]
+fn on_failure_does_not_restart_a_clean_exit() {
+    let created = Arc::new(Mutex::new(0));
+    let mut cap_sess = Services::new();
+
+    let policy = RestartPolicy::OnFailure {
+        backoff: backoff(),
+        max_retries: None,
+    };
+    cap_sess
+        .add_service_with_restart(
+            ServiceType::TelemetryInput,
+            policy,
+            factory(false, &created),
+        )
+        .unwrap();
+
+    poll_until_settled(&mut cap_sess, ServiceType::TelemetryInput);
+
+    assert_eq!(*created.lock().unwrap(), 1);
+    assert_eq!(
+        cap_sess.status(ServiceType::TelemetryInput),
+        Some(ServiceState::Stopped)
+    );
+}
+
+#[test]
+fn always_restarts_a_clean_exit_until_stopped() {
+    let created = Arc::new(Mutex::new(0));
+    let mut cap_sess = Services::new();
+
+    let policy = RestartPolicy::Always {
+        backoff: backoff(),
+        max_retries: None,
+    };
+    cap_sess
+        .add_service_with_restart(
+            ServiceType::TelemetryInput,
+            policy,
+            factory(false, &created),
+        )
+        .unwrap();
+
+    let started = Instant::now();
+    while cap_sess.restarts(ServiceType::TelemetryInput) < Some(2) {
+        assert!(started.elapsed() < Duration::from_secs(5));
+        cap_sess.poll_services();
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    // stopping cancels a pending restart
+    while !cap_sess.restart_pending(ServiceType::TelemetryInput) {
+        cap_sess.poll_services();
+    }
+    cap_sess.stop_service(ServiceType::TelemetryInput).unwrap();
+    let restarts = cap_sess.restarts(ServiceType::TelemetryInput);
+
+    thread::sleep(Duration::from_millis(10));
+    cap_sess.poll_services();
+
+    assert_eq!(cap_sess.restarts(ServiceType::TelemetryInput), restarts);
+    assert!(cap_sess.failures().is_empty());
+    assert_eq!(
+        cap_sess.status(ServiceType::TelemetryInput),
+        Some(ServiceState::Stopped)
+    );
+}
+
+#[test]
+fn restarts_are_reset_once_the_service_stayed_running() {
+    let created = Arc::new(Mutex::new(0));
+    let mut cap_sess = Services::new();
+
+    // the third process runs longer than the max backoff, every other one fails right away
+    let counter = Arc::clone(&created);
+    let factory = move || {
+        let mut created = counter.lock().unwrap();
+        *created += 1;
+        let lifetime = if *created == 3 {
+            Duration::from_millis(20)
+        } else {
+            Duration::from_millis(0)
+        };
+        Ok(Box::new(TimedProcess {
+            lifetime,
+            started: None,
+        }) as Box<dyn Process>)
+    };
+
+    let policy = RestartPolicy::OnFailure {
+        backoff: backoff(),
+        max_retries: Some(2),
+    };
+    cap_sess
+        .add_service_with_restart(ServiceType::TelemetryInput, policy, factory)
+        .unwrap();
+
+    poll_until_settled(&mut cap_sess, ServiceType::TelemetryInput);
+
+    // two restarts before and two after the stable run
+    assert_eq!(*created.lock().unwrap(), 5);
+    assert_eq!(cap_sess.restarts(ServiceType::TelemetryInput), Some(2));
+    assert_eq!(
+        cap_sess.status(ServiceType::TelemetryInput),
+        Some(ServiceState::Failed)
+    );
+}
@@ -0,0 +1,102 @@
+use control::screencapture::Pixels;
+use control::telemetry::{PacketParser, SelectGame};
+use control::{ImageEncoding, Input, Serialized, ServiceError};
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{TelemetryBuffer, FRAME_END, PAUSED};
+
+#[test]
+fn telemetry_frame_end_serializes_to_json() {
+    let parser = PacketParser::new(SelectGame::ETS2);
+
+    let mut buffer = TelemetryBuffer::new(true, FRAME_END, 216, 7, 5000);
+    // engine_gear, speed and engine_rpm are the first fields of frame_end
+    buffer.write_data(0, &3u32.to_ne_bytes());
+    buffer.write_data(4, &12.5f32.to_ne_bytes());
+    buffer.write_data(8, &1500.0f32.to_ne_bytes());
+
+    let packet = parser.data(buffer.address()).1;
+
+    let json = match packet.serialize(ImageEncoding::Png).unwrap() {
+        Serialized::Json(json) => json,
+        _ => panic!("telemetry should serialize to json"),
+    };
+
+    assert_eq!(json["id"], 7);
+    assert_eq!(json["event"], "FrameEnd");
+    assert_eq!(json["time"], 5000);
+    assert_eq!(json["frame_end"]["engine_gear"], 3);
+    assert_eq!(json["frame_end"]["speed"], 12.5);
+    assert_eq!(json["frame_end"]["engine_rpm"], 1500.0);
+    assert_eq!(json["frame_end"]["world_placement"]["position"]["x"], 0.0);
+    assert!(json["frame_end"].get("padding").is_none());
+    assert!(json.get("frame_start").is_none());
+}
+
+#[test]
+fn telemetry_event_without_data_serializes_header_only() {
+    let parser = PacketParser::new(SelectGame::ETS2);
+    let mut buffer = TelemetryBuffer::new(true, PAUSED, 0, 8, 6000);
+
+    let packet = parser.data(buffer.address()).1;
+
+    match packet.serialize(ImageEncoding::Png).unwrap() {
+        Serialized::Json(json) => {
+            assert_eq!(json["event"], "Paused");
+            assert!(json.get("frame_end").is_none());
+        }
+        _ => panic!("telemetry should serialize to json"),
+    }
+}
+
+#[test]
+fn pixels_serialize_to_encoded_image() {
+    // 2x2 BGRA with a padded row pitch of 12 bytes
+    let bgra = vec![
+        255, 0, 0, 255, 0, 255, 0, 255, 9, 9, 9, 9, //
+        0, 0, 255, 255, 255, 255, 255, 255, 9, 9, 9, 9,
+    ];
+    let pixels = Pixels::new(bgra, 2, 2);
+
+    assert_eq!(
+        pixels.to_rgb().unwrap(),
+        vec![0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255]
+    );
+
+    let data = match pixels.serialize(ImageEncoding::Png).unwrap() {
+        Serialized::Image { encoding, data } => {
+            assert_eq!(encoding, ImageEncoding::Png);
+            data
+        }
+        _ => panic!("pixels should serialize to an image"),
+    };
+
+    let decoded = image::load_from_memory(&data).unwrap().to_rgb8();
+    assert_eq!(decoded.dimensions(), (2, 2));
+    assert_eq!(decoded.into_raw(), pixels.to_rgb().unwrap());
+
+    match pixels.serialize(ImageEncoding::Jpeg(90)).unwrap() {
+        Serialized::Image { data, .. } => assert_eq!(&data[0..2], &[0xff, 0xd8]),
+        _ => panic!("pixels should serialize to an image"),
+    }
+}
+
+#[test]
+fn malformed_pixels_fail_to_serialize() {
+    let malformed = [
+        // fewer bytes than rows
+        Pixels::new(vec![0; 3], 2, 4),
+        // rows shorter than the width
+        Pixels::new(vec![0; 14], 2, 2),
+    ];
+
+    for pixels in malformed.iter() {
+        for encoding in [ImageEncoding::Png, ImageEncoding::Jpeg(90)].iter() {
+            assert!(matches!(
+                pixels.serialize(*encoding),
+                Err(ServiceError::Serialization(_))
+            ));
+        }
+    }
+}
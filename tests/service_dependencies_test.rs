@@ -0,0 +1,189 @@
+use control::{DependencyGraph, Process, ServiceError, ServiceState, ServiceType, Services};
+use std::sync::{Arc, Mutex};
+
+// records the calls made by Services on a process
+struct RecordingProcess {
+    service_type: ServiceType,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingProcess {
+    fn push(&self, call: &str) {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("{} {:?}", call, self.service_type));
+    }
+}
+
+impl Process for RecordingProcess {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        self.push("start");
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        self.push("stop");
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    // no thread, runs until stopped
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+fn register(cap_sess: &mut Services, calls: &Arc<Mutex<Vec<String>>>, service_type: ServiceType) {
+    let process = RecordingProcess {
+        service_type,
+        calls: Arc::clone(calls),
+    };
+    cap_sess
+        .register_service(service_type, Box::new(process))
+        .unwrap();
+}
+
+#[test]
+fn start_all_orders_dependencies_first_and_stop_all_reverses_it() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mut cap_sess = Services::new();
+
+    // registration order does not matter
+    for service_type in [
+        ServiceType::TelemetryInput,
+        ServiceType::ScreenCaptureInput,
+        ServiceType::SynchronizeInputs,
+        ServiceType::DatasetOutput,
+    ] {
+        register(&mut cap_sess, &calls, service_type);
+    }
+    assert_eq!(
+        cap_sess.status(ServiceType::DatasetOutput),
+        Some(ServiceState::Registered)
+    );
+
+    cap_sess.start_all_services().unwrap();
+    cap_sess.stop_all_services().unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "start SynchronizeInputs",
+            "start TelemetryInput",
+            "start ScreenCaptureInput",
+            "start DatasetOutput",
+            "stop ScreenCaptureInput",
+            "stop TelemetryInput",
+            "stop SynchronizeInputs",
+            "stop DatasetOutput",
+        ]
+    );
+}
+
+#[test]
+fn stopping_a_service_stops_its_dependents_in_data_flow_order() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mut cap_sess = Services::new();
+
+    // telemetry is passed through the recorder
+    cap_sess
+        .declare_dependency(ServiceType::TelemetryInput, ServiceType::RecordInputs)
+        .unwrap();
+
+    for service_type in [
+        ServiceType::SynchronizeInputs,
+        ServiceType::RecordInputs,
+        ServiceType::TelemetryInput,
+        ServiceType::DatasetOutput,
+    ] {
+        register(&mut cap_sess, &calls, service_type);
+    }
+    cap_sess.start_all_services().unwrap();
+    calls.lock().unwrap().clear();
+
+    cap_sess
+        .stop_service(ServiceType::SynchronizeInputs)
+        .unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "stop TelemetryInput",
+            "stop RecordInputs",
+            "stop SynchronizeInputs",
+            // receives the output of synchronization, drains its last groups
+            "stop DatasetOutput",
+        ]
+    );
+    assert_eq!(
+        cap_sess.status(ServiceType::DatasetOutput),
+        Some(ServiceState::Stopped)
+    );
+}
+
+#[test]
+fn cycles_are_refused() {
+    let mut graph = DependencyGraph::default();
+
+    assert!(matches!(
+        graph.add(ServiceType::DatasetOutput, ServiceType::TelemetryInput),
+        Err(ServiceError::DependencyCycle(cycle)) if cycle == vec![
+            ServiceType::DatasetOutput,
+            ServiceType::TelemetryInput,
+            ServiceType::SynchronizeInputs,
+            ServiceType::DatasetOutput,
+        ]
+    ));
+    assert!(matches!(
+        graph.add(ServiceType::UserInput, ServiceType::UserInput),
+        Err(ServiceError::DependencyCycle(_))
+    ));
+    assert_eq!(
+        graph.dependencies(ServiceType::DatasetOutput),
+        vec![ServiceType::SynchronizeInputs, ServiceType::ResampleInputs]
+    );
+
+    // data would flow from the dataset back to synchronization
+    assert!(matches!(
+        graph.add(ServiceType::DatasetOutput, ServiceType::SynchronizeInputs),
+        Err(ServiceError::DependencyCycle(_))
+    ));
+}
+
+#[test]
+fn add_service_refuses_a_dependency_that_is_not_running() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let mut cap_sess = Services::new();
+
+    register(&mut cap_sess, &calls, ServiceType::SynchronizeInputs);
+
+    let process = RecordingProcess {
+        service_type: ServiceType::TelemetryInput,
+        calls: Arc::clone(&calls),
+    };
+    assert!(matches!(
+        cap_sess.add_service(ServiceType::TelemetryInput, Box::new(process)),
+        Err(ServiceError::DependencyNotRunning {
+            service: ServiceType::TelemetryInput,
+            dependency: ServiceType::SynchronizeInputs
+        })
+    ));
+
+    // a sink can not read from a synchronization that is not running
+    let process = RecordingProcess {
+        service_type: ServiceType::DatasetOutput,
+        calls: Arc::clone(&calls),
+    };
+    assert!(matches!(
+        cap_sess.add_service(ServiceType::DatasetOutput, Box::new(process)),
+        Err(ServiceError::DependencyNotRunning {
+            service: ServiceType::DatasetOutput,
+            dependency: ServiceType::SynchronizeInputs
+        })
+    ));
+    assert!(calls.lock().unwrap().is_empty());
+}
@@ -0,0 +1,288 @@
+use control::queue::unbounded;
+use control::telemetry::{ReplaySpeed, SelectGame, Telemetry, RECORDING_MAGIC, RECORDING_VERSION};
+use control::{Process, ServiceError, ServiceState, ServiceType, Services};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// records the calls made by Services on a process
+struct RecordingProcess {
+    name: &'static str,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl Process for RecordingProcess {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("start {}", self.name));
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("stop {}", self.name));
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push(format!("join {}", self.name));
+        Ok(())
+    }
+
+    // no thread, runs until stopped
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn services_stop_all_in_reverse_start_order() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+
+    let mut cap_sess = Services::new();
+
+    for (service_type, name) in [
+        (ServiceType::SynchronizeInputs, "sync"),
+        (ServiceType::TelemetryInput, "telemetry"),
+    ] {
+        let process = RecordingProcess {
+            name,
+            calls: Arc::clone(&calls),
+        };
+        cap_sess
+            .add_service(service_type, Box::new(process))
+            .unwrap();
+    }
+
+    cap_sess.stop_all_services().unwrap();
+
+    assert_eq!(
+        *calls.lock().unwrap(),
+        vec![
+            "start sync",
+            "start telemetry",
+            "stop telemetry",
+            "join telemetry",
+            "stop sync",
+            "join sync"
+        ]
+    );
+}
+
+#[test]
+fn services_stop_service_not_active() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+
+    let mut cap_sess = Services::new();
+
+    let process = RecordingProcess {
+        name: "sync",
+        calls: Arc::clone(&calls),
+    };
+    cap_sess
+        .add_service(ServiceType::SynchronizeInputs, Box::new(process))
+        .unwrap();
+
+    assert!(matches!(
+        cap_sess.stop_service(ServiceType::TelemetryInput),
+        Err(ServiceError::NotActive)
+    ));
+    assert!(matches!(
+        cap_sess.block_until_telemetry_finished(),
+        Err(ServiceError::NotActive)
+    ));
+
+    cap_sess
+        .stop_service(ServiceType::SynchronizeInputs)
+        .unwrap();
+
+    assert!(matches!(
+        cap_sess.stop_service(ServiceType::SynchronizeInputs),
+        Err(ServiceError::NotActive)
+    ));
+    assert_eq!(calls.lock().unwrap().len(), 3);
+}
+
+// fails to start with the given error
+struct FailingProcess {
+    error: Option<ServiceError>,
+}
+
+impl Process for FailingProcess {
+    fn start(&mut self) -> Result<(), ServiceError> {
+        Err(self.error.take().unwrap())
+    }
+
+    fn stop(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    fn join(&mut self) -> Result<(), ServiceError> {
+        Ok(())
+    }
+
+    fn is_finished(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn services_start_failure_keeps_the_source_chain() {
+    let mut cap_sess = Services::new();
+
+    let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "recording.cktr");
+    let process = FailingProcess {
+        error: Some(ServiceError::from(io_error)),
+    };
+
+    let err = cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(process))
+        .unwrap_err();
+
+    assert_eq!(err.to_string(), "failed to start service TelemetryInput");
+
+    // composes with Box<dyn Error> applications
+    let boxed: Box<dyn Error> = Box::new(err);
+    let mut chain = Vec::new();
+    let mut source = Some(boxed.as_ref());
+    while let Some(err) = source {
+        chain.push(err.to_string());
+        source = err.source();
+    }
+
+    assert_eq!(
+        chain,
+        vec![
+            "failed to start service TelemetryInput",
+            "i/o error",
+            "recording.cktr"
+        ]
+    );
+    assert!(matches!(
+        cap_sess.stop_service(ServiceType::TelemetryInput),
+        Err(ServiceError::NotActive)
+    ));
+}
+
+#[test]
+fn services_poll_reports_which_service_died_and_why() {
+    // a recording cut off in the middle of the first payload
+    let path = std::env::temp_dir().join(format!("control_truncated_{}.cktr", std::process::id()));
+    let mut data = Vec::new();
+    data.extend_from_slice(RECORDING_MAGIC);
+    data.extend_from_slice(&RECORDING_VERSION.to_le_bytes());
+    data.extend_from_slice(&SelectGame::ETS2.code().to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&2u32.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.extend_from_slice(&216u32.to_le_bytes());
+    data.extend_from_slice(&216u32.to_le_bytes());
+    data.extend_from_slice(&[0u8; 10]);
+    std::fs::write(&path, data).unwrap();
+
+    let (tx, _rx) = unbounded();
+    let mut replay = Telemetry::via_replay(&path, ReplaySpeed::AsFastAsPossible);
+    replay.set_transmitter(tx);
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(replay))
+        .unwrap();
+
+    let started = Instant::now();
+    let mut failed = Vec::new();
+    while failed.is_empty() && started.elapsed() < Duration::from_secs(5) {
+        failed = cap_sess.poll_services();
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    assert_eq!(failed, vec![ServiceType::TelemetryInput]);
+    assert_eq!(
+        cap_sess.status(ServiceType::TelemetryInput),
+        Some(ServiceState::Failed)
+    );
+    match cap_sess.failure(ServiceType::TelemetryInput) {
+        Some(ServiceError::Io(err)) => {
+            assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof)
+        }
+        other => panic!("unexpected failure {:?}", other),
+    }
+    assert_eq!(cap_sess.failures().len(), 1);
+
+    // the failed service is no longer running
+    assert!(matches!(
+        cap_sess.block_until_telemetry_finished(),
+        Err(ServiceError::NotActive)
+    ));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn services_status_follows_the_lifecycle() {
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    let recording = |name| {
+        Box::new(RecordingProcess {
+            name,
+            calls: Arc::clone(&calls),
+        })
+    };
+
+    let mut cap_sess = Services::new();
+    assert_eq!(cap_sess.status(ServiceType::SynchronizeInputs), None);
+
+    cap_sess
+        .add_service(ServiceType::SynchronizeInputs, recording("sync"))
+        .unwrap();
+    assert_eq!(
+        cap_sess.status(ServiceType::SynchronizeInputs),
+        Some(ServiceState::Running)
+    );
+    assert!(matches!(
+        cap_sess.add_service(ServiceType::SynchronizeInputs, recording("sync")),
+        Err(ServiceError::AlreadyActive)
+    ));
+
+    let failing = FailingProcess {
+        error: Some(ServiceError::TransmitterNotSet),
+    };
+    assert!(cap_sess
+        .add_service(ServiceType::TelemetryInput, Box::new(failing))
+        .is_err());
+
+    cap_sess
+        .stop_service(ServiceType::SynchronizeInputs)
+        .unwrap();
+    assert_eq!(
+        cap_sess.list(),
+        vec![
+            (ServiceType::SynchronizeInputs, ServiceState::Stopped),
+            (ServiceType::TelemetryInput, ServiceState::Failed)
+        ]
+    );
+
+    // stopped and failed services can be started again, telemetry needs synchronization running
+    cap_sess
+        .add_service(ServiceType::SynchronizeInputs, recording("sync"))
+        .unwrap();
+    cap_sess
+        .add_service(ServiceType::TelemetryInput, recording("telemetry"))
+        .unwrap();
+    cap_sess.stop_all_services().unwrap();
+    assert_eq!(
+        cap_sess.list(),
+        vec![
+            (ServiceType::SynchronizeInputs, ServiceState::Stopped),
+            (ServiceType::TelemetryInput, ServiceState::Stopped)
+        ]
+    );
+    assert!(!ServiceState::Stopped.can_transition_to(ServiceState::Running));
+}
@@ -1,3 +1,6 @@
+// requires TelemetryEmulation.exe and the win32 capture backends
+#![cfg(windows)]
+
 use control::screencapture::ScreenCapture;
 use control::synchronization::Synchronization;
 use control::telemetry::{SelectGame, Telemetry};
@@ -0,0 +1,97 @@
+use control::queue::{unbounded, InputQueueConfig, QueuePolicy};
+use control::synchronization::{NearestCapture, PauseGate, Synchronization};
+use control::telemetry::EventGame;
+use control::{Input, InputType, Process};
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{image, packet, FRAME_END, FRAME_START, STARTED};
+
+fn capture_ms(input: &dyn Input) -> u64 {
+    input.capture().time.as_millis() as u64
+}
+
+#[test]
+fn late_images_are_matched_to_the_nearest_frame() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let policy = NearestCapture::new(Duration::from_millis(10));
+    let report = policy.report_handle();
+
+    // every queued image has to reach the alignment
+    let mut sync = Synchronization::with_config(
+        &InputQueueConfig {
+            image: QueuePolicy::block(16),
+            ..InputQueueConfig::default()
+        },
+        Box::new(PauseGate::new(policy)),
+    );
+    sync.set_output_transmitter(out_transmitter);
+    let tx = sync.get_input_transmitter();
+
+    let inputs = vec![
+        packet(STARTED, 0, 90),
+        packet(FRAME_START, 1, 100),
+        packet(FRAME_END, 2, 101),
+        packet(FRAME_START, 3, 116),
+        // captured during the first frame, arrives after the second one started
+        image(1, 102),
+        // further away from the first frame than the image before
+        image(2, 105),
+        packet(FRAME_END, 4, 117),
+        image(3, 150),
+        packet(FRAME_START, 5, 132),
+        packet(FRAME_END, 6, 133),
+        packet(FRAME_START, 7, 180),
+        packet(FRAME_END, 8, 181),
+    ];
+    for input in inputs {
+        tx.send(input).unwrap();
+    }
+
+    sync.start().unwrap();
+    thread::sleep(Duration::from_millis(100));
+    sync.stop().unwrap();
+    sync.join().unwrap();
+
+    let groups: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.try_iter().collect();
+    let times: Vec<Vec<u64>> = groups
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .map(|input| capture_ms(input.as_ref()))
+                .collect()
+        })
+        .collect();
+
+    assert_eq!(
+        times,
+        vec![
+            vec![100, 102, 101],
+            vec![116, 117],
+            vec![132, 133],
+            vec![180, 181],
+        ]
+    );
+    assert!(matches!(groups[0][1].input_type(), InputType::Image));
+    for group in groups.iter() {
+        assert!(group.first().unwrap().event_type() == EventGame::FrameStartEvent);
+        assert!(group.last().unwrap().event_type() == EventGame::FrameEndEvent);
+    }
+
+    let report = report.report();
+    assert_eq!(report.counters.frames, 4);
+    assert_eq!(report.counters.matched_images, 1);
+    assert_eq!(report.counters.unmatched_images, 2);
+    assert_eq!(report.counters.incomplete_frames, 0);
+
+    let unmatched: Vec<u64> = report
+        .recent_unmatched
+        .iter()
+        .map(|header| header.sequence)
+        .collect();
+    assert_eq!(unmatched, vec![2, 3]);
+}
@@ -0,0 +1,122 @@
+use control::queue::unbounded;
+use control::screencapture::Pixels;
+use control::synchronization::Synchronization;
+use control::telemetry::{EventGame, PacketParser, SelectGame};
+use control::{Input, InputHeader, InputType, Process, ServiceError, ServiceType, Services};
+use std::thread;
+use std::time::Duration;
+
+mod telemetry_buffer;
+
+use telemetry_buffer::{TelemetryBuffer, FRAME_END, FRAME_START, PAUSED, STARTED};
+
+fn packet(type_: u32, id: u64) -> Box<dyn Input + Send> {
+    let parser = PacketParser::new(SelectGame::ETS2);
+    let mut buffer = TelemetryBuffer::new(true, type_, 0, id, id * 10);
+
+    parser.data(buffer.address()).1
+}
+
+#[test]
+fn packet_parser_reads_header_and_alive_flag() {
+    let parser = PacketParser::new(SelectGame::ETS2);
+
+    let mut buffer = TelemetryBuffer::new(true, FRAME_END, 216, 42, 1000);
+    let pair = parser.data(buffer.address());
+
+    assert!(pair.0);
+    let header = pair.1.header();
+    assert_eq!(
+        header,
+        InputHeader {
+            source: InputType::Telemetry,
+            sequence: 42,
+            event: EventGame::FrameEndEvent,
+            event_code: FRAME_END,
+            game_time: 1000,
+            capture: header.capture,
+            payload_size: 216,
+        }
+    );
+    assert!(header.capture.is_captured());
+
+    let mut buffer = TelemetryBuffer::new(false, 99, 0, 43, 1001);
+    let pair = parser.data(buffer.address());
+
+    assert!(!pair.0);
+    assert!(pair.1.event_type() == EventGame::NotValidEvent);
+}
+
+#[test]
+fn synchronization_service_groupify_between_start_and_end_frames() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let mut sync = Synchronization::new();
+    let tx = sync.get_input_transmitter();
+    sync.set_output_transmitter(out_transmitter);
+
+    let mut cap_sess = Services::new();
+    cap_sess
+        .add_service(ServiceType::SynchronizeInputs, Box::new(sync))
+        .unwrap();
+
+    // inputs before the started event are discarded
+    tx.send(packet(FRAME_START, 0)).unwrap();
+    tx.send(packet(FRAME_END, 1)).unwrap();
+
+    tx.send(packet(STARTED, 2)).unwrap();
+    tx.send(packet(FRAME_START, 3)).unwrap();
+    tx.send(Box::new(Pixels::new(vec![0; 16], 2, 2))).unwrap();
+    tx.send(packet(FRAME_END, 4)).unwrap();
+    tx.send(packet(FRAME_START, 5)).unwrap();
+    tx.send(packet(FRAME_END, 6)).unwrap();
+
+    // inputs after the paused event are discarded
+    tx.send(packet(PAUSED, 7)).unwrap();
+    tx.send(packet(FRAME_START, 8)).unwrap();
+    tx.send(packet(FRAME_END, 9)).unwrap();
+
+    thread::sleep(Duration::from_millis(100));
+    cap_sess.stop_all_services().unwrap();
+
+    let groups: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.iter().collect();
+
+    assert_eq!(groups.len(), 2);
+
+    assert_eq!(groups[0].len(), 3);
+    assert!(groups[0][0].event_type() == EventGame::FrameStartEvent);
+    assert!(matches!(groups[0][1].input_type(), InputType::Image));
+    assert!(groups[0][2].event_type() == EventGame::FrameEndEvent);
+
+    assert_eq!(groups[1].len(), 2);
+    assert_eq!(groups[1][0].header().sequence, 5);
+    assert_eq!(groups[1][1].header().sequence, 6);
+}
+
+#[test]
+fn synchronization_groups_inputs_queued_before_stop() {
+    let (out_transmitter, out_receiver) = unbounded();
+
+    let mut sync = Synchronization::new();
+    let tx = sync.get_input_transmitter();
+
+    // no output transmitter set
+    assert!(matches!(sync.start(), Err(ServiceError::TransmitterNotSet)));
+    sync.set_output_transmitter(out_transmitter);
+
+    // queued before the loop had a chance to receive them
+    tx.send(packet(STARTED, 0)).unwrap();
+    tx.send(packet(FRAME_START, 1)).unwrap();
+    tx.send(packet(FRAME_END, 2)).unwrap();
+
+    sync.start().unwrap();
+    sync.stop().unwrap();
+    sync.join().unwrap();
+    drop(sync);
+
+    let groups: Vec<Vec<Box<dyn Input + Send>>> = out_receiver.iter().collect();
+
+    assert_eq!(groups.len(), 1);
+    assert_eq!(groups[0][0].header().sequence, 1);
+    assert_eq!(groups[0][1].header().sequence, 2);
+}
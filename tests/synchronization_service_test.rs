@@ -1,8 +1,11 @@
+// requires TelemetryEmulation.exe and the win32 capture backends
+#![cfg(windows)]
+
+use control::queue::unbounded;
 use control::screencapture::ScreenCapture;
 use control::synchronization::Synchronization;
 use control::telemetry::{EventGame, SelectGame, Telemetry};
 use control::{ServiceType, Services};
-use std::sync::mpsc::channel;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -13,7 +16,7 @@ use std::io::BufRead;
 
 #[test]
 fn synchronization_service_groupify_first_last_type_check() {
-    let (out_transmitter, out_receiver) = channel();
+    let (out_transmitter, out_receiver) = unbounded();
 
     //game selection
     let sel_game = SelectGame::ETS2;
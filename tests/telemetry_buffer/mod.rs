@@ -0,0 +1,67 @@
+// shared between test crates, not every crate uses every helper
+#![allow(dead_code)]
+
+// builds the shared memory layout read by PacketParser: (is_alive: bool, TelemetryPacket)
+// the packet starts on the next 8 byte boundary after the bool
+use control::screencapture::Pixels;
+use control::telemetry::ets2::TelemetryPacket;
+use control::{CaptureStamp, Input};
+use std::ffi::c_void;
+use std::time::Duration;
+
+pub const FRAME_START: u32 = 1;
+pub const FRAME_END: u32 = 2;
+pub const PAUSED: u32 = 3;
+pub const STARTED: u32 = 4;
+
+pub struct TelemetryBuffer {
+    words: [u64; 31],
+}
+
+impl TelemetryBuffer {
+    pub fn new(is_alive: bool, type_: u32, length: u32, id: u64, time: u64) -> TelemetryBuffer {
+        let mut words = [0u64; 31];
+        words[0] = is_alive as u64;
+        words[1] = (type_ as u64) | ((length as u64) << 32);
+        words[2] = id;
+        words[3] = time;
+
+        TelemetryBuffer { words }
+    }
+
+    // write bytes into the event data, offset is relative to the start of the union
+    pub fn write_data(&mut self, offset: usize, bytes: &[u8]) {
+        let start = 32 + offset;
+        for (i, byte) in bytes.iter().enumerate() {
+            let word = (start + i) / 8;
+            let shift = ((start + i) % 8) * 8;
+            self.words[word] &= !(0xff << shift);
+            self.words[word] |= (*byte as u64) << shift;
+        }
+    }
+
+    pub fn address(&mut self) -> *mut c_void {
+        self.words.as_mut_ptr() as *mut c_void
+    }
+}
+
+// packet as parsed from the shared memory, captured at capture_ms. the capture sequence is
+// id + 1 and the game time is the capture time
+pub fn telemetry_packet(type_: u32, id: u64, capture_ms: u64) -> TelemetryPacket {
+    let mut packet = TelemetryPacket::new();
+    packet.type_ = type_;
+    packet.id = id;
+    packet.time = capture_ms * 1_000;
+    packet.capture = CaptureStamp::new(id + 1, Duration::from_millis(capture_ms));
+    packet
+}
+
+pub fn packet(type_: u32, id: u64, capture_ms: u64) -> Box<dyn Input + Send> {
+    Box::new(telemetry_packet(type_, id, capture_ms))
+}
+
+// one pixel screenshot captured at capture_ms
+pub fn image(sequence: u64, capture_ms: u64) -> Box<dyn Input + Send> {
+    let stamp = CaptureStamp::new(sequence, Duration::from_millis(capture_ms));
+    Box::new(Pixels::new(vec![0; 4], 1, 1).with_capture(stamp))
+}
@@ -0,0 +1,153 @@
+use control::queue::unbounded;
+use control::telemetry::ReplaySpeed;
+use control::userinput::{
+    EvdevEvent, EventFileReader, UserEvent, UserEventKind, UserInput, EVENT_SIZE, EV_ABS, EV_KEY,
+    EV_SYN, SYN_DROPPED,
+};
+use control::{Input, InputType, Process, ServiceError};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// linux/input-event-codes.h
+const KEY_W: u16 = 17;
+const BTN_SOUTH: u16 = 0x130;
+const ABS_X: u16 = 0x00;
+const ABS_Z: u16 = 0x02;
+
+fn event(ms: u64, type_: u16, code: u16, value: i32) -> EvdevEvent {
+    EvdevEvent::new(Duration::from_millis(1_000 + ms), type_, code, value)
+}
+
+fn event_file(test: &str, device: &str, events: &[EvdevEvent]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "control_{}_{}_{}.evdev",
+        test,
+        device,
+        std::process::id()
+    ));
+    let bytes: Vec<u8> = events.iter().flat_map(|event| event.to_bytes()).collect();
+    std::fs::write(&path, bytes).unwrap();
+    path
+}
+
+fn replay(files: &[PathBuf], speed: ReplaySpeed) -> Vec<Box<dyn Input + Send>> {
+    let (tx, rx) = unbounded();
+
+    let mut user_input = UserInput::via_event_files(files, speed);
+    user_input.set_transmitter(tx);
+
+    // stops at the end of the files
+    user_input.start().unwrap();
+    user_input.join().unwrap();
+
+    rx.try_iter().collect()
+}
+
+#[test]
+fn event_files_are_merged_by_kernel_time() {
+    let keyboard = event_file(
+        "merge",
+        "keyboard",
+        &[
+            event(0, EV_KEY, KEY_W, 1),
+            event(0, EV_SYN, 0, 0),
+            event(30, EV_KEY, KEY_W, 0),
+            event(30, EV_SYN, 0, 0),
+        ],
+    );
+    let wheel = event_file(
+        "merge",
+        "wheel",
+        &[
+            event(10, EV_ABS, ABS_X, 32_000),
+            event(10, EV_ABS, ABS_Z, 255),
+            event(10, EV_SYN, 0, 0),
+            event(20, EV_KEY, BTN_SOUTH, 1),
+            event(25, EV_SYN, SYN_DROPPED, 0),
+        ],
+    );
+
+    let inputs = replay(
+        &[keyboard.clone(), wheel.clone()],
+        ReplaySpeed::AsFastAsPossible,
+    );
+    std::fs::remove_file(keyboard).unwrap();
+    std::fs::remove_file(wheel).unwrap();
+
+    let events: Vec<(u16, UserEventKind, u16, i32)> = inputs
+        .iter()
+        .map(|input| {
+            let event = UserEvent::from_input(input.as_ref()).unwrap();
+            (event.device, event.kind, event.code, event.value)
+        })
+        .collect();
+
+    assert_eq!(
+        events,
+        vec![
+            (0, UserEventKind::Key, KEY_W, 1),
+            (1, UserEventKind::Axis, ABS_X, 32_000),
+            (1, UserEventKind::Axis, ABS_Z, 255),
+            (1, UserEventKind::Button, BTN_SOUTH, 1),
+            (1, UserEventKind::Dropped, SYN_DROPPED, 0),
+            (0, UserEventKind::Key, KEY_W, 0),
+        ]
+    );
+
+    for (i, input) in inputs.iter().enumerate() {
+        let header = input.header();
+        assert!(matches!(header.source, InputType::User));
+        assert_eq!(header.sequence, i as u64 + 1);
+        assert_eq!(header.payload_size, EVENT_SIZE as u32);
+    }
+
+    // raw event is kept as the payload
+    let first = UserEvent::from_input(inputs[0].as_ref()).unwrap();
+    assert_eq!(first.evdev(), event(0, EV_KEY, KEY_W, 1));
+    assert_eq!(
+        inputs[0].payload(),
+        &event(0, EV_KEY, KEY_W, 1).to_bytes()[..]
+    );
+}
+
+#[test]
+fn original_speed_keeps_the_recorded_timing() {
+    let gamepad = event_file(
+        "timing",
+        "gamepad",
+        &[
+            event(0, EV_KEY, BTN_SOUTH, 1),
+            event(100, EV_KEY, BTN_SOUTH, 0),
+        ],
+    );
+
+    let started = Instant::now();
+    let inputs = replay(std::slice::from_ref(&gamepad), ReplaySpeed::Original);
+    std::fs::remove_file(gamepad).unwrap();
+
+    assert_eq!(inputs.len(), 2);
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    let gap = inputs[1].capture().time - inputs[0].capture().time;
+    assert!(gap >= Duration::from_millis(95));
+}
+
+#[test]
+fn partial_events_and_missing_files_are_errors() {
+    let mut bytes = event(0, EV_KEY, KEY_W, 1).to_bytes().to_vec();
+    bytes.extend_from_slice(&[0u8; 10]);
+
+    let mut reader = EventFileReader::new(&bytes[..]);
+    assert_eq!(
+        reader.next_event().unwrap(),
+        Some(event(0, EV_KEY, KEY_W, 1))
+    );
+    assert!(reader.next_event().is_err());
+
+    let (tx, _rx) = unbounded();
+    let mut user_input = UserInput::via_event_files(
+        &[std::env::temp_dir().join("control_missing_device.evdev")],
+        ReplaySpeed::AsFastAsPossible,
+    );
+    user_input.set_transmitter(tx);
+    assert!(matches!(user_input.start(), Err(ServiceError::Io(_))));
+}
@@ -0,0 +1,180 @@
+use control::userinput::{EV_ABS, EV_KEY, EV_SYN};
+use control::virtual_controller::{
+    Action, MockBackend, VirtualController, ABS_BRAKE, ABS_STEERING, ABS_THROTTLE, AXIS_MAX,
+    GEAR_BUTTON_FIRST,
+};
+use control::ServiceError;
+
+// linux/input-event-codes.h
+const KEY_E: u16 = 18;
+const BTN_SOUTH: u16 = 0x130;
+
+fn events(backend: &MockBackend) -> Vec<(u16, u16, i32)> {
+    backend
+        .events()
+        .iter()
+        .map(|event| (event.type_, event.code, event.value))
+        .collect()
+}
+
+#[test]
+fn actions_are_emitted_as_one_report_of_changes() {
+    let backend = MockBackend::new();
+    let mut controller = VirtualController::new(Box::new(backend.clone()));
+
+    // nothing changed, the device is not created yet
+    controller.apply(&[Action::Steering(0.0)]).unwrap();
+    assert!(backend.capabilities().is_none());
+
+    controller
+        .apply(&[
+            Action::Steering(-0.5),
+            Action::Throttle(2.0),
+            Action::Gear(1),
+            Action::Key {
+                code: KEY_E,
+                pressed: true,
+            },
+        ])
+        .unwrap();
+
+    let capabilities = backend.capabilities().unwrap();
+    assert!(capabilities.keys.contains(&KEY_E));
+    assert!(capabilities.keys.contains(&BTN_SOUTH));
+
+    // same throttle, shift from first to reverse
+    controller
+        .apply(&[Action::Throttle(1.0), Action::Gear(-1), Action::Brake(0.25)])
+        .unwrap();
+
+    let reports = backend.reports();
+    assert_eq!(reports.len(), 2);
+    for report in reports.iter() {
+        assert_eq!(report.last().unwrap().type_, EV_SYN);
+    }
+
+    assert_eq!(
+        events(&backend),
+        vec![
+            (EV_ABS, ABS_STEERING, -AXIS_MAX / 2 - 1),
+            // clamped
+            (EV_ABS, ABS_THROTTLE, AXIS_MAX),
+            (EV_KEY, GEAR_BUTTON_FIRST, 1),
+            (EV_KEY, KEY_E, 1),
+            (EV_KEY, GEAR_BUTTON_FIRST, 0),
+            (EV_KEY, GEAR_BUTTON_FIRST + 16, 1),
+            (EV_ABS, ABS_BRAKE, (AXIS_MAX as f32 * 0.25).round() as i32),
+        ]
+    );
+}
+
+#[test]
+fn invalid_actions_are_rejected_without_emitting() {
+    let backend = MockBackend::new();
+    let mut controller = VirtualController::new(Box::new(backend.clone()));
+
+    let invalid = [
+        vec![Action::Throttle(1.0), Action::Gear(17)],
+        vec![Action::Steering(f32::NAN)],
+        // gear buttons are only pressed through Gear
+        vec![Action::Key {
+            code: GEAR_BUTTON_FIRST,
+            pressed: true,
+        }],
+        vec![Action::Key {
+            code: 0x3ff,
+            pressed: true,
+        }],
+    ];
+
+    for actions in invalid.iter() {
+        assert!(matches!(
+            controller.apply(actions),
+            Err(ServiceError::InvalidAction(_))
+        ));
+    }
+    assert!(backend.reports().is_empty());
+}
+
+#[test]
+fn closing_releases_every_input() {
+    let backend = MockBackend::new();
+    let mut controller = VirtualController::new(Box::new(backend.clone()));
+
+    controller
+        .apply(&[
+            Action::Steering(1.0),
+            Action::Throttle(0.5),
+            Action::Gear(3),
+            Action::Key {
+                code: BTN_SOUTH,
+                pressed: true,
+            },
+        ])
+        .unwrap();
+
+    drop(controller);
+
+    assert!(backend.is_closed());
+    let reports = backend.reports();
+    assert_eq!(reports.len(), 2);
+
+    let released: Vec<(u16, u16, i32)> = reports[1]
+        .iter()
+        .filter(|event| event.type_ != EV_SYN)
+        .map(|event| (event.type_, event.code, event.value))
+        .collect();
+    assert_eq!(
+        released,
+        vec![
+            (EV_ABS, ABS_STEERING, 0),
+            (EV_ABS, ABS_THROTTLE, 0),
+            (EV_KEY, GEAR_BUTTON_FIRST + 2, 0),
+            (EV_KEY, BTN_SOUTH, 0),
+        ]
+    );
+}
+
+#[test]
+fn failed_emits_are_applied_again() {
+    let backend = MockBackend::new();
+    let mut controller = VirtualController::new(Box::new(backend.clone()));
+
+    backend.set_failing(true);
+    let pressed = [
+        Action::Throttle(1.0),
+        Action::Gear(1),
+        Action::Key {
+            code: KEY_E,
+            pressed: true,
+        },
+    ];
+    assert!(matches!(
+        controller.apply(&pressed),
+        Err(ServiceError::Io(_))
+    ));
+
+    // the failed report does not count as sent, the same actions are emitted again
+    backend.set_failing(false);
+    controller.apply(&pressed).unwrap();
+    assert_eq!(
+        events(&backend),
+        vec![
+            (EV_ABS, ABS_THROTTLE, AXIS_MAX),
+            (EV_KEY, GEAR_BUTTON_FIRST, 1),
+            (EV_KEY, KEY_E, 1),
+        ]
+    );
+}
+
+#[test]
+fn closing_removes_the_device_when_the_release_fails() {
+    let backend = MockBackend::new();
+    let mut controller = VirtualController::new(Box::new(backend.clone()));
+
+    controller.apply(&[Action::Brake(1.0)]).unwrap();
+    backend.set_failing(true);
+
+    assert!(matches!(controller.close(), Err(ServiceError::Io(_))));
+    assert!(backend.is_closed());
+}